@@ -0,0 +1,101 @@
+use crate::{Parse, Rule};
+
+/// An AArch64 condition code, tested against the NZCV flags by `b.cond`,
+/// `csel`, `ccmp` and friends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    EQ, // Equal
+    NE, // Not equal
+    HS, // Unsigned higher or same, also written `cs`
+    LO, // Unsigned lower, also written `cc`
+    MI, // Negative
+    PL, // Positive or zero
+    VS, // Overflow
+    VC, // No overflow
+    HI, // Unsigned higher
+    LS, // Unsigned lower or same
+    GE, // Signed greater than or equal
+    LT, // Signed less than
+    GT, // Signed greater than
+    LE, // Signed less than or equal
+    AL, // Always
+    NV, // Always, like `al`
+}
+
+impl Condition {
+    /// Reads a condition by name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let cond = match name.to_ascii_lowercase().as_str() {
+            "eq" => Condition::EQ,
+            "ne" => Condition::NE,
+            "cs" | "hs" => Condition::HS,
+            "cc" | "lo" => Condition::LO,
+            "mi" => Condition::MI,
+            "pl" => Condition::PL,
+            "vs" => Condition::VS,
+            "vc" => Condition::VC,
+            "hi" => Condition::HI,
+            "ls" => Condition::LS,
+            "ge" => Condition::GE,
+            "lt" => Condition::LT,
+            "gt" => Condition::GT,
+            "le" => Condition::LE,
+            "al" => Condition::AL,
+            "nv" => Condition::NV,
+            _ => return None,
+        };
+        Some(cond)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Condition::EQ => "eq",
+            Condition::NE => "ne",
+            Condition::HS => "hs",
+            Condition::LO => "lo",
+            Condition::MI => "mi",
+            Condition::PL => "pl",
+            Condition::VS => "vs",
+            Condition::VC => "vc",
+            Condition::HI => "hi",
+            Condition::LS => "ls",
+            Condition::GE => "ge",
+            Condition::LT => "lt",
+            Condition::GT => "gt",
+            Condition::LE => "le",
+            Condition::AL => "al",
+            Condition::NV => "nv",
+        }
+    }
+
+    /// The condition that holds exactly when this one doesn't, as used by
+    /// `cset` and `cinc`. `al` and `nv` both always hold, so they stay as
+    /// they are.
+    pub fn invert(&self) -> Self {
+        match self {
+            Condition::EQ => Condition::NE,
+            Condition::NE => Condition::EQ,
+            Condition::HS => Condition::LO,
+            Condition::LO => Condition::HS,
+            Condition::MI => Condition::PL,
+            Condition::PL => Condition::MI,
+            Condition::VS => Condition::VC,
+            Condition::VC => Condition::VS,
+            Condition::HI => Condition::LS,
+            Condition::LS => Condition::HI,
+            Condition::GE => Condition::LT,
+            Condition::LT => Condition::GE,
+            Condition::GT => Condition::LE,
+            Condition::LE => Condition::GT,
+            Condition::AL => Condition::AL,
+            Condition::NV => Condition::NV,
+        }
+    }
+}
+
+impl Parse<'_> for Condition {
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, crate::Err> {
+        debug_assert_eq!(pair.as_rule(), Rule::condition);
+        Ok(Condition::from_name(pair.as_str()).expect("Invalid condition"))
+    }
+}
@@ -0,0 +1,498 @@
+use std::fmt;
+
+use pest::iterators::Pair;
+
+use crate::error::Snippet;
+use crate::expr::Expr;
+use crate::{ArmParserError, Parse, Rule};
+
+/// An assembler directive with its arguments parsed.
+///
+/// Only the directives that gcc emits for plain C code are understood; the
+/// rest are kept as [`Directive::Raw`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Directive<'a> {
+    /// `.text`
+    Text,
+    /// `.data`
+    Data,
+    /// `.bss`
+    Bss,
+    /// `.section name[, "flags"[, @type[, args...]]]`
+    Section {
+        name: &'a str,
+        flags: Option<SectionFlags>,
+        kind: Option<&'a str>,
+        args: Vec<Expr<'a>>,
+    },
+    /// `.align` and `.p2align`, which both take a power of two on AArch64
+    Align {
+        pow2: u32,
+        fill: Option<Expr<'a>>,
+        max: Option<Expr<'a>>,
+    },
+    /// `.balign`, in bytes
+    BAlign {
+        bytes: u64,
+        fill: Option<Expr<'a>>,
+        max: Option<Expr<'a>>,
+    },
+    /// `.ascii`, `.string` and `.asciz`
+    String {
+        zero_terminated: bool,
+        values: Vec<StringLiteral>,
+    },
+    /// `.byte`, `.hword`, `.word`, `.dword` and their synonyms
+    Values { size: u8, values: Vec<Expr<'a>> },
+    /// `.zero`, `.skip` and `.space`
+    Space {
+        size: Expr<'a>,
+        fill: Option<Expr<'a>>,
+    },
+    /// `.type symbol, %kind`
+    Type { symbol: &'a str, kind: SymbolType },
+    /// `.size symbol, expr`
+    Size { symbol: &'a str, size: Expr<'a> },
+    /// `.global` and `.globl`
+    Global(&'a str),
+    /// `.local`
+    Local(&'a str),
+    /// `.weak`
+    Weak(&'a str),
+    /// `.file "name"`, or `.file number "name"` for debug info
+    File {
+        number: Option<u64>,
+        name: StringLiteral,
+    },
+    /// `.ident "comment"`
+    Ident(StringLiteral),
+    /// Any other directive, with its arguments as written
+    Raw { name: &'a str, args: &'a str },
+}
+
+impl<'a> Directive<'a> {
+    /// Every expression among the arguments, in order.
+    pub fn exprs_mut(&mut self) -> Vec<&mut Expr<'a>> {
+        match self {
+            Directive::Section { args, .. } => args.iter_mut().collect(),
+            Directive::Align { fill, max, .. } | Directive::BAlign { fill, max, .. } => {
+                fill.iter_mut().chain(max.iter_mut()).collect()
+            }
+            Directive::Values { values, .. } => values.iter_mut().collect(),
+            Directive::Space { size, fill } => {
+                std::iter::once(size).chain(fill.iter_mut()).collect()
+            }
+            Directive::Size { size, .. } => vec![size],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Flags of a `.section`, e.g. the `"aMS"` in
+/// `.section .rodata.str1.8,"aMS",@progbits,1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SectionFlags {
+    /// `a`
+    pub alloc: bool,
+    /// `w`
+    pub write: bool,
+    /// `x`
+    pub exec: bool,
+    /// `M`
+    pub merge: bool,
+    /// `S`
+    pub strings: bool,
+    /// `G`
+    pub group: bool,
+    /// `T`
+    pub tls: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolType {
+    Function,
+    Object,
+    TlsObject,
+    Common,
+    NoType,
+    GnuIndirectFunction,
+    GnuUniqueObject,
+}
+
+/// The bytes of a string literal, with escape sequences resolved.
+#[derive(Clone, PartialEq, Eq)]
+pub struct StringLiteral(pub Vec<u8>);
+
+impl fmt::Debug for StringLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.0.escape_ascii())
+    }
+}
+
+impl SectionFlags {
+    fn parse(flags: &[u8]) -> Option<Self> {
+        let mut res = SectionFlags::default();
+        for flag in flags {
+            match flag {
+                b'a' => res.alloc = true,
+                b'w' => res.write = true,
+                b'x' => res.exec = true,
+                b'M' => res.merge = true,
+                b'S' => res.strings = true,
+                b'G' => res.group = true,
+                b'T' => res.tls = true,
+                _ => return None,
+            }
+        }
+        Some(res)
+    }
+}
+
+impl SymbolType {
+    fn parse(tag: &str) -> Option<Self> {
+        match &tag[1..] {
+            "function" => Some(SymbolType::Function),
+            "object" => Some(SymbolType::Object),
+            "tls_object" => Some(SymbolType::TlsObject),
+            "common" => Some(SymbolType::Common),
+            "notype" => Some(SymbolType::NoType),
+            "gnu_indirect_function" => Some(SymbolType::GnuIndirectFunction),
+            "gnu_unique_object" => Some(SymbolType::GnuUniqueObject),
+            _ => None,
+        }
+    }
+}
+
+impl Parse<'_> for StringLiteral {
+    fn parse(pair: Pair<Rule>) -> Result<Self, crate::Err> {
+        debug_assert_eq!(pair.as_rule(), Rule::string_lit);
+        let text = pair.as_str();
+        Ok(StringLiteral(unescape(&text[1..text.len() - 1])))
+    }
+}
+
+/// Resolves the escape sequences GAS accepts in string literals.
+fn unescape(s: &str) -> Vec<u8> {
+    let mut res = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes().peekable();
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            res.push(b);
+            continue;
+        }
+        // the grammar never lets a string end in a lone backslash
+        let escaped = match bytes.next().unwrap_or(b'\\') {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'v' => 0x0b,
+            b'a' => 0x07,
+            b'e' => 0x1b,
+            b'x' => {
+                let mut value = 0u8;
+                let mut digits = 0;
+                while let Some(d) = bytes.peek().and_then(|c| (*c as char).to_digit(16)) {
+                    value = value.wrapping_mul(16).wrapping_add(d as u8);
+                    digits += 1;
+                    bytes.next();
+                }
+                if digits == 0 {
+                    b'x'
+                } else {
+                    value
+                }
+            }
+            d @ b'0'..=b'7' => {
+                let mut value = d - b'0';
+                for _ in 0..2 {
+                    match bytes.peek() {
+                        Some(d @ b'0'..=b'7') => {
+                            value = value.wrapping_mul(8).wrapping_add(d - b'0');
+                            bytes.next();
+                        }
+                        _ => break,
+                    }
+                }
+                value
+            }
+            other => other,
+        };
+        res.push(escaped);
+    }
+    res
+}
+
+const KNOWN_DIRECTIVES: &[&str] = &[
+    ".text", ".data", ".bss", ".section", ".align", ".p2align", ".balign", ".ascii", ".string",
+    ".asciz", ".byte", ".hword", ".short", ".half", ".word", ".long", ".int", ".dword", ".quad",
+    ".xword", ".zero", ".skip", ".space", ".type", ".size", ".global", ".globl", ".local", ".weak",
+    ".file", ".ident",
+];
+
+/// Directives whose arguments may be separated by blanks, as in
+/// `.file 1 "main.c"` or `.loc 1 5 7`.
+const BLANK_SEPARATED: &[&str] = &[".file", ".loc"];
+
+/// The arguments of a directive, checked one by one against what the
+/// directive expects.
+struct Args<'a> {
+    name: &'a str,
+    directive: Pair<'a, Rule>,
+    args: Vec<Pair<'a, Rule>>,
+}
+
+impl<'a> Args<'a> {
+    fn error(&self, pair: Option<&Pair<'a, Rule>>, message: &str) -> ArmParserError {
+        ArmParserError::InvalidDirective {
+            directive: self.name.to_string(),
+            message: message.to_string(),
+            at: Snippet::of(pair.unwrap_or(&self.directive)),
+        }
+    }
+
+    fn count(&self, min: usize, max: usize) -> Result<(), ArmParserError> {
+        let n = self.args.len();
+        if n < min {
+            Err(self.error(None, &format!("expected at least {min} argument(s)")))
+        } else if n > max {
+            Err(self.error(self.args.get(max), "too many arguments"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The argument at `i`, unwrapped from its `dir_arg`. Arguments left
+    /// empty, like the fill in `.p2align 4,,15`, are `None`.
+    fn get(&self, i: usize) -> Option<Pair<'a, Rule>> {
+        self.args
+            .get(i)
+            .and_then(|arg| arg.clone().into_inner().next())
+    }
+
+    fn expr(&self, i: usize) -> Result<Expr<'a>, ArmParserError> {
+        match self.get(i) {
+            Some(arg) if arg.as_rule() == Rule::expr => Expr::parse(arg),
+            arg => Err(self.error(arg.as_ref(), "expected an expression")),
+        }
+    }
+
+    fn opt_expr(&self, i: usize) -> Result<Option<Expr<'a>>, ArmParserError> {
+        self.get(i).map(|_| self.expr(i)).transpose()
+    }
+
+    fn exprs(&self) -> Result<Vec<Expr<'a>>, ArmParserError> {
+        (0..self.args.len()).map(|i| self.expr(i)).collect()
+    }
+
+    fn int(&self, i: usize) -> Result<u64, ArmParserError> {
+        let value = self.expr(i)?.as_int();
+        value
+            .and_then(|n| u64::try_from(n).ok())
+            .ok_or_else(|| self.error(self.args.get(i), "expected a non-negative integer"))
+    }
+
+    fn symbol(&self, i: usize) -> Result<&'a str, ArmParserError> {
+        match self.get(i) {
+            Some(arg) if arg.as_rule() == Rule::expr => {
+                let arg_str = arg.as_str();
+                Expr::parse(arg.clone())?
+                    .as_symbol()
+                    .map(|_| arg_str)
+                    .ok_or_else(|| self.error(Some(&arg), "expected a symbol"))
+            }
+            arg => Err(self.error(arg.as_ref(), "expected a symbol")),
+        }
+    }
+
+    fn string(&self, i: usize) -> Result<StringLiteral, ArmParserError> {
+        match self.get(i) {
+            Some(arg) if arg.as_rule() == Rule::string_lit => StringLiteral::parse(arg),
+            arg => Err(self.error(arg.as_ref(), "expected a string")),
+        }
+    }
+
+    fn strings(&self) -> Result<Vec<StringLiteral>, ArmParserError> {
+        (0..self.args.len()).map(|i| self.string(i)).collect()
+    }
+
+    fn type_tag(&self, i: usize) -> Option<Pair<'a, Rule>> {
+        self.get(i).filter(|arg| arg.as_rule() == Rule::type_tag)
+    }
+}
+
+impl<'a> Parse<'a> for Directive<'a> {
+    fn parse(pair: Pair<'a, Rule>) -> Result<Self, crate::Err> {
+        debug_assert_eq!(pair.as_rule(), Rule::directive);
+        let mut inner = pair.clone().into_inner();
+        let name = inner.next().expect("No inner pair").as_str();
+        let rest = inner.next();
+        let raw = rest.as_ref().map_or("", |rest| rest.as_str().trim_end());
+        let lower = name.to_ascii_lowercase();
+        let mut args = match rest {
+            Some(rest) if rest.as_rule() == Rule::directive_args => rest.into_inner().collect(),
+            Some(rest) if KNOWN_DIRECTIVES.contains(&lower.as_str()) => {
+                return Err(ArmParserError::InvalidDirective {
+                    directive: name.to_string(),
+                    message: "malformed arguments".to_string(),
+                    at: Snippet::of(&rest),
+                });
+            }
+            _ => Vec::new(),
+        };
+        if let Some(i) = args.iter().position(|arg| arg.as_rule() == Rule::blank_sep) {
+            if !BLANK_SEPARATED.contains(&lower.as_str()) {
+                return Err(ArmParserError::InvalidDirective {
+                    directive: name.to_string(),
+                    message: "expected `,` between arguments".to_string(),
+                    at: Snippet::of(&args[i + 1]),
+                });
+            }
+        }
+        args.retain(|arg| arg.as_rule() != Rule::blank_sep);
+        let args = Args {
+            name,
+            directive: pair,
+            args,
+        };
+
+        match lower.as_str() {
+            ".text" => {
+                args.count(0, 0)?;
+                Ok(Directive::Text)
+            }
+            ".data" => {
+                args.count(0, 0)?;
+                Ok(Directive::Data)
+            }
+            ".bss" => {
+                args.count(0, 0)?;
+                Ok(Directive::Bss)
+            }
+            ".section" => {
+                args.count(1, usize::MAX)?;
+                let name = args.args[0].as_str();
+                let flags = args
+                    .get(1)
+                    .map(|_| {
+                        let flags = args.string(1)?;
+                        SectionFlags::parse(&flags.0)
+                            .ok_or_else(|| args.error(args.args.get(1), "unknown section flag"))
+                    })
+                    .transpose()?;
+                let kind = args.type_tag(2).map(|tag| &tag.as_str()[1..]);
+                let rest = if kind.is_some() { 3 } else { 2 };
+                let rest = (rest..args.args.len())
+                    .map(|i| args.expr(i))
+                    .collect::<Result<_, _>>()?;
+                Ok(Directive::Section {
+                    name,
+                    flags,
+                    kind,
+                    args: rest,
+                })
+            }
+            ".align" | ".p2align" => {
+                args.count(1, 3)?;
+                let pow2 = u32::try_from(args.int(0)?)
+                    .map_err(|_| args.error(args.args.first(), "alignment too large"))?;
+                Ok(Directive::Align {
+                    pow2,
+                    fill: args.opt_expr(1)?,
+                    max: args.opt_expr(2)?,
+                })
+            }
+            ".balign" => {
+                args.count(1, 3)?;
+                Ok(Directive::BAlign {
+                    bytes: args.int(0)?,
+                    fill: args.opt_expr(1)?,
+                    max: args.opt_expr(2)?,
+                })
+            }
+            ".ascii" => Ok(Directive::String {
+                zero_terminated: false,
+                values: args.strings()?,
+            }),
+            ".string" | ".asciz" => Ok(Directive::String {
+                zero_terminated: true,
+                values: args.strings()?,
+            }),
+            ".byte" => Ok(Directive::Values {
+                size: 1,
+                values: args.exprs()?,
+            }),
+            ".hword" | ".short" | ".half" => Ok(Directive::Values {
+                size: 2,
+                values: args.exprs()?,
+            }),
+            ".word" | ".long" | ".int" => Ok(Directive::Values {
+                size: 4,
+                values: args.exprs()?,
+            }),
+            ".dword" | ".quad" | ".xword" => Ok(Directive::Values {
+                size: 8,
+                values: args.exprs()?,
+            }),
+            ".zero" => {
+                args.count(1, 1)?;
+                Ok(Directive::Space {
+                    size: args.expr(0)?,
+                    fill: None,
+                })
+            }
+            ".skip" | ".space" => {
+                args.count(1, 2)?;
+                Ok(Directive::Space {
+                    size: args.expr(0)?,
+                    fill: args.opt_expr(1)?,
+                })
+            }
+            ".type" => {
+                args.count(2, 2)?;
+                let tag = args
+                    .type_tag(1)
+                    .ok_or_else(|| args.error(args.args.get(1), "expected a symbol type"))?;
+                let kind = SymbolType::parse(tag.as_str())
+                    .ok_or_else(|| args.error(Some(&tag), "unknown symbol type"))?;
+                Ok(Directive::Type {
+                    symbol: args.symbol(0)?,
+                    kind,
+                })
+            }
+            ".size" => {
+                args.count(2, 2)?;
+                Ok(Directive::Size {
+                    symbol: args.symbol(0)?,
+                    size: args.expr(1)?,
+                })
+            }
+            ".global" | ".globl" => {
+                args.count(1, 1)?;
+                Ok(Directive::Global(args.symbol(0)?))
+            }
+            ".local" => {
+                args.count(1, 1)?;
+                Ok(Directive::Local(args.symbol(0)?))
+            }
+            ".weak" => {
+                args.count(1, 1)?;
+                Ok(Directive::Weak(args.symbol(0)?))
+            }
+            ".file" => {
+                args.count(1, 2)?;
+                let (number, name) = match args.args.len() {
+                    1 => (None, args.string(0)?),
+                    _ => (Some(args.int(0)?), args.string(1)?),
+                };
+                Ok(Directive::File { number, name })
+            }
+            ".ident" => {
+                args.count(1, 1)?;
+                Ok(Directive::Ident(args.string(0)?))
+            }
+            _ => Ok(Directive::Raw { name, args: raw }),
+        }
+    }
+}
@@ -1,32 +1,304 @@
-use std::error::Error;
+use std::fmt::Write;
 
-use crate::Rule;
+use pest::error::{ErrorVariant, InputLocation, LineColLocation};
+use pest::iterators::Pair;
+
+use crate::register::suggest_register;
+use crate::{Location, Rule};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ArmParserError {
-    #[error("Pest error: {0}")]
-    PestError(#[from] pest::error::Error<Rule>),
-    #[error("ParseInt error: {0}")]
-    ParseIntError(#[from] std::num::ParseIntError),
-    #[error("Invalid register range")]
-    InvalidRegisterRange,
-    #[error("Invalid register type")]
-    InvalidRegisterType,
-    #[error("Invalid opcode")]
-    InvalidOpcode,
+    #[error("syntax error: {message}")]
+    PestError { message: String, at: Box<Snippet> },
+    #[error("invalid integer `{text}`: {source}")]
+    ParseIntError {
+        text: String,
+        source: std::num::ParseIntError,
+        at: Box<Snippet>,
+    },
+    #[error("invalid register range `{text}`")]
+    InvalidRegisterRange { text: String, at: Box<Snippet> },
+    #[error("invalid register `{text}`")]
+    InvalidRegister {
+        text: String,
+        suggestion: Option<String>,
+        at: Box<Snippet>,
+    },
+    #[error("invalid `{directive}` directive: {message}")]
+    InvalidDirective {
+        directive: String,
+        message: String,
+        at: Box<Snippet>,
+    },
+    #[error("invalid operand `{text}`: {message}")]
+    InvalidOperand {
+        text: String,
+        message: String,
+        at: Box<Snippet>,
+    },
+    #[error("unknown mnemonic `{text}`")]
+    InvalidOpcode {
+        text: String,
+        suggestion: Option<String>,
+        at: Box<Snippet>,
+    },
+}
+
+/// The piece of source an error points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub file: Option<String>,
+    pub loc: Location,
+    /// Full text of the source line `loc` starts on
+    pub line_text: String,
+    /// Line of the macro call or repeat block the text was expanded from
+    pub expanded_from: Option<usize>,
+}
+
+impl Snippet {
+    pub fn of(pair: &Pair<Rule>) -> Box<Self> {
+        Box::new(Snippet {
+            file: None,
+            loc: Location::of(pair),
+            line_text: pair.as_span().start_pos().line_of().trim_end().to_string(),
+            expanded_from: None,
+        })
+    }
+}
+
+impl From<pest::error::Error<Rule>> for ArmParserError {
+    fn from(e: pest::error::Error<Rule>) -> Self {
+        let (line, col) = match e.line_col {
+            LineColLocation::Pos(pos) | LineColLocation::Span(pos, _) => pos,
+        };
+        let (start, end) = match e.location {
+            InputLocation::Pos(pos) => (pos, pos),
+            InputLocation::Span(span) => span,
+        };
+        let line_text = e.line().trim_end();
+
+        // A word where a register was expected is most likely a misspelled
+        // register, so report it as one.
+        if let ErrorVariant::ParsingError { positives, .. } = &e.variant {
+            let wants_register = positives.iter().any(|r| {
+                matches!(
+                    r,
+                    Rule::register | Rule::register_item | Rule::offset | Rule::shift_by
+                )
+            });
+            let word = line_text[col_offset(line_text, col)..]
+                .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .next()
+                .unwrap_or("");
+            if let Some(suggestion) = wants_register.then(|| suggest_register(word)).flatten() {
+                return ArmParserError::InvalidRegister {
+                    text: word.to_string(),
+                    suggestion: Some(suggestion),
+                    at: Box::new(Snippet {
+                        file: None,
+                        loc: Location {
+                            line,
+                            col,
+                            start,
+                            end: start + word.len(),
+                        },
+                        line_text: line_text.to_string(),
+                        expanded_from: None,
+                    }),
+                };
+            }
+        }
+
+        ArmParserError::PestError {
+            message: e
+                .clone()
+                .renamed_rules(rule_name)
+                .variant
+                .message()
+                .into_owned(),
+            at: Box::new(Snippet {
+                file: None,
+                loc: Location {
+                    line,
+                    col,
+                    start,
+                    end,
+                },
+                line_text: line_text.to_string(),
+                expanded_from: None,
+            }),
+        }
+    }
+}
+
+/// Name of a grammar rule as it should read in an error message.
+fn rule_name(rule: &Rule) -> String {
+    match rule {
+        Rule::half_reg
+        | Rule::full_reg
+        | Rule::named_reg
+        | Rule::float_reg
+        | Rule::byte8
+        | Rule::float16
+        | Rule::float32
+        | Rule::double64
+        | Rule::quad128
+        | Rule::vector_reg => "register".to_string(),
+        Rule::operation => "instruction".to_string(),
+        Rule::indirect => "memory operand".to_string(),
+        Rule::reglist | Rule::register_items => "register list".to_string(),
+        Rule::proc_load => "relocation operand".to_string(),
+        Rule::EOI => "end of input".to_string(),
+        rule => format!("{rule:?}").replace('_', " "),
+    }
+}
+
+impl ArmParserError {
+    pub fn at(&self) -> &Snippet {
+        match self {
+            ArmParserError::PestError { at, .. }
+            | ArmParserError::ParseIntError { at, .. }
+            | ArmParserError::InvalidRegisterRange { at, .. }
+            | ArmParserError::InvalidRegister { at, .. }
+            | ArmParserError::InvalidDirective { at, .. }
+            | ArmParserError::InvalidOperand { at, .. }
+            | ArmParserError::InvalidOpcode { at, .. } => at,
+        }
+    }
+
+    pub(crate) fn at_mut(&mut self) -> &mut Snippet {
+        match self {
+            ArmParserError::PestError { at, .. }
+            | ArmParserError::ParseIntError { at, .. }
+            | ArmParserError::InvalidRegisterRange { at, .. }
+            | ArmParserError::InvalidRegister { at, .. }
+            | ArmParserError::InvalidDirective { at, .. }
+            | ArmParserError::InvalidOperand { at, .. }
+            | ArmParserError::InvalidOpcode { at, .. } => at,
+        }
+    }
+
+    pub fn suggestion(&self) -> Option<&str> {
+        match self {
+            ArmParserError::InvalidRegister { suggestion, .. }
+            | ArmParserError::InvalidOpcode { suggestion, .. } => suggestion.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Attaches the name of the file being parsed.
+    pub fn with_file(mut self, file: &str) -> Self {
+        self.at_mut().file = Some(file.to_string());
+        self
+    }
+
+    /// Renders the error the way compilers do, with the offending source
+    /// line and a caret underline below it:
+    ///
+    /// ```text
+    /// error: unknown mnemonic `addd`
+    ///  --> main.s:3:5
+    ///   |
+    /// 3 |     addd x0, x1, x2
+    ///   |     ^^^^ help: did you mean `add`?
+    /// ```
+    pub fn render(&self) -> String {
+        let at = self.at();
+        let line_no = at.loc.line.to_string();
+        let gutter = " ".repeat(line_no.len());
+        let mut out = String::new();
+        let _ = writeln!(out, "error: {self}");
+        let _ = writeln!(
+            out,
+            "{gutter}--> {}:{}:{}",
+            at.file.as_deref().unwrap_or("<input>"),
+            at.loc.line,
+            at.loc.col
+        );
+        let _ = writeln!(out, "{gutter} |");
+        let _ = writeln!(out, "{line_no} | {}", at.line_text);
+
+        // keep tabs from the source line so the caret lines up under it
+        let indent = at
+            .line_text
+            .chars()
+            .take(at.loc.col - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let from = col_offset(&at.line_text, at.loc.col);
+        let to = (from + at.loc.end.saturating_sub(at.loc.start)).min(at.line_text.len());
+        let width = at
+            .line_text
+            .get(from..to)
+            .map_or(0, |s| s.trim_end().chars().count())
+            .max(1);
+        let _ = write!(out, "{gutter} | {indent}{}", "^".repeat(width));
+        if let Some(suggestion) = self.suggestion() {
+            let _ = write!(out, " help: did you mean `{suggestion}`?");
+        }
+        out.push('\n');
+        if let Some(line) = at.expanded_from {
+            let _ = writeln!(out, "{gutter} = note: in expansion at line {line}");
+        }
+        out
+    }
+}
+
+/// Byte offset of the 1-based character column `col` in `text`.
+fn col_offset(text: &str, col: usize) -> usize {
+    text.char_indices()
+        .nth(col - 1)
+        .map_or(text.len(), |(i, _)| i)
+}
+
+/// Picks the candidate closest to `text` by edit distance, if any is close
+/// enough to be a plausible typo.
+pub fn suggest<'c>(text: &str, candidates: impl IntoIterator<Item = &'c str>) -> Option<String> {
+    let text = text.to_ascii_lowercase();
+    let limit = (text.len() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|c| (edit_distance(&text, c), c))
+        .filter(|&(d, _)| d <= limit)
+        .min()
+        .map(|(_, c)| c.to_string())
+}
+
+/// Edit distance counting insertions, deletions, substitutions and swaps of
+/// two adjacent characters, so that `wrz` is one edit away from `wzr`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j - 1] + cost)
+                .min(d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
 }
 
 pub trait PrintError {
-    fn print_error(self, loc: &str) -> Self;
+    fn print_error(self, file: &str) -> Self;
 }
 
-impl<T, E> PrintError for Result<T, E>
-where
-    E: Error,
-{
-    fn print_error(self, loc: &str) -> Self {
-        self.inspect_err(|e| {
-            eprintln!("Error at {}: {}", loc, e);
+impl<T> PrintError for Result<T, ArmParserError> {
+    fn print_error(self, file: &str) -> Self {
+        self.map_err(|e| {
+            let e = e.with_file(file);
+            eprint!("{}", e.render());
+            e
         })
     }
 }
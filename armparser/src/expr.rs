@@ -0,0 +1,257 @@
+use std::borrow::Cow;
+
+use crate::error::Snippet;
+use crate::immediate::{parse_char_literal, parse_int_literal};
+use crate::local_label::LocalRef;
+use crate::{Parse, Rule};
+
+/// An assembler expression over symbols and integers, such as the `.-main`
+/// in `.size main, .-main`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<'a> {
+    Int(i64),
+    Symbol(Cow<'a, str>),
+    /// `.`, the address of the current location
+    Here,
+    /// `1b` or `2f`, until [`resolve_local_labels`] gives the label a name
+    ///
+    /// [`resolve_local_labels`]: crate::local_label::resolve_local_labels
+    LocalRef(LocalRef),
+    Neg(Box<Expr<'a>>),
+    Binary(Box<Expr<'a>>, BinOp, Box<Expr<'a>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Expr<'_> {
+    /// Folds the expression to a constant, if it doesn't refer to any symbol.
+    pub fn as_int(&self) -> Option<i64> {
+        self.eval(&|_| None)
+    }
+
+    /// Folds the expression to a constant, taking the value of symbols from
+    /// `symbol`.
+    pub fn eval(&self, symbol: &dyn Fn(&str) -> Option<i64>) -> Option<i64> {
+        match self {
+            Expr::Int(n) => Some(*n),
+            Expr::Symbol(s) => symbol(s),
+            Expr::Here | Expr::LocalRef(_) => None,
+            Expr::Neg(e) => e.eval(symbol)?.checked_neg(),
+            Expr::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval(symbol)?, rhs.eval(symbol)?);
+                match op {
+                    BinOp::Add => lhs.checked_add(rhs),
+                    BinOp::Sub => lhs.checked_sub(rhs),
+                    BinOp::Mul => lhs.checked_mul(rhs),
+                    BinOp::Div => lhs.checked_div(rhs),
+                }
+            }
+        }
+    }
+
+    /// The symbol name, if the expression is nothing but a symbol.
+    pub fn as_symbol(&self) -> Option<&str> {
+        match self {
+            Expr::Symbol(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Splits `sym+8` or `sym-8` into the symbol and its constant addend.
+    pub fn as_symbol_offset(&self) -> Option<(&str, i64)> {
+        match self {
+            Expr::Symbol(s) => Some((s, 0)),
+            Expr::Binary(lhs, op @ (BinOp::Add | BinOp::Sub), rhs) => {
+                let (sym, addend) = lhs.as_symbol_offset()?;
+                let rhs = rhs.as_int()?;
+                match op {
+                    BinOp::Add => Some((sym, addend.checked_add(rhs)?)),
+                    _ => Some((sym, addend.checked_sub(rhs)?)),
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Expr<'_> {
+    /// The character of an `expr` that is nothing but a character literal,
+    /// like `'a'`.
+    pub(crate) fn char_literal(pair: &pest::iterators::Pair<Rule>) -> Option<char> {
+        debug_assert_eq!(pair.as_rule(), Rule::expr);
+        let atom = (pair.clone().into_inner().flatten()).find(|p| p.as_rule() == Rule::char_val)?;
+        (atom.as_str() == pair.as_str()).then(|| parse_char_literal(atom.as_str()))
+    }
+}
+
+impl<'a> Parse<'a> for Expr<'a> {
+    fn parse(pair: pest::iterators::Pair<'a, Rule>) -> Result<Self, crate::Err> {
+        debug_assert_eq!(pair.as_rule(), Rule::expr);
+        parse_binary(pair, parse_product)
+    }
+}
+
+fn parse_product<'a>(pair: pest::iterators::Pair<'a, Rule>) -> Result<Expr<'a>, crate::Err> {
+    debug_assert_eq!(pair.as_rule(), Rule::expr_prod);
+    parse_binary(pair, parse_term)
+}
+
+/// Folds a left-associative chain of operands and operators.
+fn parse_binary<'a>(
+    pair: pest::iterators::Pair<'a, Rule>,
+    operand: fn(pest::iterators::Pair<'a, Rule>) -> Result<Expr<'a>, crate::Err>,
+) -> Result<Expr<'a>, crate::Err> {
+    let mut inner = pair.into_inner();
+    let mut expr = operand(inner.next().expect("No inner pair"))?;
+    while let Some(op) = inner.next() {
+        let op = match op.as_str() {
+            "+" => BinOp::Add,
+            "-" => BinOp::Sub,
+            "*" => BinOp::Mul,
+            "/" => BinOp::Div,
+            _ => unreachable!("Invalid expression operator"),
+        };
+        let rhs = operand(inner.next().expect("No inner pair"))?;
+        expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+    }
+    Ok(expr)
+}
+
+fn parse_term<'a>(pair: pest::iterators::Pair<'a, Rule>) -> Result<Expr<'a>, crate::Err> {
+    debug_assert_eq!(pair.as_rule(), Rule::expr_term);
+    let mut inner = pair.into_inner();
+    let mut first = inner.next().expect("No inner pair");
+    let negated = first.as_rule() == Rule::expr_neg;
+    if negated {
+        first = inner.next().expect("No inner pair");
+    }
+    let atom = first.into_inner().next().expect("No inner pair");
+    let expr = match atom.as_rule() {
+        Rule::hex_val | Rule::bin_val | Rule::uint => Expr::Int(parse_atom_int(&atom)?),
+        Rule::char_val => Expr::Int(i64::from(u32::from(parse_char_literal(atom.as_str())))),
+        Rule::here => Expr::Here,
+        Rule::label_target => Expr::Symbol(Cow::Borrowed(atom.as_str())),
+        Rule::local_ref => Expr::LocalRef(LocalRef::parse(atom)?),
+        Rule::expr => Expr::parse(atom)?,
+        _ => unreachable!("Invalid expression atom"),
+    };
+    Ok(match expr {
+        Expr::Int(n) if negated => Expr::Int(-n),
+        expr if negated => Expr::Neg(Box::new(expr)),
+        expr => expr,
+    })
+}
+
+fn parse_atom_int(pair: &pest::iterators::Pair<Rule>) -> Result<i64, crate::Err> {
+    parse_int_literal(pair.as_str()).map_err(|source| crate::Err::ParseIntError {
+        text: pair.as_str().to_string(),
+        source,
+        at: Snippet::of(pair),
+    })
+}
+
+macro_rules! define_reloc_modifiers {
+    ($($name:ident = $value:expr),* $(,)?) => {
+        /// A relocation operator, the `lo12` of `:lo12:sym`.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum RelocModifier {
+            $($name,)*
+        }
+
+        impl RelocModifier {
+            /// Reads an operator by the name GNU as gives it.
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($value => Some(RelocModifier::$name),)*
+                    _ => None,
+                }
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(RelocModifier::$name => $value,)*
+                }
+            }
+        }
+    };
+}
+
+define_reloc_modifiers! {
+    Lo12 = "lo12",
+    PgHi21 = "pg_hi21",
+    PgHi21Nc = "pg_hi21_nc",
+    Got = "got",
+    GotLo12 = "got_lo12",
+    GotPageLo15 = "gotpage_lo15",
+    GotOffLo15 = "gotoff_lo15",
+    GotPrelG1 = "got_prel_g1",
+    GotPrelG0Nc = "got_prel_g0_nc",
+    AbsG0 = "abs_g0",
+    AbsG0Nc = "abs_g0_nc",
+    AbsG0S = "abs_g0_s",
+    AbsG1 = "abs_g1",
+    AbsG1Nc = "abs_g1_nc",
+    AbsG1S = "abs_g1_s",
+    AbsG2 = "abs_g2",
+    AbsG2Nc = "abs_g2_nc",
+    AbsG2S = "abs_g2_s",
+    AbsG3 = "abs_g3",
+    PrelG0 = "prel_g0",
+    PrelG0Nc = "prel_g0_nc",
+    PrelG1 = "prel_g1",
+    PrelG1Nc = "prel_g1_nc",
+    PrelG2 = "prel_g2",
+    PrelG2Nc = "prel_g2_nc",
+    PrelG3 = "prel_g3",
+    TlsGd = "tlsgd",
+    TlsGdLo12 = "tlsgd_lo12",
+    TlsGdG1 = "tlsgd_g1",
+    TlsGdG0Nc = "tlsgd_g0_nc",
+    TlsDesc = "tlsdesc",
+    TlsDescLo12 = "tlsdesc_lo12",
+    TlsDescOffG1 = "tlsdesc_off_g1",
+    TlsDescOffG0Nc = "tlsdesc_off_g0_nc",
+    TlsLdm = "tlsldm",
+    TlsLdmLo12Nc = "tlsldm_lo12_nc",
+    TlsLdG1 = "tlsld_g1",
+    TlsLdG0Nc = "tlsld_g0_nc",
+    GotTprel = "gottprel",
+    GotTprelLo12 = "gottprel_lo12",
+    GotTprelG1 = "gottprel_g1",
+    GotTprelG0Nc = "gottprel_g0_nc",
+    TprelG2 = "tprel_g2",
+    TprelG1 = "tprel_g1",
+    TprelG1Nc = "tprel_g1_nc",
+    TprelG0 = "tprel_g0",
+    TprelG0Nc = "tprel_g0_nc",
+    TprelHi12 = "tprel_hi12",
+    TprelLo12 = "tprel_lo12",
+    TprelLo12Nc = "tprel_lo12_nc",
+    DtprelG2 = "dtprel_g2",
+    DtprelG1 = "dtprel_g1",
+    DtprelG1Nc = "dtprel_g1_nc",
+    DtprelG0 = "dtprel_g0",
+    DtprelG0Nc = "dtprel_g0_nc",
+    DtprelHi12 = "dtprel_hi12",
+    DtprelLo12 = "dtprel_lo12",
+    DtprelLo12Nc = "dtprel_lo12_nc",
+}
+
+impl Parse<'_> for RelocModifier {
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, crate::Err> {
+        debug_assert_eq!(pair.as_rule(), Rule::mode);
+        RelocModifier::from_name(&pair.as_str().to_ascii_lowercase()).ok_or_else(|| {
+            crate::Err::InvalidOperand {
+                text: pair.as_str().to_string(),
+                message: "unknown relocation operator".to_string(),
+                at: Snippet::of(&pair),
+            }
+        })
+    }
+}
@@ -0,0 +1,54 @@
+//! Assembly text that was produced from some other input, such as expanded
+//! macros or a disassembly, along with the input each of its lines came from.
+
+use crate::error::ArmParserError;
+use crate::Location;
+
+/// Generated text, ready for [`parse_asm`](crate::parse_asm), with an origin
+/// for each of its lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generated<O> {
+    pub text: String,
+    /// One per line of `text`, in order
+    pub origins: Vec<O>,
+}
+
+/// An origin that is a line of source text, which errors can be moved to.
+pub trait SourceOrigin {
+    /// Bytes a generated line puts before the text it took from the source
+    const LEAD: usize = 0;
+
+    /// Where the text the line was made from starts in the source
+    fn loc(&self) -> Location;
+
+    /// The macro call or repeat block the line was expanded from, if any
+    fn expanded_from(&self) -> Option<Location> {
+        None
+    }
+}
+
+impl<O> Generated<O> {
+    /// Origin of a 1-based line of `text`.
+    pub fn origin(&self, line: usize) -> Option<&O> {
+        self.origins.get(line.checked_sub(1)?)
+    }
+}
+
+impl<O: SourceOrigin> Generated<O> {
+    /// Moves an error from the generated text to the source line it came
+    /// from.
+    pub fn remap(&self, mut e: ArmParserError) -> ArmParserError {
+        let at = e.at_mut();
+        if let Some(origin) = self.origin(at.loc.line) {
+            // positions within the line carry over as they are
+            let line_start = self.text[..at.loc.start].rfind('\n').map_or(0, |i| i + 1);
+            let text_start = line_start + O::LEAD;
+            let loc = origin.loc();
+            at.loc.start = loc.start + at.loc.start.saturating_sub(text_start);
+            at.loc.end = loc.start + at.loc.end.saturating_sub(text_start);
+            at.loc.line = loc.line;
+            at.expanded_from = origin.expanded_from().map(|loc| loc.line);
+        }
+        e
+    }
+}
@@ -0,0 +1,155 @@
+use std::num::ParseIntError;
+
+use crate::error::Snippet;
+use crate::{Parse, Rule};
+
+/// An immediate in the form it was written: `#16`, `#-0x10` and `#0b1010`
+/// are integers, `#1.5e0` is a float and `#'a'` a character.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Immediate {
+    Int(i64),
+    /// Only taken by floating-point instructions, as in `fmov d0, #1.5`
+    Float(f64),
+    /// Stands for its character code
+    Char(char),
+}
+
+impl Immediate {
+    /// The integer value, with characters taken as their code.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Immediate::Int(n) => Some(*n),
+            Immediate::Char(c) => Some(i64::from(u32::from(*c))),
+            Immediate::Float(_) => None,
+        }
+    }
+
+    /// The floating-point value. Integers convert only when they do so
+    /// exactly, as `fmov d0, #2` is the same as `fmov d0, #2.0`.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Immediate::Float(f) => Some(*f),
+            Immediate::Int(n) if n.unsigned_abs() <= 1 << f64::MANTISSA_DIGITS => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    /// The integer value as `T`, if it is in range, e.g. `to_int::<u16>()`
+    /// for the payload of `movk`.
+    pub fn to_int<T: TryFrom<i64>>(&self) -> Option<T> {
+        T::try_from(self.as_int()?).ok()
+    }
+
+    /// Whether the value is an integer that fits a `bits` wide unsigned
+    /// field, like the 12 bits of `add x0, x1, #imm`.
+    pub fn fits_unsigned(&self, bits: u32) -> bool {
+        self.as_int()
+            .is_some_and(|n| n >= 0 && (bits >= 64 || n < 1 << bits))
+    }
+
+    /// Whether the value is an integer that fits a `bits` wide two's
+    /// complement field, like the 9 bits of `ldr x0, [x1, #imm]!`.
+    pub fn fits_signed(&self, bits: u32) -> bool {
+        self.as_int().is_some_and(|n| match bits {
+            0 => n == 0,
+            64.. => true,
+            _ => (-(1 << (bits - 1))..1 << (bits - 1)).contains(&n),
+        })
+    }
+
+    /// The 8-bit encoding `fmov` uses for its immediate, if the value has
+    /// one. Those are ±n/16 × 2^r with n in 16..=31 and r in -3..=4.
+    pub fn to_fp8(&self) -> Option<u8> {
+        let value = self.as_float()?;
+        (0..=u8::MAX).find(|&imm8| fp8_value(imm8) == value)
+    }
+}
+
+/// The value of an 8-bit `fmov` immediate, as the manual's `VFPExpandImm`
+/// computes it.
+fn fp8_value(imm8: u8) -> f64 {
+    let sign = if imm8 & 0x80 != 0 { -1.0 } else { 1.0 };
+    let b = imm8 & 0x40 != 0;
+    let cd = i32::from((imm8 >> 4) & 0x3);
+    let exp = if b { cd - 3 } else { cd + 1 };
+    let frac = f64::from(imm8 & 0xf);
+    sign * (1.0 + frac / 16.0) * 2f64.powi(exp)
+}
+
+/// Parses an integer literal: decimal, `0x`/`0X` hex or `0b`/`0B` binary,
+/// with an optional minus sign. Like gas, any 64-bit pattern is accepted and
+/// wraps, so `0xffffffffffffffff` is -1.
+pub(crate) fn parse_int_literal(text: &str) -> Result<i64, ParseIntError> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let prefixed = |lower: &str, upper: &str| {
+        digits
+            .strip_prefix(lower)
+            .or_else(|| digits.strip_prefix(upper))
+    };
+    let (radix, digits) = if let Some(hex) = prefixed("0x", "0X") {
+        (16, hex)
+    } else if let Some(bin) = prefixed("0b", "0B") {
+        (2, bin)
+    } else {
+        (10, digits)
+    };
+    let value = u64::from_str_radix(digits, radix)? as i64;
+    Ok(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+/// Parses a character literal like `'a'` or `'\n'`.
+pub(crate) fn parse_char_literal(text: &str) -> char {
+    let inner = &text[1..text.len() - 1];
+    let mut chars = inner.chars();
+    match (chars.next(), chars.next()) {
+        (Some('\\'), Some(escaped)) => match escaped {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            c => c,
+        },
+        (Some(c), _) => c,
+        (None, _) => unreachable!("Empty character literal"),
+    }
+}
+
+impl Parse<'_> for Immediate {
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, crate::Err> {
+        debug_assert!(matches!(
+            pair.as_rule(),
+            Rule::immediate | Rule::address | Rule::float_val
+        ));
+        let literal = pair.as_str();
+        let text = literal.trim_start_matches(['#', '=']).trim_start();
+        if pair.as_rule() == Rule::float_val {
+            let value = text.parse().expect("Invalid float literal");
+            return Ok(Immediate::Float(value));
+        }
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, text),
+        };
+        if unsigned.starts_with('\'') {
+            let c = parse_char_literal(unsigned);
+            return Ok(match negative {
+                true => Immediate::Int(-i64::from(u32::from(c))),
+                false => Immediate::Char(c),
+            });
+        }
+        parse_int_literal(text)
+            .map(Immediate::Int)
+            .map_err(|source| crate::Err::ParseIntError {
+                text: literal.to_string(),
+                source,
+                at: Snippet::of(&pair),
+            })
+    }
+}
@@ -1,17 +1,119 @@
+pub mod condition;
+pub mod directive;
 pub mod error;
+pub mod expr;
+pub mod generated;
+pub mod immediate;
+pub mod local_label;
+pub mod objdump;
 pub mod opcode;
+pub mod preprocess;
+pub mod print;
 pub mod register;
 #[cfg(test)]
 pub mod tests;
-use error::ArmParserError;
-use opcode::{Opcode, OPCODES};
+use std::borrow::Cow;
+
+use condition::Condition;
+use directive::Directive;
+use error::{ArmParserError, Snippet};
+use expr::{Expr, RelocModifier};
+use immediate::Immediate;
+use opcode::{Mnemonic, Opcode};
 use pest::Parser;
 use pest_derive::Parser;
-use register::Register;
+use register::{suggest_register, Register, RegisterType};
+
+/// Parses a whole assembly file into its lines, in source order.
+///
+/// Blank lines and comments produce no [`SourceLine`]; every label,
+/// directive and instruction is returned together with its [`Location`].
+/// Parsing stops at the first bad line, see [`parse_asm_recovering`] to get
+/// all of them.
+/// Assembler macros aren't expanded here, run [`preprocess::preprocess`]
+/// first for sources that use them.
+pub fn parse_asm<'i>(src: &'i str) -> Result<Vec<SourceLine<'i>>, ArmParserError> {
+    file_pairs(src)?.map(parse_line).collect()
+}
 
-pub fn parse_asm<'i>(src: &'i str) -> Result<Vec<Line<'i>>, ArmParserError> {
-    let res = ARM64Parser::parse(Rule::line, &src)?;
-    todo!();
+/// Same as [`parse_asm`], but errors name `filename` when they are rendered.
+pub fn parse_asm_file<'i>(
+    filename: &str,
+    src: &'i str,
+) -> Result<Vec<SourceLine<'i>>, ArmParserError> {
+    parse_asm(src).map_err(|e| e.with_file(filename))
+}
+
+/// Parses a whole assembly file without stopping at bad lines.
+///
+/// Each line that fails to parse shows up as a [`Line::Error`] in the
+/// returned lines and adds one diagnostic to the returned errors, in source
+/// order.
+pub fn parse_asm_recovering<'i>(src: &'i str) -> (Vec<SourceLine<'i>>, Vec<ArmParserError>) {
+    let pairs = match file_pairs(src) {
+        Ok(pairs) => pairs,
+        Err(e) => return (Vec::new(), vec![e]),
+    };
+    let mut errors = Vec::new();
+    let lines = pairs
+        .map(|pair| {
+            let loc = Location::of(&pair);
+            let text = pair.as_str().trim_end();
+            parse_line(pair).unwrap_or_else(|e| {
+                errors.push(e);
+                SourceLine {
+                    line: Line::Error(text),
+                    loc,
+                }
+            })
+        })
+        .collect();
+    (lines, errors)
+}
+
+/// The `line` and `bad_line` pairs of `src`, in source order.
+fn file_pairs(
+    src: &str,
+) -> Result<impl Iterator<Item = pest::iterators::Pair<'_, Rule>>, ArmParserError> {
+    let file = ARM64Parser::parse(Rule::file, src)?
+        .next()
+        .expect("No file pair");
+    Ok(file
+        .into_inner()
+        .filter(|pair| matches!(pair.as_rule(), Rule::line | Rule::bad_line)))
+}
+
+fn parse_line(pair: pest::iterators::Pair<Rule>) -> Result<SourceLine, ArmParserError> {
+    let loc = Location::of(&pair);
+    match pair.as_rule() {
+        Rule::line => Ok(SourceLine {
+            line: Line::parse(pair)?,
+            loc,
+        }),
+        Rule::bad_line => Err(diagnose_bad_line(&pair)),
+        _ => unreachable!("Invalid line"),
+    }
+}
+
+/// Finds out why `bad_line` doesn't parse by parsing it again on its own.
+fn diagnose_bad_line(pair: &pest::iterators::Pair<Rule>) -> ArmParserError {
+    let loc = Location::of(pair);
+    let mut e = match ARM64Parser::parse(Rule::strict_line, pair.as_str()) {
+        Err(e) => ArmParserError::from(e),
+        Ok(_) => ArmParserError::PestError {
+            message: "unexpected input".to_string(),
+            at: Snippet::of(pair),
+        },
+    };
+    // positions are relative to the start of `bad_line`, which is always
+    // on the same source line
+    let at = e.at_mut();
+    at.loc.line = loc.line;
+    at.loc.col += loc.col - 1;
+    at.loc.start += loc.start;
+    at.loc.end += loc.start;
+    at.line_text = pair.as_span().start_pos().line_of().trim_end().to_string();
+    e
 }
 
 #[derive(Parser)]
@@ -27,17 +129,37 @@ pub enum ShiftType {
     RRX, // Rotate Right with Extend
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Immediate(i64);
+/// How an index register is widened to 64 bits before it is added, as in
+/// the `sxtw` of `add x0, x1, w2, sxtw #2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtendType {
+    UXTB, // Unsigned extend byte
+    UXTH, // Unsigned extend halfword
+    UXTW, // Unsigned extend word
+    UXTX, // Unsigned extend doubleword
+    SXTB, // Signed extend byte
+    SXTH, // Signed extend halfword
+    SXTW, // Signed extend word
+    SXTX, // Signed extend doubleword
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ShiftedRegister {
     // The register to be shifted
-    reg: Register,
+    pub reg: Register,
     // The type of shift operation to perform (LSL, LSR, ASR, ROR, RRX)
-    shift_type: ShiftType,
+    pub shift_type: ShiftType,
     // Optional amount to shift by - can be immediate value or register
-    shift_amount: Option<ShiftAmount>,
+    pub shift_amount: Option<ShiftAmount>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedRegister {
+    // The register to be extended
+    pub reg: Register,
+    pub extend_type: ExtendType,
+    // Optional left shift applied after extending
+    pub shift_amount: Option<Immediate>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -51,19 +173,36 @@ pub enum ShiftAmount {
 #[derive(Debug, Clone, PartialEq)]
 pub struct RegisterList {
     pub regs: Vec<Register>,
+    /// Lane of every register the list accesses, as in `{v0.s, v1.s}[1]`
+    pub lane: Option<u8>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct RegisterRange {
-    start: Register,
-    end: Register,
+    pub start: Register,
+    pub end: Register,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Indirect<'a> {
-    base: Register,
-    offset: Option<Offset<'a>>,
-    writeback: bool,
+    pub base: Register,
+    pub mode: AddressMode<'a>,
+}
+
+/// How a memory operand forms its address from the base register, and
+/// whether the base is updated afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddressMode<'a> {
+    /// `[x0]`
+    Base,
+    /// `[x0, #8]` or `[x0, x1, lsl #3]`, base left unchanged
+    Offset(Offset<'a>),
+    /// `[x0, #-16]!`, base updated before the access
+    PreIndex(Immediate),
+    /// `[x0], #16`, base updated after the access
+    PostIndex(Immediate),
+    /// `[x0], x1`, base updated by a register after the access
+    PostIndexRegister(Register),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -71,13 +210,15 @@ pub enum Offset<'a> {
     Immediate(Immediate),
     Register(Register),
     ShiftedRegister(ShiftedRegister),
+    ExtendedRegister(ExtendedRegister),
     ProcLoad(ProcLoad<'a>),
 }
 
+/// A symbol expression under a relocation operator, as in `:lo12:.LC2+8`.
 #[derive(Debug, Clone, PartialEq)]
 pub struct ProcLoad<'a> {
-    mode: &'a str,
-    target: &'a str,
+    pub modifier: RelocModifier,
+    pub expr: Expr<'a>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -85,25 +226,78 @@ pub enum Operand<'a> {
     Register(Register),
     Immediate(Immediate),
     Address(Immediate),
-    LabelTarget(&'a str),
+    LabelTarget(Cow<'a, str>),
     Indirect(Indirect<'a>),
     RegisterList(RegisterList),
     ShiftedRegister(ShiftedRegister),
+    ExtendedRegister(ExtendedRegister),
     ProcLoad(ProcLoad<'a>),
+    /// Condition operand of `csel`, `cset`, `ccmp` and friends
+    Condition(Condition),
+    /// A value that is neither a plain integer nor a plain symbol, as in
+    /// `.LC0+8` or `.-.L3`
+    Expr(Expr<'a>),
+    ShiftedImmediate(ShiftedImmediate<'a>),
+}
+
+/// An immediate shifted left, as in `movk x0, #:abs_g1_nc:sym, lsl #16`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShiftedImmediate<'a> {
+    // Either an immediate or a relocation operand
+    pub value: Box<Operand<'a>>,
+    pub lsl: Immediate,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Instruction<'a> {
-    opcode: Opcode,
-    operands: Vec<Operand<'a>>,
+    pub opcode: Opcode,
+    /// Condition suffix of the mnemonic, as in `b.eq`
+    pub condition: Option<Condition>,
+    pub operands: Vec<Operand<'a>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Line<'a> {
-    Label(&'a str),
-    Directive(&'a str),
+    Label(Cow<'a, str>),
+    Directive(Directive<'a>),
     Instruction(Instruction<'a>),
+    /// A line that failed to parse, only produced by [`parse_asm_recovering`]
+    Error(&'a str),
+}
+
+/// Position of a parsed item in the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column, counted in characters
+    pub col: usize,
+    /// Byte offset of the first character
+    pub start: usize,
+    /// Byte offset one past the last character
+    pub end: usize,
+}
+
+impl Location {
+    pub fn of(pair: &pest::iterators::Pair<Rule>) -> Self {
+        let (line, col) = pair.line_col();
+        let span = pair.as_span();
+        Location {
+            line,
+            col,
+            start: span.start(),
+            end: span.end(),
+        }
+    }
+}
+
+/// A [`Line`] together with the place it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine<'a> {
+    pub line: Line<'a>,
+    pub loc: Location,
 }
+
 type Err = crate::error::ArmParserError;
 impl ARM64Parser {}
 
@@ -113,26 +307,6 @@ pub trait Parse<'a> {
         Self: Sized;
 }
 
-impl Parse<'_> for Immediate {
-    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, Err> {
-        debug_assert_eq!(pair.as_rule(), Rule::immediate);
-        fn parse_int(s: &str) -> Result<Immediate, Err> {
-            if s.starts_with("0x") {
-                Ok(Immediate(i64::from_str_radix(&s[2..], 16)?))
-            } else {
-                Ok(Immediate(s.parse()?))
-            }
-        }
-
-        let literal = pair.as_str();
-        let first_char = literal.chars().next().expect("No first char");
-        match first_char {
-            '#' => parse_int(&literal[1..]),
-            _ => parse_int(literal),
-        }
-    }
-}
-
 impl Parse<'_> for ShiftType {
     fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, Err> {
         debug_assert_eq!(pair.as_rule(), Rule::shift_type);
@@ -147,6 +321,38 @@ impl Parse<'_> for ShiftType {
     }
 }
 
+impl Parse<'_> for ExtendType {
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, Err> {
+        debug_assert_eq!(pair.as_rule(), Rule::extend_type);
+        match pair.as_str() {
+            "uxtb" => Ok(ExtendType::UXTB),
+            "uxth" => Ok(ExtendType::UXTH),
+            "uxtw" => Ok(ExtendType::UXTW),
+            "uxtx" => Ok(ExtendType::UXTX),
+            "sxtb" => Ok(ExtendType::SXTB),
+            "sxth" => Ok(ExtendType::SXTH),
+            "sxtw" => Ok(ExtendType::SXTW),
+            "sxtx" => Ok(ExtendType::SXTX),
+            _ => unreachable!("Invalid extend type"),
+        }
+    }
+}
+
+impl Parse<'_> for ExtendedRegister {
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, Err> {
+        debug_assert_eq!(pair.as_rule(), Rule::extended_register);
+        let mut inner = pair.into_inner();
+        let reg = Register::parse(inner.next().expect("No inner pair"))?;
+        let extend_type = ExtendType::parse(inner.next().expect("No inner pair"))?;
+        let shift_amount = inner.next().map(Immediate::parse).transpose()?;
+        Ok(ExtendedRegister {
+            reg,
+            extend_type,
+            shift_amount,
+        })
+    }
+}
+
 impl Parse<'_> for ShiftedRegister {
     fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, Err> {
         debug_assert_eq!(pair.as_rule(), Rule::shifted_register);
@@ -178,9 +384,33 @@ impl<'a> Parse<'a> for Offset<'a> {
 
         let inner = pair.into_inner().next().expect("No inner pair");
         match inner.as_rule() {
-            Rule::immediate => Ok(Offset::Immediate(Immediate::parse(inner)?)),
+            Rule::value => match parse_value(inner.clone())? {
+                Value::Imm(Immediate::Float(_)) => Err(Err::InvalidOperand {
+                    text: inner.as_str().to_string(),
+                    message: "offset must be an integer".to_string(),
+                    at: Snippet::of(&inner),
+                }),
+                Value::Imm(imm) => Ok(Offset::Immediate(imm)),
+                // symbols only make sense under a relocation operator, so
+                // this is likely a misspelled register
+                Value::Symbol(sym) if suggest_register(&sym).is_some() => {
+                    Err(Err::InvalidRegister {
+                        text: sym.to_string(),
+                        suggestion: suggest_register(&sym),
+                        at: Snippet::of(&inner),
+                    })
+                }
+                Value::Symbol(_) | Value::Expr(_) => Err(Err::InvalidOperand {
+                    text: inner.as_str().to_string(),
+                    message: "offset must be a constant or use a relocation operator".to_string(),
+                    at: Snippet::of(&inner),
+                }),
+            },
             Rule::register => Ok(Offset::Register(Register::parse(inner)?)),
             Rule::shifted_register => Ok(Offset::ShiftedRegister(ShiftedRegister::parse(inner)?)),
+            Rule::extended_register => {
+                Ok(Offset::ExtendedRegister(ExtendedRegister::parse(inner)?))
+            }
             Rule::proc_load => Ok(Offset::ProcLoad(ProcLoad::parse(inner)?)),
             _ => unreachable!("Invalid offset"),
         }
@@ -190,62 +420,131 @@ impl<'a> Parse<'a> for Offset<'a> {
 impl<'a> Parse<'a> for Indirect<'a> {
     fn parse(pair: pest::iterators::Pair<'a, Rule>) -> Result<Self, Err> {
         debug_assert_eq!(pair.as_rule(), Rule::indirect);
-        let mut inner = pair.into_inner();
+        let mut inner = pair.clone().into_inner().peekable();
         let base = Register::parse(inner.next().expect("No inner pair"))?;
         let offset = inner
-            .next()
-            .map(|pair| -> Result<Offset, Err> { Ok(Offset::parse(pair)?) })
+            .next_if(|p| p.as_rule() == Rule::offset)
+            .map(Offset::parse)
             .transpose()?;
-        let writeback = inner.next().is_some();
-        Ok(Indirect {
-            base,
-            offset,
-            writeback,
-        })
+        let invalid = |message: &str| Err::InvalidOperand {
+            text: pair.as_str().to_string(),
+            message: message.to_string(),
+            at: Snippet::of(&pair),
+        };
+        let mode = match (offset, inner.next()) {
+            (None, None) => AddressMode::Base,
+            (Some(offset), None) => AddressMode::Offset(offset),
+            (Some(Offset::Immediate(imm)), Some(p)) if p.as_rule() == Rule::writeback => {
+                AddressMode::PreIndex(imm)
+            }
+            (_, Some(p)) if p.as_rule() == Rule::writeback => {
+                return Err(invalid("writeback needs an immediate offset"))
+            }
+            (Some(_), Some(_)) => {
+                return Err(invalid("post-index can't be combined with an offset"))
+            }
+            (None, Some(p)) => {
+                let inner = p.into_inner().next().expect("No inner pair");
+                match inner.as_rule() {
+                    Rule::immediate => AddressMode::PostIndex(Immediate::parse(inner)?),
+                    Rule::register => AddressMode::PostIndexRegister(Register::parse(inner)?),
+                    _ => unreachable!("Invalid post-index amount"),
+                }
+            }
+        };
+        Ok(Indirect { base, mode })
     }
 }
 impl<'a> Parse<'a> for ProcLoad<'a> {
     fn parse(pair: pest::iterators::Pair<'a, Rule>) -> Result<Self, Err> {
         debug_assert_eq!(pair.as_rule(), Rule::proc_load);
         let mut inner = pair.into_inner();
-        let mode = inner.next().expect("No inner pair").as_str();
-        let target = inner.next().expect("No inner pair").as_str();
-        Ok(ProcLoad { mode, target })
+        let modifier = RelocModifier::parse(inner.next().expect("No inner pair"))?;
+        let expr = Expr::parse(inner.next().expect("No inner pair"))?;
+        Ok(ProcLoad { modifier, expr })
     }
 }
 
+/// What a `value` operand folds to.
+enum Value<'a> {
+    Imm(Immediate),
+    Symbol(Cow<'a, str>),
+    Expr(Expr<'a>),
+}
+
+fn parse_value(pair: pest::iterators::Pair<Rule>) -> Result<Value, Err> {
+    debug_assert_eq!(pair.as_rule(), Rule::value);
+    let inner = pair.into_inner().next().expect("No inner pair");
+    if inner.as_rule() == Rule::float_val {
+        return Ok(Value::Imm(Immediate::parse(inner)?));
+    }
+    // keep `#'a'` a character rather than folding it to its code
+    if let Some(c) = Expr::char_literal(&inner) {
+        return Ok(Value::Imm(Immediate::Char(c)));
+    }
+    let expr = Expr::parse(inner)?;
+    Ok(match expr {
+        Expr::Symbol(sym) => Value::Symbol(sym),
+        expr => match expr.as_int() {
+            Some(n) => Value::Imm(Immediate::Int(n)),
+            None => Value::Expr(expr),
+        },
+    })
+}
+
 impl Parse<'_> for RegisterList {
     fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, Err> {
         debug_assert_eq!(pair.as_rule(), Rule::reglist);
         let mut registers = Vec::with_capacity(30);
+        let mut inner = pair.clone().into_inner();
+        let items = inner.next().expect("No inner pair");
 
-        pair.into_inner()
-            .map(|pair| {
-                debug_assert_eq!(pair.as_rule(), Rule::register_item);
-                pair.into_inner().next().expect("No inner pair")
-            })
-            .for_each(|pair| match pair.as_rule() {
-                Rule::register => {
-                    registers.push(Register::parse(pair).expect("Invalid register"));
-                }
+        for item in items.into_inner() {
+            debug_assert_eq!(item.as_rule(), Rule::register_item);
+            let pair = item.into_inner().next().expect("No inner pair");
+            match pair.as_rule() {
+                Rule::register => registers.push(Register::parse(pair)?),
                 Rule::register_range => {
-                    let range = RegisterRange::parse(pair).expect("Invalid register range");
-                    let list = range.to_reg_list().expect("Invalid register range");
-                    registers.extend(list.regs);
+                    registers.extend(RegisterRange::parse(pair)?.to_reg_list().regs)
                 }
                 _ => unreachable!("Invalid register list"),
-            });
+            }
+        }
         registers.shrink_to_fit();
-        Ok(RegisterList { regs: registers })
+        let lane = inner.next().map(|l| register::lane_index(&l)).transpose()?;
+        if let Some(lane) = lane {
+            let message = registers.iter().find_map(|reg| match reg.reg_type {
+                RegisterType::Element(size) if u32::from(lane) < 128 / size.bits() => None,
+                RegisterType::Element(_) => Some("lane index out of range"),
+                _ => Some("a lane needs registers like `v0.s`"),
+            });
+            if let Some(message) = message {
+                return Err(Err::InvalidOperand {
+                    text: pair.as_str().to_string(),
+                    message: message.to_string(),
+                    at: Snippet::of(&pair),
+                });
+            }
+        }
+        Ok(RegisterList {
+            regs: registers,
+            lane,
+        })
     }
 }
 
 impl Parse<'_> for RegisterRange {
     fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, Err> {
         debug_assert_eq!(pair.as_rule(), Rule::register_range);
-        let mut inner = pair.into_inner();
+        let mut inner = pair.clone().into_inner();
         let start = Register::parse(inner.next().expect("No inner pair"))?;
         let end = Register::parse(inner.next().expect("No inner pair"))?;
+        if start.reg_type != end.reg_type || start.reg_num > end.reg_num {
+            return Err(Err::InvalidRegisterRange {
+                text: pair.as_str().to_string(),
+                at: Snippet::of(&pair),
+            });
+        }
         Ok(RegisterRange { start, end })
     }
 }
@@ -256,34 +555,70 @@ impl<'a> Parse<'a> for Operand<'a> {
         let inner = pair.into_inner().next().expect("No inner pair");
         match inner.as_rule() {
             Rule::register => Ok(Operand::Register(Register::parse(inner)?)),
-            Rule::immediate => Ok(Operand::Immediate(Immediate::parse(inner)?)),
+            Rule::value => match parse_value(inner)? {
+                Value::Imm(imm) => Ok(Operand::Immediate(imm)),
+                Value::Symbol(sym) => Ok(Operand::LabelTarget(sym)),
+                Value::Expr(expr) => Ok(Operand::Expr(expr)),
+            },
+            Rule::shifted_imm => {
+                let mut inner = inner.into_inner();
+                let value = inner.next().expect("No inner pair");
+                let value = match value.as_rule() {
+                    Rule::proc_load => Operand::ProcLoad(ProcLoad::parse(value)?),
+                    _ => match parse_value(value)? {
+                        Value::Imm(imm) => Operand::Immediate(imm),
+                        Value::Symbol(sym) => Operand::LabelTarget(sym),
+                        Value::Expr(expr) => Operand::Expr(expr),
+                    },
+                };
+                let lsl = Immediate::parse(inner.next().expect("No inner pair"))?;
+                Ok(Operand::ShiftedImmediate(ShiftedImmediate {
+                    value: Box::new(value),
+                    lsl,
+                }))
+            }
             Rule::address => Ok(Operand::Address(Immediate::parse(inner)?)),
-            Rule::label_target => Ok(Operand::LabelTarget(inner.as_str())),
             Rule::indirect => Ok(Operand::Indirect(Indirect::parse(inner)?)),
             Rule::reglist => Ok(Operand::RegisterList(RegisterList::parse(inner)?)),
             Rule::shifted_register => Ok(Operand::ShiftedRegister(ShiftedRegister::parse(inner)?)),
+            Rule::extended_register => {
+                Ok(Operand::ExtendedRegister(ExtendedRegister::parse(inner)?))
+            }
             Rule::proc_load => Ok(Operand::ProcLoad(ProcLoad::parse(inner)?)),
+            Rule::condition => Ok(Operand::Condition(Condition::parse(inner)?)),
             _ => unreachable!("Invalid operand"),
         }
     }
 }
-impl Parse<'_> for Opcode {
-    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, Err> {
-        debug_assert_eq!(pair.as_rule(), Rule::opcode);
-        let opcode = OPCODES.get(pair.as_str()).ok_or(Err::InvalidOpcode)?;
-        Ok(Opcode(opcode.0))
-    }
-}
 
 impl<'a> Parse<'a> for Instruction<'a> {
     fn parse(pair: pest::iterators::Pair<'a, Rule>) -> Result<Self, Err> {
         debug_assert_eq!(pair.as_rule(), Rule::operation);
         let mut inner = pair.into_inner();
-        let opcode = Opcode::parse(inner.next().expect("No inner pair"))?;
+        let Mnemonic { opcode, condition } = Mnemonic::parse(inner.next().expect("No inner pair"))?;
+        let count = inner.len();
         let operands = inner
-            .map(|pair| -> Result<Operand, Err> { Ok(Operand::parse(pair)?) })
+            .enumerate()
+            .map(|(i, pair)| {
+                // `b lo` and `bl cc` branch to symbols that happen to be
+                // spelled like conditions
+                let cond = pair.clone().into_inner().next();
+                match cond {
+                    Some(cond)
+                        if cond.as_rule() == Rule::condition
+                            && !(opcode.takes_condition() && i + 1 == count) =>
+                    {
+                        Ok(Operand::LabelTarget(Cow::Borrowed(cond.as_str())))
+                    }
+                    _ => Operand::parse(pair),
+                }
+            })
             .collect::<Result<Vec<Operand>, Err>>()?;
-        Ok(Instruction { opcode, operands })
+        Ok(Instruction {
+            opcode,
+            condition,
+            operands,
+        })
     }
 }
 
@@ -298,27 +633,23 @@ where
         debug_assert_eq!(pair.as_rule(), Rule::line);
         let inner = pair.into_inner().next().expect("No inner pair");
         match inner.as_rule() {
-            Rule::directive => Ok(Line::Directive(inner.as_str())),
+            Rule::directive => Ok(Line::Directive(Directive::parse(inner)?)),
             Rule::operation => Ok(Line::Instruction(Instruction::parse(inner)?)),
-            Rule::label => Ok(Line::Label(inner.as_str())),
-            r => {
-                println!("{:?}", r);
-                unreachable!("invalid Line")
+            Rule::label => {
+                let name = inner.into_inner().next().expect("No inner pair");
+                Ok(Line::Label(Cow::Borrowed(name.as_str())))
             }
+            _ => unreachable!("invalid Line"),
         }
     }
 }
 
 impl RegisterRange {
-    fn to_reg_list(&self) -> Result<RegisterList, Err> {
-        let mut registers = Vec::with_capacity(16);
-        if self.start.reg_type != self.end.reg_type {
-            return Err(Err::InvalidRegisterRange);
-        }
+    fn to_reg_list(&self) -> RegisterList {
         let reg_type = self.start.reg_type;
-        for i in self.start.reg_num..=self.end.reg_num {
-            registers.push(Register::new(reg_type, i));
-        }
-        Ok(RegisterList { regs: registers })
+        let regs = (self.start.reg_num..=self.end.reg_num)
+            .map(|i| Register::new(reg_type, i))
+            .collect();
+        RegisterList { regs, lane: None }
     }
 }
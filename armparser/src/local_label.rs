@@ -0,0 +1,173 @@
+//! GNU numeric local labels. `1:` may be defined any number of times, and
+//! `1b` and `1f` refer to the closest definition before and after the use.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::error::{ArmParserError, Snippet};
+use crate::expr::Expr;
+use crate::{AddressMode, Line, Location, Offset, Operand, Parse, Rule, SourceLine};
+
+/// A reference to a numeric local label, like `1b` or `2f`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalRef {
+    pub number: u32,
+    /// Whether it refers to the next definition (`f`) rather than the
+    /// previous one (`b`)
+    pub forward: bool,
+}
+
+impl Parse<'_> for LocalRef {
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, crate::Err> {
+        debug_assert_eq!(pair.as_rule(), Rule::local_ref);
+        let (digits, direction) = pair.as_str().split_at(pair.as_str().len() - 1);
+        let number = digits
+            .parse()
+            .map_err(|source| ArmParserError::ParseIntError {
+                text: pair.as_str().to_string(),
+                source,
+                at: Snippet::of(&pair),
+            })?;
+        Ok(LocalRef {
+            number,
+            forward: direction == "f",
+        })
+    }
+}
+
+/// Name [`resolve_local_labels`] gives to the `instance`-th definition of a
+/// numeric label, counting from 0.
+pub fn local_label_name(number: u32, instance: usize) -> String {
+    format!(".L{number}.{instance}")
+}
+
+/// Gives each definition of a numeric local label a unique name and points
+/// every reference at the definition it means, so that `1:` ... `b 1b`
+/// becomes `.L1.0:` ... `b .L1.0`. `src` is the text the lines were parsed
+/// from.
+pub fn resolve_local_labels(src: &str, lines: &mut [SourceLine]) -> Result<(), ArmParserError> {
+    let mut defined = HashMap::<u32, usize>::new();
+    for line in lines.iter() {
+        if let Some(number) = local_number(line) {
+            *defined.entry(number).or_default() += 1;
+        }
+    }
+
+    let mut seen = HashMap::<u32, usize>::new();
+    for line in lines.iter_mut() {
+        if let Some(number) = local_number(line) {
+            let count = seen.entry(number).or_default();
+            line.line = Line::Label(Cow::Owned(local_label_name(number, *count)));
+            *count += 1;
+            continue;
+        }
+        let mut name = |r: LocalRef| {
+            let seen = seen.get(&r.number).copied().unwrap_or(0);
+            let instance = match r.forward {
+                true => Some(seen).filter(|&i| i < defined.get(&r.number).copied().unwrap_or(0)),
+                false => seen.checked_sub(1),
+            };
+            instance
+                .map(|i| local_label_name(r.number, i))
+                .ok_or_else(|| {
+                    let (suffix, place) = if r.forward {
+                        ('f', "after")
+                    } else {
+                        ('b', "before")
+                    };
+                    error(
+                        src,
+                        line.loc,
+                        format!("{}{suffix}", r.number),
+                        format!("no `{}:` label {place} it", r.number),
+                    )
+                })
+        };
+        match &mut line.line {
+            Line::Instruction(inst) => {
+                for operand in &mut inst.operands {
+                    rewrite_operand(operand, &mut name)?;
+                }
+            }
+            Line::Directive(directive) => {
+                for expr in directive.exprs_mut() {
+                    rewrite_expr(expr, &mut name)?;
+                }
+            }
+            Line::Label(_) | Line::Error(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// The number of a numeric label definition like `1:`.
+fn local_number(line: &SourceLine) -> Option<u32> {
+    match &line.line {
+        Line::Label(name) if name.bytes().all(|b| b.is_ascii_digit()) => name.parse().ok(),
+        _ => None,
+    }
+}
+
+type Namer<'n> = dyn FnMut(LocalRef) -> Result<String, ArmParserError> + 'n;
+
+fn rewrite_operand(operand: &mut Operand, name: &mut Namer) -> Result<(), ArmParserError> {
+    match operand {
+        Operand::Expr(expr) => {
+            rewrite_expr(expr, name)?;
+            // `b 1b` ends up as a plain branch to a label
+            if let Expr::Symbol(sym) = expr {
+                *operand = Operand::LabelTarget(sym.clone());
+            }
+        }
+        Operand::ProcLoad(load)
+        | Operand::Indirect(crate::Indirect {
+            mode: AddressMode::Offset(Offset::ProcLoad(load)),
+            ..
+        }) => rewrite_expr(&mut load.expr, name)?,
+        Operand::ShiftedImmediate(shifted) => rewrite_operand(&mut shifted.value, name)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn rewrite_expr(expr: &mut Expr, name: &mut Namer) -> Result<(), ArmParserError> {
+    match expr {
+        Expr::LocalRef(r) => *expr = Expr::Symbol(Cow::Owned(name(*r)?)),
+        Expr::Neg(e) => rewrite_expr(e, name)?,
+        Expr::Binary(lhs, _, rhs) => {
+            rewrite_expr(lhs, name)?;
+            rewrite_expr(rhs, name)?;
+        }
+        Expr::Int(_) | Expr::Symbol(_) | Expr::Here => {}
+    }
+    Ok(())
+}
+
+/// An unresolved reference `text` on the line at `loc`, with the caret on
+/// its first occurrence in the line.
+fn error(src: &str, loc: Location, text: String, message: String) -> ArmParserError {
+    let line_start = src[..loc.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = src[loc.start..]
+        .find('\n')
+        .map_or(src.len(), |i| loc.start + i);
+    let line_text = &src[line_start..line_end];
+    let loc = match line_text.find(&text) {
+        Some(offset) => Location {
+            col: line_text[..offset].chars().count() + 1,
+            start: line_start + offset,
+            end: line_start + offset + text.len(),
+            ..loc
+        },
+        None => loc,
+    };
+    ArmParserError::InvalidOperand {
+        text,
+        message,
+        at: Box::new(Snippet {
+            file: None,
+            loc,
+            line_text: line_text.trim_end().to_string(),
+            expanded_from: None,
+        }),
+    }
+}
@@ -0,0 +1,203 @@
+//! Input from `objdump -d` listings, for when there is a binary to work from
+//! rather than compiler output:
+//!
+//! ```text
+//! 0000000000400580 <main>:
+//!   400580:    d10083ff     sub    sp, sp, #0x20
+//!   400584:    97ffffeb     bl     400530 <puts@plt>
+//! ```
+//!
+//! Symbol headers become labels and branch targets become the symbol named
+//! in their `<sym+0x10>` comment, so that the listing reads as assembly.
+
+use crate::error::ArmParserError;
+use crate::generated::{Generated, SourceOrigin};
+use crate::{parse_asm, Location, SourceLine};
+
+/// Where a line of converted text came from in the listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Origin {
+    /// The listing line, from the start of the instruction text
+    pub loc: Location,
+    /// Address of the instruction or symbol. Section headers have none.
+    pub address: Option<u64>,
+    /// The instruction word as the listing shows it
+    pub encoding: Option<u32>,
+}
+
+impl SourceOrigin for Origin {
+    // the converted line is the listing's text behind a tab
+    const LEAD: usize = 1;
+
+    fn loc(&self) -> Location {
+        self.loc
+    }
+}
+
+/// A listing converted to assembly text.
+pub type Listing = Generated<Origin>;
+
+/// A parsed line of a listing with the address and encoding it was listed
+/// with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListingLine<'a> {
+    pub address: Option<u64>,
+    pub encoding: Option<u32>,
+    /// The line, located in [`Listing::text`]
+    pub line: SourceLine<'a>,
+}
+
+impl Listing {
+    /// Parses the converted text. Errors point at the listing line they
+    /// came from.
+    pub fn parse(&self) -> Result<Vec<ListingLine<'_>>, ArmParserError> {
+        let lines = parse_asm(&self.text).map_err(|e| self.remap(e))?;
+        Ok(lines
+            .into_iter()
+            .map(|line| {
+                let origin = self
+                    .origin(line.loc.line)
+                    .expect("Line outside the listing");
+                ListingLine {
+                    address: origin.address,
+                    encoding: origin.encoding,
+                    line,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Converts an `objdump -d` listing, with or without `--no-show-raw-insn`.
+///
+/// Lines that carry no code, like the `file format` banner and `...` for
+/// skipped zeros, are dropped. Symbol names that aren't valid identifiers,
+/// like `puts@plt`, have the offending characters replaced with `_`.
+pub fn objdump(src: &str) -> Listing {
+    let mut text = Vec::new();
+    let mut origins = Vec::new();
+    let mut start = 0;
+    for (i, raw) in src.split_inclusive('\n').enumerate() {
+        let line = raw.trim_end_matches(['\n', '\r']);
+        let line_start = start;
+        start += raw.len();
+        let trimmed = line.trim();
+        let at = |offset: usize, address, encoding| Origin {
+            loc: Location {
+                line: i + 1,
+                col: line[..offset].chars().count() + 1,
+                start: line_start + offset,
+                end: line_start + line.trim_end().len(),
+            },
+            address,
+            encoding,
+        };
+        let offset = line.len() - line.trim_start().len();
+
+        if let Some(section) = trimmed
+            .strip_prefix("Disassembly of section ")
+            .and_then(|s| s.strip_suffix(':'))
+        {
+            text.push(format!("\t.section\t{section}"));
+            origins.push(at(offset, None, None));
+        } else if let Some((address, symbol)) = symbol_header(trimmed) {
+            text.push(format!("{}:", symbol_name(symbol)));
+            origins.push(at(offset, Some(address), None));
+        } else if let Some((address, rest)) = trimmed.split_once(':').filter(|(_, rest)| {
+            // `-r` lists relocations below the instruction they apply to,
+            // as in `4: R_AARCH64_ADR_PREL_PG_HI21 .rodata`
+            let rest = rest.trim_start();
+            !rest.starts_with("file format") && !rest.starts_with("R_")
+        }) {
+            let Ok(address) = u64::from_str_radix(address, 16) else {
+                continue;
+            };
+            let (encoding, insn) = split_encoding(rest);
+            if insn.is_empty() {
+                // raw bytes wrapped onto a line of their own
+                continue;
+            }
+            text.push(format!("\t{}", symbolic_targets(insn)));
+            origins.push(at(line.len() - insn.len(), Some(address), encoding));
+        }
+    }
+    Listing {
+        text: text.join("\n"),
+        origins,
+    }
+}
+
+/// Reads a `0000000000400580 <main>:` header.
+fn symbol_header(line: &str) -> Option<(u64, &str)> {
+    let (address, symbol) = line.strip_suffix(">:")?.split_once(" <")?;
+    Some((u64::from_str_radix(address, 16).ok()?, symbol))
+}
+
+/// Splits the raw encoding off the text after the address, e.g.
+/// `d10083ff \tsub\tsp, sp, #0x20`.
+fn split_encoding(rest: &str) -> (Option<u32>, &str) {
+    let rest = rest.trim_start();
+    let (raw, insn) = rest.split_once('\t').unwrap_or((rest, ""));
+    let is_raw = !raw.trim().is_empty()
+        && (raw.split_whitespace()).all(|w| w.bytes().all(|b| b.is_ascii_hexdigit()));
+    match is_raw {
+        true => {
+            let word = raw.trim();
+            let encoding = (word.len() == 8)
+                .then(|| u32::from_str_radix(word, 16).ok())
+                .flatten();
+            (encoding, insn.trim())
+        }
+        false => (None, rest.trim_end()),
+    }
+}
+
+/// Replaces every `400530 <puts@plt>` in the operands with the symbol it
+/// names, keeping offsets like the one of `<main+0x10>`.
+fn symbolic_targets(insn: &str) -> String {
+    let mut out = String::new();
+    let mut rest = insn;
+    while let Some(open) = rest.find(" <") {
+        let Some(close) = rest[open..].find('>').map(|i| open + i) else {
+            break;
+        };
+        let before = &rest[..open];
+        let digits_start = before
+            .rfind(|c: char| !c.is_ascii_hexdigit())
+            .map_or(0, |i| i + 1);
+        // llvm-objdump writes the address as `0x0`
+        let address_start = match before[..digits_start].ends_with("0x") {
+            true => digits_start - 2,
+            false => digits_start,
+        };
+        if digits_start == before.len() {
+            out.push_str(&rest[..=close]);
+        } else {
+            let target = &rest[open + 2..close];
+            // the offset is `+0x10`, or `-0x3e8` below the first symbol
+            let (symbol, offset) = match target.rfind(['+', '-']) {
+                Some(i) if target[i + 1..].starts_with("0x") => target.split_at(i),
+                _ => (target, ""),
+            };
+            out.push_str(&before[..address_start]);
+            out.push_str(&symbol_name(symbol));
+            out.push_str(offset);
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `symbol` as an identifier the parser accepts.
+fn symbol_name(symbol: &str) -> String {
+    symbol
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '_' | '.') {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect()
+}
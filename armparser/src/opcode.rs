@@ -1,45 +1,284 @@
 use std::collections::HashMap;
 use std::sync::LazyLock;
+
+use crate::condition::Condition;
+use crate::error::{suggest, Snippet};
+use crate::{Parse, Rule};
+
+/// An A64 mnemonic, by its lowercase name in [`OPCODES`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Opcode(pub &'static str);
 
-macro_rules! define_opcodes {
-    ($($name:ident = $value:expr),* $(,)?) => {
-        impl Opcode {
-            $(
-                pub const $name: Opcode = Opcode($value);
-            )*
+impl Opcode {
+    /// Looks up a mnemonic, ignoring case.
+    pub fn lookup(text: &str) -> Option<Opcode> {
+        OPCODES
+            .get(text.to_ascii_lowercase().as_str())
+            .map(|info| Opcode(info.name))
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+
+    pub fn info(&self) -> &'static OpcodeInfo {
+        &OPCODES[self.0]
+    }
+
+    pub fn class(&self) -> InstClass {
+        self.info().class
+    }
 
-            pub fn name(&self) -> &'static str {
-                self.0
+    /// Whether the last operand is a condition, as in `csel x0, x1, x2, eq`.
+    pub fn takes_condition(&self) -> bool {
+        matches!(
+            self.0,
+            "ccmn"
+                | "ccmp"
+                | "cinc"
+                | "cinv"
+                | "cneg"
+                | "csel"
+                | "cset"
+                | "csetm"
+                | "csinc"
+                | "csinv"
+                | "csneg"
+                | "fccmp"
+                | "fccmpe"
+                | "fcsel"
+        )
+    }
+}
+
+/// A mnemonic as written, split into the instruction and its condition
+/// suffix: `b.eq`, `beq` and `cbgt` have one, `csel` takes its condition as
+/// an operand instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mnemonic {
+    pub opcode: Opcode,
+    pub condition: Option<Condition>,
+}
+
+impl Mnemonic {
+    /// Looks up a mnemonic, ignoring case.
+    pub fn lookup(text: &str) -> Option<Mnemonic> {
+        if let Some(opcode) = Opcode::lookup(text) {
+            return Some(Mnemonic {
+                opcode,
+                condition: None,
+            });
+        }
+        // `b.eq`, or the `beq` spelling GNU as also takes
+        let (base, cond) = match text.rsplit_once('.') {
+            Some(split) => split,
+            None => text.split_at_checked(text.len().checked_sub(2)?)?,
+        };
+        let opcode = Opcode::lookup(base).filter(|op| op.info().conditional)?;
+        Some(Mnemonic {
+            opcode,
+            condition: Some(Condition::from_name(cond)?),
+        })
+    }
+}
+
+impl Parse<'_> for Mnemonic {
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, crate::Err> {
+        debug_assert_eq!(pair.as_rule(), Rule::opcode);
+        Mnemonic::lookup(pair.as_str()).ok_or_else(|| crate::Err::InvalidOpcode {
+            text: pair.as_str().to_string(),
+            suggestion: suggest(pair.as_str(), OPCODES.keys().copied()),
+            at: Snippet::of(&pair),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstClass {
+    DataProcessing,
+    LoadStore,
+    Branch,
+    /// Scalar floating-point and Advanced SIMD data processing
+    FloatingPoint,
+    Atomic,
+    System,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub name: &'static str,
+    pub class: InstClass,
+    /// One-line summary from the instruction list, e.g. `Move wide with keep`
+    pub description: &'static str,
+    /// Instructions this mnemonic is an alias of, e.g. `sbfm` for `sxtw`.
+    /// Conditional families are named the way the manual does, as in `cb<cc>`.
+    pub alias_of: Vec<&'static str>,
+    /// Mnemonics that are aliases of this instruction
+    pub aliases: Vec<&'static str>,
+    /// Whether the mnemonic takes a condition suffix, like `b` in `b.eq`
+    pub conditional: bool,
+}
+
+/// Every A64 mnemonic the parser accepts, built from the base instruction
+/// list in `isa/armisa/base.txt` plus the scalar floating-point and Advanced
+/// SIMD instructions in `isa/armisa/fp.txt` and `isa/armisa/simd.txt`.
+pub static OPCODES: LazyLock<HashMap<&'static str, OpcodeInfo>> = LazyLock::new(|| {
+    let mut map = HashMap::new();
+    // each list with the class of entries that don't say otherwise
+    let lists = [
+        (
+            include_str!("../../isa/armisa/base.txt"),
+            InstClass::DataProcessing,
+        ),
+        (
+            include_str!("../../isa/armisa/fp.txt"),
+            InstClass::FloatingPoint,
+        ),
+        (
+            include_str!("../../isa/armisa/simd.txt"),
+            InstClass::FloatingPoint,
+        ),
+    ];
+    for (list, class) in lists {
+        for entry in list.split("\n\n").map(str::trim).filter(|e| !e.is_empty()) {
+            add_entry(&mut map, entry, class);
+        }
+    }
+
+    // link every alias back from the instruction it stands for
+    let links = map
+        .values()
+        .flat_map(|info: &OpcodeInfo| info.alias_of.iter().map(|&of| (of, info.name)))
+        .collect::<Vec<_>>();
+    for (of, alias) in links {
+        if let Some(info) = map.get_mut(of) {
+            if !info.aliases.contains(&alias) {
+                info.aliases.push(alias);
             }
         }
+    }
+    for info in map.values_mut() {
+        info.aliases.sort_unstable();
+    }
+    map
+});
 
-        // 创建全局静态HashMap
-        pub static OPCODES: LazyLock<HashMap<&'static str, Opcode>> = LazyLock::new(|| {
-            let mut map = HashMap::new();
-            $(
-                map.insert($value, Opcode::$name);
-            )*
-            map
-        });
+/// Adds one entry of an instruction list, which reads like
+/// `NAME[, NAME...][ (form)]: Description[: an alias of TARGET[ (form)]].`
+///
+/// Forms of the same mnemonic come as separate entries and are merged.
+fn add_entry(map: &mut HashMap<&'static str, OpcodeInfo>, entry: &'static str, class: InstClass) {
+    let (names, rest) = entry.split_once(": ").expect("Malformed instruction entry");
+    let rest = rest.trim_end_matches('.');
+    let (description, target) = match rest.split_once(": an alias of ") {
+        Some((description, target)) => (description, Some(strip_form(target))),
+        None => (rest, None),
     };
+    // `cble` and the like are just `cb` with a condition suffix
+    if target.is_some_and(|t| t.ends_with("<cc>")) {
+        return;
+    }
+    let alias_of = target.map(manual_name);
+    let class = classify(description, class);
+
+    for name in strip_form(names).split(", ") {
+        let (name, conditional) = base_name(name);
+        let info = map.entry(name).or_insert_with(|| OpcodeInfo {
+            name,
+            class,
+            description,
+            alias_of: vec![],
+            aliases: vec![],
+            conditional: false,
+        });
+        info.conditional |= conditional;
+        if let Some(alias_of) = alias_of {
+            if !info.alias_of.contains(&alias_of) {
+                info.alias_of.push(alias_of);
+            }
+        }
+    }
+}
+
+/// Drops the `(form)` qualifier, as in `ADD (immediate)`.
+fn strip_form(names: &str) -> &str {
+    names.split(" (").next().unwrap_or(names)
+}
+
+/// Lowercase name of an instruction as the manual writes it. For a list of
+/// alternatives like `LDADD, LDADDA, LDADDAL, LDADDL` that's the first one.
+fn manual_name(name: &str) -> &'static str {
+    let first = name.split(", ").next().unwrap_or(name);
+    first.to_ascii_lowercase().leak()
+}
+
+/// Lowercase mnemonic of a list name, without the condition placeholder of
+/// conditional families like `B.cond` and `CB<cc>`, and whether it had one.
+fn base_name(name: &str) -> (&'static str, bool) {
+    let name = name.to_ascii_lowercase();
+    match name
+        .strip_suffix(".cond")
+        .or_else(|| name.strip_suffix("<cc>"))
+    {
+        Some(base) => (base.to_string().leak(), true),
+        None => (name.leak(), false),
+    }
 }
 
-define_opcodes! {
-    ADD = "add",
-    SUB = "sub",
-    MUL = "mul",
-    DIV = "div",
-    MOV = "mov",
-    LOAD = "load",
-    STORE = "store",
-    JMP = "jmp",
-    STP = "stp",
-    B = "b",
-    BL = "bl",
-    LI = "li",
-    LDR = "ldr",
-    STR = "str",
-    LDP = "ldp",
+/// Sorts an instruction into its class by what its description says, or
+/// into `class` if it doesn't say.
+fn classify(description: &str, class: InstClass) -> InstClass {
+    let d = description.to_ascii_lowercase();
+    let has = |words: &[&str]| words.iter().any(|w| d.contains(w));
+    if has(&["atomic", "swap"]) && !d.starts_with("single-copy") {
+        InstClass::Atomic
+    } else if (d.starts_with("branch") && !has(&["history", "record", "target"]))
+        || has(&["and branch", "return from", "exception return"])
+    {
+        InstClass::Branch
+    } else if d.starts_with("load")
+        || d.starts_with("store")
+        || has(&[
+            "prefetch memory",
+            "memory copy",
+            "memory set",
+            "single-copy",
+            "store register",
+        ])
+    {
+        InstClass::LoadStore
+    } else if has(&[
+        "barrier",
+        "hint",
+        "call",
+        "system",
+        "special register",
+        "cache",
+        "event",
+        "interrupt",
+        "exception",
+        "debug",
+        "breakpoint",
+        "halt",
+        "no operation",
+        "yield",
+        "tlb",
+        "translate",
+        "transaction",
+        "trace",
+        "guarded control stack",
+        "prediction restriction",
+        "streaming sve",
+        "clear exclusive",
+        "branch history",
+        "branch record",
+        "branch target",
+        "feature",
+        "undefined",
+        "physical address space",
+    ]) {
+        InstClass::System
+    } else {
+        class
+    }
 }
@@ -0,0 +1,785 @@
+//! Expansion of the GNU as macro language ahead of parsing: `.macro`,
+//! `.rept`, `.irp`/`.irpc`, `.set`/`.equ` constants and conditional
+//! assembly with `.if` and friends.
+
+use std::collections::{HashMap, HashSet};
+
+use pest::Parser;
+
+use crate::error::{ArmParserError, Snippet};
+use crate::expr::Expr;
+use crate::generated::{Generated, SourceOrigin};
+use crate::{parse_asm, ARM64Parser, Location, Parse, Rule, SourceLine};
+
+/// How deep macro calls and repeat blocks may nest, which also stops
+/// macros that call themselves without end.
+const MAX_DEPTH: usize = 100;
+
+/// Where a line of preprocessed text came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Origin {
+    /// The source line the text was taken from. For expanded lines, that's
+    /// the line inside the macro or repeat block.
+    pub loc: Location,
+    /// The outermost macro call or repeat block that produced the text
+    pub expanded_from: Option<Location>,
+}
+
+impl SourceOrigin for Origin {
+    fn loc(&self) -> Location {
+        self.loc
+    }
+
+    fn expanded_from(&self) -> Option<Location> {
+        self.expanded_from
+    }
+}
+
+/// Source text with every macro, repeat block, conditional and constant
+/// expanded.
+pub type Preprocessed = Generated<Origin>;
+
+impl Preprocessed {
+    /// Parses the expanded text. The lines' locations refer to `text`, see
+    /// [`Generated::origin`], while errors point at the original source.
+    pub fn parse(&self) -> Result<Vec<SourceLine<'_>>, ArmParserError> {
+        parse_asm(&self.text).map_err(|e| self.remap(e))
+    }
+}
+
+/// Expands the macro language of `src`.
+pub fn preprocess(src: &str) -> Result<Preprocessed, ArmParserError> {
+    let mut start = 0;
+    let lines = src
+        .split_inclusive('\n')
+        .enumerate()
+        .flat_map(|(i, raw)| {
+            let text = raw.trim_end_matches(['\n', '\r']);
+            let loc = Location {
+                line: i + 1,
+                col: 1,
+                start,
+                end: start + text.len(),
+            };
+            start += raw.len();
+            split_statements(text).into_iter().map(move |text| PpLine {
+                text,
+                origin: Origin {
+                    loc,
+                    expanded_from: None,
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut pp = Preprocessor {
+        src,
+        macros: HashMap::new(),
+        symbols: HashMap::new(),
+        labels: HashSet::new(),
+        calls: 0,
+        macro_depth: 0,
+        out: Vec::new(),
+    };
+    pp.run(&lines, 0)?;
+    Ok(Preprocessed {
+        text: pp
+            .out
+            .iter()
+            .map(|l| l.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        origins: pp.out.iter().map(|l| l.origin).collect(),
+    })
+}
+
+#[derive(Debug, Clone)]
+struct PpLine {
+    text: String,
+    origin: Origin,
+}
+
+struct Macro {
+    params: Vec<Param>,
+    body: Vec<PpLine>,
+}
+
+struct Param {
+    name: String,
+    default: Option<String>,
+    required: bool,
+    /// Takes all remaining arguments
+    vararg: bool,
+}
+
+/// Value of a `.set` or `.equ` symbol. Values that aren't constant, like
+/// `.equ end, buf+16`, are substituted as written.
+#[derive(Debug, Clone)]
+enum Value {
+    Int(i64),
+    Text(String),
+}
+
+/// One `.if` block being read.
+struct Cond {
+    /// Whether lines are kept at this point
+    active: bool,
+    /// Whether one of the branches was taken already
+    taken: bool,
+    /// Whether the block itself is in kept code
+    enclosing: bool,
+    at: PpLine,
+}
+
+/// Whether a `.exitm` stopped the current macro.
+enum Flow {
+    Continue,
+    Exit,
+}
+
+struct Preprocessor<'s> {
+    src: &'s str,
+    macros: HashMap<String, Macro>,
+    symbols: HashMap<String, Value>,
+    labels: HashSet<String>,
+    /// Number of macro calls so far, for `\@`
+    calls: usize,
+    /// Number of macro bodies being expanded, outside of which `.exitm` is
+    /// an error
+    macro_depth: usize,
+    out: Vec<PpLine>,
+}
+
+impl Preprocessor<'_> {
+    fn run(&mut self, lines: &[PpLine], depth: usize) -> Result<Flow, ArmParserError> {
+        if depth > MAX_DEPTH {
+            let line = lines.first().expect("Empty expansion");
+            return Err(self.error(line, "macro", "expansion nested too deeply"));
+        }
+        let mut conds: Vec<Cond> = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = &lines[i];
+            i += 1;
+            let (label, word, rest) = split_line(&line.text);
+            let directive = word.to_ascii_lowercase();
+            let active = conds.iter().all(|c| c.active);
+
+            match directive.as_str() {
+                ".if" | ".ifdef" | ".ifndef" | ".ifeq" | ".ifne" | ".ifgt" | ".ifge" | ".iflt"
+                | ".ifle" | ".ifc" | ".ifnc" | ".ifb" | ".ifnb" => {
+                    let value = active && self.condition(line, &directive, rest)?;
+                    conds.push(Cond {
+                        active: value,
+                        taken: value,
+                        enclosing: active,
+                        at: line.clone(),
+                    });
+                    continue;
+                }
+                ".elseif" | ".else" | ".endif" => {
+                    let Some(cond) = conds.last_mut() else {
+                        return Err(self.error(line, word, "no matching `.if`"));
+                    };
+                    match directive.as_str() {
+                        ".elseif" => {
+                            let value =
+                                cond.enclosing && !cond.taken && self.eval(line, word, rest)? != 0;
+                            cond.active = value;
+                            cond.taken |= value;
+                        }
+                        ".else" => {
+                            cond.active = cond.enclosing && !cond.taken;
+                            cond.taken = true;
+                        }
+                        _ => {
+                            conds.pop();
+                        }
+                    }
+                    continue;
+                }
+                _ if !active => continue,
+                _ => {}
+            }
+
+            if let Some(label) = label {
+                self.labels.insert(label.to_string());
+            }
+            let is_call = self.macros.contains_key(word);
+            let assigns = rest.starts_with('=') && !rest.starts_with("==") && !word.is_empty();
+            let handled = is_call
+                || assigns
+                || matches!(
+                    directive.as_str(),
+                    ".macro"
+                        | ".endm"
+                        | ".exitm"
+                        | ".purgem"
+                        | ".rept"
+                        | ".irp"
+                        | ".irpc"
+                        | ".endr"
+                        | ".set"
+                        | ".equ"
+                        | ".equiv"
+                );
+            if !handled {
+                let text = self.substitute_symbols(&line.text);
+                self.out.push(PpLine {
+                    text,
+                    origin: line.origin,
+                });
+                continue;
+            }
+            if let Some(label) = label {
+                self.out.push(PpLine {
+                    text: format!("{label}:"),
+                    origin: line.origin,
+                });
+            }
+
+            match directive.as_str() {
+                ".macro" => {
+                    let end = self.block_end(lines, i, line, &[".macro"], ".endm")?;
+                    self.define_macro(line, rest, &lines[i..end])?;
+                    i = end + 1;
+                }
+                ".rept" | ".irp" | ".irpc" => {
+                    let end =
+                        self.block_end(lines, i, line, &[".rept", ".irp", ".irpc"], ".endr")?;
+                    let body = &lines[i..end];
+                    i = end + 1;
+                    for expansion in self.repeat(line, &directive, rest, body)? {
+                        if let Flow::Exit = self.run(&expansion, depth + 1)? {
+                            return Ok(Flow::Exit);
+                        }
+                    }
+                }
+                ".endm" | ".endr" => return Err(self.error(line, word, "no matching block start")),
+                ".exitm" if self.macro_depth == 0 => {
+                    return Err(self.error(line, word, "not inside a macro"))
+                }
+                ".exitm" => return Ok(Flow::Exit),
+                ".purgem" => {
+                    if self.macros.remove(rest).is_none() {
+                        return Err(self.error(line, word, &format!("no macro named `{rest}`")));
+                    }
+                }
+                ".set" | ".equ" | ".equiv" => {
+                    let Some((name, value)) = rest.split_once(',') else {
+                        return Err(self.error(line, word, "expected a symbol and a value"));
+                    };
+                    let name = name.trim();
+                    if directive == ".equiv" && self.symbols.contains_key(name) {
+                        return Err(self.error(
+                            line,
+                            word,
+                            &format!("`{name}` is already defined"),
+                        ));
+                    }
+                    self.define_symbol(name, value.trim());
+                }
+                _ if assigns => self.define_symbol(word, rest[1..].trim()),
+                _ => {
+                    let expansion = self.call(line, word, rest)?;
+                    self.macro_depth += 1;
+                    self.run(&expansion, depth + 1)?;
+                    self.macro_depth -= 1;
+                }
+            }
+        }
+        match conds.first() {
+            Some(cond) => Err(self.error(
+                &cond.at,
+                split_line(&cond.at.text).1,
+                "no matching `.endif`",
+            )),
+            None => Ok(Flow::Continue),
+        }
+    }
+
+    /// Index of the line that closes the block opened just before `start`,
+    /// skipping over nested blocks of the same kind.
+    fn block_end(
+        &self,
+        lines: &[PpLine],
+        start: usize,
+        opener: &PpLine,
+        opens: &[&str],
+        close: &str,
+    ) -> Result<usize, ArmParserError> {
+        let mut depth = 0;
+        for (i, line) in lines.iter().enumerate().skip(start) {
+            let word = split_line(&line.text).1.to_ascii_lowercase();
+            if opens.contains(&word.as_str()) {
+                depth += 1;
+            } else if word == close {
+                if depth == 0 {
+                    return Ok(i);
+                }
+                depth -= 1;
+            }
+        }
+        let word = split_line(&opener.text).1;
+        Err(self.error(opener, word, &format!("no matching `{close}`")))
+    }
+
+    fn define_macro(
+        &mut self,
+        line: &PpLine,
+        rest: &str,
+        body: &[PpLine],
+    ) -> Result<(), ArmParserError> {
+        let (name, params) = rest
+            .split_once(|c: char| c == ',' || c.is_whitespace())
+            .unwrap_or((rest, ""));
+        if name.is_empty() {
+            return Err(self.error(line, ".macro", "expected a name"));
+        }
+        let params = split_args(params)
+            .into_iter()
+            .map(|param| {
+                let (param, default) = match param.split_once('=') {
+                    Some((param, default)) => (param.trim(), Some(default.trim().to_string())),
+                    None => (param.as_str(), None),
+                };
+                let (name, qualifier) = param.split_once(':').unwrap_or((param, ""));
+                Param {
+                    name: name.to_string(),
+                    default,
+                    required: qualifier == "req",
+                    vararg: qualifier == "vararg",
+                }
+            })
+            .collect();
+        self.macros.insert(
+            name.to_string(),
+            Macro {
+                params,
+                body: body.to_vec(),
+            },
+        );
+        Ok(())
+    }
+
+    /// The body of a macro call with its arguments filled in.
+    fn call(
+        &mut self,
+        line: &PpLine,
+        name: &str,
+        rest: &str,
+    ) -> Result<Vec<PpLine>, ArmParserError> {
+        let mac = &self.macros[name];
+        let mut values: Vec<Option<String>> = vec![None; mac.params.len()];
+        let mut next = 0;
+        for arg in split_args(rest) {
+            let keyword = arg.split_once('=').and_then(|(key, value)| {
+                let i = mac.params.iter().position(|p| p.name == key.trim())?;
+                Some((i, value.trim().to_string()))
+            });
+            if let Some((i, value)) = keyword {
+                values[i] = Some(value);
+                continue;
+            }
+            match mac.params.get(next) {
+                Some(param) if param.vararg => {
+                    let value = values[next].get_or_insert_with(String::new);
+                    if !value.is_empty() {
+                        value.push_str(", ");
+                    }
+                    value.push_str(&arg);
+                }
+                Some(_) => {
+                    values[next] = Some(arg);
+                    next += 1;
+                }
+                None => return Err(self.error(line, name, "too many arguments")),
+            }
+        }
+
+        let mut args = HashMap::new();
+        for (param, value) in mac.params.iter().zip(values) {
+            let value = match value.or_else(|| param.default.clone()) {
+                Some(value) => value,
+                None if param.required => {
+                    let message = format!("missing value for `{}`", param.name);
+                    return Err(self.error(line, name, &message));
+                }
+                None => String::new(),
+            };
+            args.insert(param.name.as_str(), value);
+        }
+        let body = mac
+            .body
+            .iter()
+            .map(|body_line| expand_line(body_line, line, &args, self.calls))
+            .collect();
+        self.calls += 1;
+        Ok(body)
+    }
+
+    /// The expansions of a `.rept`, `.irp` or `.irpc` block, in order.
+    fn repeat(
+        &self,
+        line: &PpLine,
+        directive: &str,
+        rest: &str,
+        body: &[PpLine],
+    ) -> Result<Vec<Vec<PpLine>>, ArmParserError> {
+        let expand = |name: &str, value: String| {
+            let args = HashMap::from([(name, value)]);
+            body.iter()
+                .map(|body_line| expand_line(body_line, line, &args, self.calls))
+                .collect()
+        };
+        if directive == ".rept" {
+            let count = self.eval(line, directive, rest)?;
+            let count = usize::try_from(count)
+                .map_err(|_| self.error(line, directive, "count can't be negative"))?;
+            return Ok(vec![expand("", String::new()); count]);
+        }
+        let (name, values) = rest.split_once(',').unwrap_or((rest, ""));
+        let name = name.trim();
+        let values = match directive {
+            ".irp" => split_args(values),
+            _ => values.trim().chars().map(String::from).collect(),
+        };
+        if values.is_empty() {
+            return Ok(vec![expand(name, String::new())]);
+        }
+        Ok(values
+            .into_iter()
+            .map(|value| expand(name, value))
+            .collect())
+    }
+
+    fn condition(
+        &self,
+        line: &PpLine,
+        directive: &str,
+        rest: &str,
+    ) -> Result<bool, ArmParserError> {
+        let value = match directive {
+            ".ifdef" => self.symbols.contains_key(rest) || self.labels.contains(rest),
+            ".ifndef" => !(self.symbols.contains_key(rest) || self.labels.contains(rest)),
+            ".ifb" => rest.is_empty(),
+            ".ifnb" => !rest.is_empty(),
+            ".ifc" | ".ifnc" => {
+                let (a, b) = rest.split_once(',').unwrap_or((rest, ""));
+                (unquote(a.trim()) == unquote(b.trim())) == (directive == ".ifc")
+            }
+            _ => {
+                let value = self.eval(line, directive, rest)?;
+                match directive {
+                    ".ifeq" => value == 0,
+                    ".ifgt" => value > 0,
+                    ".ifge" => value >= 0,
+                    ".iflt" => value < 0,
+                    ".ifle" => value <= 0,
+                    _ => value != 0,
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    /// Evaluates a constant expression. As in GNU as, comparisons give -1
+    /// when they hold.
+    fn eval(&self, line: &PpLine, directive: &str, text: &str) -> Result<i64, ArmParserError> {
+        let invalid = || {
+            self.error(
+                line,
+                directive,
+                &format!("`{text}` is not a constant expression"),
+            )
+        };
+        let symbol = |name: &str| match self.symbols.get(name) {
+            Some(Value::Int(n)) => Some(*n),
+            _ => None,
+        };
+        let eval_expr = |pair| {
+            let expr = Expr::parse(pair).map_err(|_| invalid())?;
+            expr.eval(&symbol).ok_or_else(invalid)
+        };
+        let pp_expr = ARM64Parser::parse(Rule::pp_expr, text)
+            .map_err(|_| invalid())?
+            .next()
+            .expect("No pp_expr pair");
+        let pp_or = pp_expr.into_inner().next().expect("No inner pair");
+        let mut any = Vec::new();
+        for pp_and in pp_or.into_inner() {
+            let mut all = Vec::new();
+            for pp_cmp in pp_and.into_inner() {
+                let mut inner = pp_cmp.into_inner();
+                let lhs = eval_expr(inner.next().expect("No inner pair"))?;
+                let value = match (inner.next(), inner.next()) {
+                    (Some(op), Some(rhs)) => {
+                        let rhs = eval_expr(rhs)?;
+                        let holds = match op.as_str() {
+                            "==" => lhs == rhs,
+                            "!=" | "<>" => lhs != rhs,
+                            "<=" => lhs <= rhs,
+                            ">=" => lhs >= rhs,
+                            "<" => lhs < rhs,
+                            _ => lhs > rhs,
+                        };
+                        -i64::from(holds)
+                    }
+                    _ => lhs,
+                };
+                all.push(value);
+            }
+            any.push(match all[..] {
+                [value] => value,
+                _ => i64::from(all.iter().all(|&v| v != 0)),
+            });
+        }
+        Ok(match any[..] {
+            [value] => value,
+            _ => i64::from(any.iter().any(|&v| v != 0)),
+        })
+    }
+
+    fn define_symbol(&mut self, name: &str, value: &str) {
+        let symbol = |name: &str| match self.symbols.get(name) {
+            Some(Value::Int(n)) => Some(*n),
+            _ => None,
+        };
+        // only a plain expression, `.set` and `.equ` don't compare
+        let constant = ARM64Parser::parse(Rule::expr, value)
+            .ok()
+            .and_then(|mut pairs| pairs.next())
+            .filter(|expr| expr.as_str() == value)
+            .and_then(|expr| Expr::parse(expr).ok()?.eval(&symbol));
+        let value = match constant {
+            Some(n) => Value::Int(n),
+            None => Value::Text(self.substitute_symbols(value)),
+        };
+        self.symbols.insert(name.to_string(), value);
+    }
+
+    /// Replaces `.set` and `.equ` symbols in the operands of a line.
+    fn substitute_symbols(&self, text: &str) -> String {
+        if self.symbols.is_empty() {
+            return text.to_string();
+        }
+        let code = strip_comment(text);
+        let comment = &text[code.len()..];
+        let mut out = String::with_capacity(text.len());
+        let mut first_word = true;
+        let mut rest = code;
+        while let Some(c) = rest.chars().next() {
+            let len = if c == '"' {
+                // a string literal, copied as it is
+                let mut escaped = false;
+                rest[1..]
+                    .find(|c| {
+                        let end = c == '"' && !escaped;
+                        escaped = c == '\\' && !escaped;
+                        end
+                    })
+                    .map_or(rest.len(), |i| i + 2)
+            } else if is_ident_char(c) {
+                let len = rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len());
+                let word = &rest[..len];
+                let is_label = rest[len..].starts_with(':');
+                match self.symbols.get(word) {
+                    Some(value) if !first_word && !is_label && !c.is_ascii_digit() => {
+                        match value {
+                            Value::Int(n) => out.push_str(&n.to_string()),
+                            Value::Text(text) => out.push_str(&format!("({text})")),
+                        }
+                        rest = &rest[len..];
+                        continue;
+                    }
+                    _ => {}
+                }
+                first_word &= is_label;
+                len
+            } else {
+                c.len_utf8()
+            };
+            out.push_str(&rest[..len]);
+            rest = &rest[len..];
+        }
+        out.push_str(comment);
+        out
+    }
+
+    fn error(&self, line: &PpLine, directive: &str, message: &str) -> ArmParserError {
+        let mut loc = line.origin.loc;
+        let line_text = &self.src[loc.start..loc.end];
+        // point at the code, after the indentation or the statements before it
+        let indent = line.text.len() - line.text.trim_start().len();
+        loc.col += line_text[..indent].chars().count();
+        loc.start += indent;
+        ArmParserError::InvalidDirective {
+            directive: directive.to_string(),
+            message: message.to_string(),
+            at: Box::new(Snippet {
+                file: None,
+                loc,
+                line_text: line_text.trim_end().to_string(),
+                expanded_from: line.origin.expanded_from.map(|loc| loc.line),
+            }),
+        }
+    }
+}
+
+/// A line of a macro or repeat body with `\name` arguments replaced, `\@`
+/// replaced with the call count and `\()` separators dropped.
+fn expand_line(
+    body_line: &PpLine,
+    call: &PpLine,
+    args: &HashMap<&str, String>,
+    calls: usize,
+) -> PpLine {
+    let text = &body_line.text;
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix("()") {
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('@') {
+            out.push_str(&calls.to_string());
+            rest = after;
+        } else {
+            let len = rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len());
+            match args.get(&rest[..len]) {
+                Some(value) if len > 0 => {
+                    out.push_str(value);
+                    rest = &rest[len..];
+                }
+                // not an argument, like the `\n` of a string
+                _ => out.push('\\'),
+            }
+        }
+    }
+    out.push_str(rest);
+    PpLine {
+        text: out,
+        origin: Origin {
+            loc: body_line.origin.loc,
+            expanded_from: call.origin.expanded_from.or(Some(call.origin.loc)),
+        },
+    }
+}
+
+/// Splits a line into its leading label, if any, its first word and the
+/// rest, leaving out the comment.
+fn split_line(text: &str) -> (Option<&str>, &str, &str) {
+    let code = strip_comment(text).trim();
+    let (label, code) = match code.find(|c| !is_ident_char(c)) {
+        Some(i) if i > 0 && code[i..].starts_with(':') => {
+            (Some(&code[..i]), code[i + 1..].trim_start())
+        }
+        _ => (None, code),
+    };
+    let end = code
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(code.len());
+    (label, &code[..end], code[end..].trim())
+}
+
+/// Splits a line into the statements that `;` separates. Each one keeps its
+/// columns, with the statements before it blanked out.
+fn split_statements(text: &str) -> Vec<String> {
+    let mut starts = vec![0];
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in strip_comment(text).char_indices() {
+        match c {
+            '"' if !escaped => in_string = !in_string,
+            ';' if !in_string => starts.push(i + 1),
+            _ => {}
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, &start)| {
+            let end = starts.get(n + 1).map_or(text.len(), |next| next - 1);
+            let blank = text[..start]
+                .chars()
+                .map(|c| {
+                    if c == '\t' {
+                        "\t".to_string()
+                    } else {
+                        " ".repeat(c.len_utf8())
+                    }
+                })
+                .collect::<String>();
+            blank + &text[start..end]
+        })
+        .collect()
+}
+
+/// The line without its comment, as the grammar's `COMMENT` rule has it.
+fn strip_comment(text: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' if !escaped => in_string = !in_string,
+            '/' if !in_string && text[i..].starts_with("//") => return &text[..i],
+            '#' if !in_string && text[i + 1..].chars().next().is_none_or(char::is_whitespace) => {
+                return &text[..i]
+            }
+            _ => {}
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    text
+}
+
+/// Splits macro arguments. They're separated by commas, or by blanks when
+/// there's no comma at all; quotes keep an argument together.
+fn split_args(text: &str) -> Vec<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let (mut in_string, mut parens) = (false, 0);
+    let by_comma = text.contains(',');
+    for c in text.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' if !in_string => parens += 1,
+            ')' if !in_string => parens -= 1,
+            _ => {}
+        }
+        let splits = !in_string
+            && parens == 0
+            && if by_comma {
+                c == ','
+            } else {
+                c.is_whitespace()
+            };
+        if splits {
+            if by_comma || !current.is_empty() {
+                args.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    args.push(current);
+    args.iter()
+        .map(|arg| unquote(arg.trim()).to_string())
+        .collect()
+}
+
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text)
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
@@ -0,0 +1,454 @@
+//! Prints the AST back to assembly text.
+//!
+//! The output is canonical rather than a copy of the source: registers take
+//! their architectural names (`fp` prints as `x29`), integers are decimal and
+//! expressions carry only the parentheses they need. Parsing the printed text
+//! gives back an equal AST.
+
+use std::fmt::{self, Display, Formatter, Write};
+
+use crate::condition::Condition;
+use crate::directive::{Directive, SectionFlags, StringLiteral, SymbolType};
+use crate::expr::{BinOp, Expr, RelocModifier};
+use crate::immediate::Immediate;
+use crate::local_label::LocalRef;
+use crate::opcode::Opcode;
+use crate::register::{Arrangement, ElementSize, Register, RegisterType};
+use crate::{
+    AddressMode, ExtendType, ExtendedRegister, Indirect, Instruction, Line, Offset, Operand,
+    ProcLoad, RegisterList, ShiftAmount, ShiftType, ShiftedImmediate, ShiftedRegister, SourceLine,
+};
+
+/// Writes `items` separated by `, `.
+fn comma_list<T: Display>(f: &mut Formatter, items: impl IntoIterator<Item = T>) -> fmt::Result {
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{item}")?;
+    }
+    Ok(())
+}
+
+impl Display for Line<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Line::Label(name) => write!(f, "{name}:"),
+            Line::Directive(directive) => write!(f, "\t{directive}"),
+            Line::Instruction(inst) => write!(f, "\t{inst}"),
+            Line::Error(text) => f.write_str(text),
+        }
+    }
+}
+
+impl Display for SourceLine<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.line.fmt(f)
+    }
+}
+
+impl Display for Instruction<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.opcode)?;
+        if let Some(cond) = self.condition {
+            write!(f, ".{cond}")?;
+        }
+        if !self.operands.is_empty() {
+            f.write_char('\t')?;
+            comma_list(f, &self.operands)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Display for Operand<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Operand::Register(reg) => reg.fmt(f),
+            Operand::Immediate(imm) => write!(f, "#{imm}"),
+            Operand::Address(imm) => write!(f, "={imm}"),
+            Operand::LabelTarget(name) => f.write_str(name),
+            Operand::Indirect(indirect) => indirect.fmt(f),
+            Operand::RegisterList(list) => list.fmt(f),
+            Operand::ShiftedRegister(reg) => reg.fmt(f),
+            Operand::ExtendedRegister(reg) => reg.fmt(f),
+            Operand::ProcLoad(load) => load.fmt(f),
+            Operand::Condition(cond) => cond.fmt(f),
+            Operand::Expr(expr) => expr.fmt(f),
+            Operand::ShiftedImmediate(imm) => imm.fmt(f),
+        }
+    }
+}
+
+impl Display for ShiftedImmediate<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}, lsl #{}", self.value, self.lsl)
+    }
+}
+
+impl Display for Indirect<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let base = &self.base;
+        match &self.mode {
+            AddressMode::Base => write!(f, "[{base}]"),
+            AddressMode::Offset(offset) => write!(f, "[{base}, {offset}]"),
+            AddressMode::PreIndex(imm) => write!(f, "[{base}, #{imm}]!"),
+            AddressMode::PostIndex(imm) => write!(f, "[{base}], #{imm}"),
+            AddressMode::PostIndexRegister(reg) => write!(f, "[{base}], {reg}"),
+        }
+    }
+}
+
+impl Display for Offset<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Offset::Immediate(imm) => write!(f, "#{imm}"),
+            Offset::Register(reg) => reg.fmt(f),
+            Offset::ShiftedRegister(reg) => reg.fmt(f),
+            Offset::ExtendedRegister(reg) => reg.fmt(f),
+            Offset::ProcLoad(load) => load.fmt(f),
+        }
+    }
+}
+
+impl Display for ProcLoad<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, ":{}:{}", self.modifier, self.expr)
+    }
+}
+
+impl Display for RelocModifier {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Display for ShiftedRegister {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}, {}", self.reg, self.shift_type)?;
+        match &self.shift_amount {
+            Some(ShiftAmount::Immediate(imm)) => write!(f, " #{imm}"),
+            Some(ShiftAmount::Register(reg)) => write!(f, " {reg}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for ShiftType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ShiftType::LSL => "lsl",
+            ShiftType::LSR => "lsr",
+            ShiftType::ASR => "asr",
+            ShiftType::ROR => "ror",
+            ShiftType::RRX => "rrx",
+        })
+    }
+}
+
+impl Display for ExtendedRegister {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}, {}", self.reg, self.extend_type)?;
+        match &self.shift_amount {
+            Some(imm) => write!(f, " #{imm}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for ExtendType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ExtendType::UXTB => "uxtb",
+            ExtendType::UXTH => "uxth",
+            ExtendType::UXTW => "uxtw",
+            ExtendType::UXTX => "uxtx",
+            ExtendType::SXTB => "sxtb",
+            ExtendType::SXTH => "sxth",
+            ExtendType::SXTW => "sxtw",
+            ExtendType::SXTX => "sxtx",
+        })
+    }
+}
+
+impl Display for RegisterList {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_char('{')?;
+        comma_list(f, &self.regs)?;
+        f.write_char('}')?;
+        match self.lane {
+            Some(lane) => write!(f, "[{lane}]"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let n = self.reg_num;
+        match self.reg_type {
+            RegisterType::Full => write!(f, "x{n}"),
+            RegisterType::Half => write!(f, "w{n}"),
+            RegisterType::Byte => write!(f, "b{n}"),
+            RegisterType::HalfFloat => write!(f, "h{n}"),
+            RegisterType::SingleFloat => write!(f, "s{n}"),
+            RegisterType::DoubleFloat => write!(f, "d{n}"),
+            RegisterType::Quad => write!(f, "q{n}"),
+            RegisterType::StackPointer => f.write_str("sp"),
+            RegisterType::WordStackPointer => f.write_str("wsp"),
+            RegisterType::XZR => f.write_str("xzr"),
+            RegisterType::WZR => f.write_str("wzr"),
+            RegisterType::Vector(arrangement) => write!(f, "v{n}.{arrangement}"),
+            RegisterType::Element(size) => write!(f, "v{n}.{size}"),
+            RegisterType::Lane(size, lane) => write!(f, "v{n}.{size}[{lane}]"),
+            RegisterType::LaneGroup(arrangement, lane) => write!(f, "v{n}.{arrangement}[{lane}]"),
+        }
+    }
+}
+
+impl Display for Arrangement {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.lanes, self.size)
+    }
+}
+
+impl Display for ElementSize {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ElementSize::B => "b",
+            ElementSize::H => "h",
+            ElementSize::S => "s",
+            ElementSize::D => "d",
+            ElementSize::Q => "q",
+        })
+    }
+}
+
+/// Without the `#`, which only some operands take.
+impl Display for Immediate {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Immediate::Int(n) => write!(f, "{n}"),
+            // `{:?}` always has a `.` or an exponent, so it reads back as a
+            // float
+            Immediate::Float(x) => write!(f, "{x:?}"),
+            Immediate::Char(c) => match c {
+                '\n' => f.write_str("'\\n'"),
+                '\t' => f.write_str("'\\t'"),
+                '\r' => f.write_str("'\\r'"),
+                '\0' => f.write_str("'\\0'"),
+                '\\' | '\'' => write!(f, "'\\{c}'"),
+                c => write!(f, "'{c}'"),
+            },
+        }
+    }
+}
+
+impl BinOp {
+    fn precedence(&self) -> u8 {
+        match self {
+            BinOp::Add | BinOp::Sub => 0,
+            BinOp::Mul | BinOp::Div => 1,
+        }
+    }
+
+    fn symbol(&self) -> char {
+        match self {
+            BinOp::Add => '+',
+            BinOp::Sub => '-',
+            BinOp::Mul => '*',
+            BinOp::Div => '/',
+        }
+    }
+}
+
+impl Display for Expr<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Expr::Int(n) => write!(f, "{n}"),
+            Expr::Symbol(name) => f.write_str(name),
+            Expr::Here => f.write_char('.'),
+            Expr::LocalRef(r) => r.fmt(f),
+            // only an atom may follow the minus sign
+            Expr::Neg(e) => match **e {
+                Expr::Symbol(_) | Expr::Here | Expr::LocalRef(_) => write!(f, "-{e}"),
+                Expr::Int(n) if n >= 0 => write!(f, "-{e}"),
+                _ => write!(f, "-({e})"),
+            },
+            Expr::Binary(lhs, op, rhs) => {
+                let binds_looser = |e: &Expr, strict: bool| match e {
+                    Expr::Binary(_, inner, _) if strict => inner.precedence() <= op.precedence(),
+                    Expr::Binary(_, inner, _) => inner.precedence() < op.precedence(),
+                    // `a-(-1)` rather than `a--1`
+                    Expr::Int(n) => strict && *n < 0,
+                    _ => false,
+                };
+                match binds_looser(lhs, false) {
+                    true => write!(f, "({lhs})")?,
+                    false => write!(f, "{lhs}")?,
+                }
+                f.write_char(op.symbol())?;
+                // operators are left-associative, so `a-(b-c)` keeps its
+                // parentheses
+                match binds_looser(rhs, true) {
+                    true => write!(f, "({rhs})"),
+                    false => write!(f, "{rhs}"),
+                }
+            }
+        }
+    }
+}
+
+impl Display for LocalRef {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let direction = if self.forward { 'f' } else { 'b' };
+        write!(f, "{}{direction}", self.number)
+    }
+}
+
+impl Display for Directive<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Directive::Text => f.write_str(".text"),
+            Directive::Data => f.write_str(".data"),
+            Directive::Bss => f.write_str(".bss"),
+            Directive::Section {
+                name,
+                flags,
+                kind,
+                args,
+            } => {
+                write!(f, ".section\t{name}")?;
+                if flags.is_some() || kind.is_some() || !args.is_empty() {
+                    f.write_char(',')?;
+                    if let Some(flags) = flags {
+                        write!(f, "\"{flags}\"")?;
+                    }
+                }
+                if let Some(kind) = kind {
+                    write!(f, ",@{kind}")?;
+                }
+                args.iter().try_for_each(|arg| write!(f, ",{arg}"))
+            }
+            Directive::Align { pow2, fill, max } => {
+                write!(f, ".align\t{pow2}")?;
+                optional_args(f, fill, max)
+            }
+            Directive::BAlign { bytes, fill, max } => {
+                write!(f, ".balign\t{bytes}")?;
+                optional_args(f, fill, max)
+            }
+            Directive::String {
+                zero_terminated,
+                values,
+            } => {
+                let name = if *zero_terminated {
+                    ".string"
+                } else {
+                    ".ascii"
+                };
+                write!(f, "{name}\t")?;
+                comma_list(f, values)
+            }
+            Directive::Values { size, values } => {
+                let name = match size {
+                    1 => ".byte",
+                    2 => ".hword",
+                    4 => ".word",
+                    _ => ".dword",
+                };
+                write!(f, "{name}\t")?;
+                comma_list(f, values)
+            }
+            Directive::Space { size, fill: None } => write!(f, ".zero\t{size}"),
+            Directive::Space {
+                size,
+                fill: Some(fill),
+            } => write!(f, ".space\t{size}, {fill}"),
+            Directive::Type { symbol, kind } => write!(f, ".type\t{symbol}, %{kind}"),
+            Directive::Size { symbol, size } => write!(f, ".size\t{symbol}, {size}"),
+            Directive::Global(symbol) => write!(f, ".global\t{symbol}"),
+            Directive::Local(symbol) => write!(f, ".local\t{symbol}"),
+            Directive::Weak(symbol) => write!(f, ".weak\t{symbol}"),
+            Directive::File { number, name } => match number {
+                Some(number) => write!(f, ".file\t{number} {name}"),
+                None => write!(f, ".file\t{name}"),
+            },
+            Directive::Ident(comment) => write!(f, ".ident\t{comment}"),
+            Directive::Raw { name, args: "" } => f.write_str(name),
+            Directive::Raw { name, args } => write!(f, "{name}\t{args}"),
+        }
+    }
+}
+
+/// The `,fill,max` of `.align` and `.balign`, leaving out what isn't there.
+fn optional_args(f: &mut Formatter, fill: &Option<Expr>, max: &Option<Expr>) -> fmt::Result {
+    match (fill, max) {
+        (None, None) => Ok(()),
+        (Some(fill), None) => write!(f, ",{fill}"),
+        (None, Some(max)) => write!(f, ",,{max}"),
+        (Some(fill), Some(max)) => write!(f, ",{fill},{max}"),
+    }
+}
+
+impl Display for SectionFlags {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let flags = [
+            (self.alloc, 'a'),
+            (self.write, 'w'),
+            (self.exec, 'x'),
+            (self.merge, 'M'),
+            (self.strings, 'S'),
+            (self.group, 'G'),
+            (self.tls, 'T'),
+        ];
+        flags
+            .iter()
+            .filter(|(set, _)| *set)
+            .try_for_each(|(_, flag)| f.write_char(*flag))
+    }
+}
+
+impl Display for SymbolType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SymbolType::Function => "function",
+            SymbolType::Object => "object",
+            SymbolType::TlsObject => "tls_object",
+            SymbolType::Common => "common",
+            SymbolType::NoType => "notype",
+            SymbolType::GnuIndirectFunction => "gnu_indirect_function",
+            SymbolType::GnuUniqueObject => "gnu_unique_object",
+        })
+    }
+}
+
+/// Quoted, with bytes that aren't printable ASCII as three-digit octal
+/// escapes so that a digit after them can't be read as part of the escape.
+impl Display for StringLiteral {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_char('"')?;
+        for &b in &self.0 {
+            match b {
+                b'"' | b'\\' => write!(f, "\\{}", b as char)?,
+                b'\n' => f.write_str("\\n")?,
+                b'\t' => f.write_str("\\t")?,
+                b' '..=b'~' => f.write_char(b as char)?,
+                _ => write!(f, "\\{b:03o}")?,
+            }
+        }
+        f.write_char('"')
+    }
+}
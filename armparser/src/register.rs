@@ -1,6 +1,9 @@
-use crate::{Parse, Rule};
+use std::sync::LazyLock;
 
-#[derive(Debug, Clone, PartialEq)]
+use crate::error::{suggest, Snippet};
+use crate::{ArmParserError, Parse, Rule};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Register {
     pub reg_type: RegisterType,
     pub reg_num: u8,
@@ -9,12 +12,65 @@ pub struct Register {
 pub enum RegisterType {
     Half,
     Full,
+    Byte,
     HalfFloat,
     SingleFloat,
     DoubleFloat,
+    Quad,
     StackPointer,
+    /// The 32-bit view of the stack pointer, `wsp`
+    WordStackPointer,
     XZR,
     WZR,
+    /// A whole SIMD register, as in `v0.4s`
+    Vector(Arrangement),
+    /// A SIMD register with just its element size, as in `{v0.s, v1.s}[1]`
+    Element(ElementSize),
+    /// One element of a SIMD register, as in `v1.s[2]`
+    Lane(ElementSize, u8),
+    /// A 32-bit group of elements, indexed like a lane, as in the `v2.4b[1]`
+    /// of `sdot`
+    LaneGroup(Arrangement, u8),
+}
+
+/// Size of the elements of a SIMD register.
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+pub enum ElementSize {
+    B,
+    H,
+    S,
+    D,
+    Q,
+}
+
+/// How a SIMD register is split into lanes, as in the `.4s` of `v0.4s`.
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+pub struct Arrangement {
+    pub lanes: u8,
+    pub size: ElementSize,
+}
+
+impl ElementSize {
+    pub fn bits(&self) -> u32 {
+        match self {
+            ElementSize::B => 8,
+            ElementSize::H => 16,
+            ElementSize::S => 32,
+            ElementSize::D => 64,
+            ElementSize::Q => 128,
+        }
+    }
+
+    fn from_char(c: char) -> Self {
+        match c {
+            'b' => ElementSize::B,
+            'h' => ElementSize::H,
+            's' => ElementSize::S,
+            'd' => ElementSize::D,
+            'q' => ElementSize::Q,
+            _ => unreachable!("Invalid element size"),
+        }
+    }
 }
 impl Register {
     pub const fn new(reg_type: RegisterType, reg_num: u8) -> Self {
@@ -29,49 +85,193 @@ impl Parse<'_> for Register {
         debug_assert_eq!(pair.as_rule(), crate::Rule::register);
         let inner = pair.into_inner().next().expect("register inner");
         match inner.as_rule() {
-            Rule::full_reg => match inner.as_str() {
-                "zxr" => Ok(XZR),
-                "sp" => Ok(SP),
-                "fp" => Ok(FP),
-                "lr" => Ok(LR),
-                _ => {
-                    let reg_num = inner.as_str()[1..].parse().unwrap();
-                    Ok(Register::new(RegisterType::Full, reg_num))
-                }
-            },
-            Rule::half_reg => match inner.as_str() {
-                "wzr" => Ok(WZR),
-                _ => {
-                    let reg_num = inner.as_str()[1..].parse().unwrap();
-                    Ok(Register::new(RegisterType::Half, reg_num))
-                }
-            },
-            Rule::float_reg => {
-                let inner = inner.into_inner().next().expect("float reg inner");
-                match inner.as_rule() {
-                    Rule::float16 => {
-                        let reg_num = inner.as_str()[1..].parse().unwrap();
-                        Ok(Register::new(RegisterType::HalfFloat, reg_num))
-                    }
-                    Rule::float32 => {
-                        let reg_num = inner.as_str()[1..].parse().unwrap();
-                        Ok(Register::new(RegisterType::SingleFloat, reg_num))
-                    }
-                    Rule::double64 => {
-                        let reg_num = inner.as_str()[1..].parse().unwrap();
-                        Ok(Register::new(RegisterType::DoubleFloat, reg_num))
-                    }
-                    _ => unreachable!("float reg inner"),
-                }
-            }
+            Rule::full_reg | Rule::half_reg | Rule::float_reg => scalar(&inner),
+            Rule::vector_reg => parse_vector(inner),
             _ => unreachable!("register inner"),
         }
     }
 }
 
+/// Registers with a name of their own rather than a prefix and a number.
+pub const NAMED_REGISTERS: &[(&str, Register)] = &[
+    ("sp", SP),
+    ("wsp", WSP),
+    ("xzr", XZR),
+    ("wzr", WZR),
+    ("fp", FP),
+    ("lr", LR),
+    ("xr", XR),
+    ("ip0", IP0),
+    ("ip1", IP1),
+];
+
+/// Prefixes of the numbered registers and the type each gives, as in `x12`.
+pub const NUMBERED_REGISTERS: &[(&str, RegisterType)] = &[
+    ("x", RegisterType::Full),
+    ("w", RegisterType::Half),
+    ("b", RegisterType::Byte),
+    ("h", RegisterType::HalfFloat),
+    ("s", RegisterType::SingleFloat),
+    ("d", RegisterType::DoubleFloat),
+    ("q", RegisterType::Quad),
+];
+
+impl Register {
+    /// Looks up a scalar register by any of its names. Number 31 of `x` and
+    /// `w` has no name of its own, since it is the stack pointer or the zero
+    /// register depending on the instruction.
+    pub fn from_name(name: &str) -> Option<Register> {
+        let name = name.to_ascii_lowercase();
+        if let Some(&(_, reg)) = NAMED_REGISTERS.iter().find(|(n, _)| *n == name) {
+            return Some(reg);
+        }
+        let &(prefix, reg_type) = NUMBERED_REGISTERS
+            .iter()
+            .find(|(prefix, _)| name.starts_with(prefix))?;
+        let digits = &name[prefix.len()..];
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let max = match reg_type {
+            RegisterType::Full | RegisterType::Half => 30,
+            _ => 31,
+        };
+        match digits.parse() {
+            Ok(n) if n <= max => Some(Register::new(reg_type, n)),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a general purpose or scalar SIMD register like `x3`, `wsp` or `d7`.
+fn scalar(pair: &pest::iterators::Pair<Rule>) -> Result<Register, crate::Err> {
+    let text = pair.as_str();
+    if let Some(reg) = Register::from_name(text) {
+        return Ok(reg);
+    }
+    let at = Snippet::of(pair);
+    let lower = text.to_ascii_lowercase();
+    match lower.as_str() {
+        "x31" | "w31" => Err(ArmParserError::InvalidOperand {
+            text: text.to_string(),
+            message: format!(
+                "register 31 is written `{}sp` or `{}zr`",
+                if lower == "w31" { "w" } else { "" },
+                &lower[..1],
+            ),
+            at,
+        }),
+        _ => Err(ArmParserError::InvalidRegister {
+            text: text.to_string(),
+            suggestion: None,
+            at,
+        }),
+    }
+}
+
+/// Parses a SIMD register with its arrangement or lane, e.g. `v0.4s` or
+/// `v1.s[2]`.
+fn parse_vector(pair: pest::iterators::Pair<Rule>) -> Result<Register, crate::Err> {
+    let text = pair.as_str().to_string();
+    let at = Snippet::of(&pair);
+    let mut inner = pair.into_inner();
+    let reg_num = reg_num(&inner.next().expect("No inner pair"))?;
+    let layout = inner.next().expect("No inner pair");
+    let size = layout.as_str().chars().last().expect("Empty layout");
+    let size = ElementSize::from_char(size.to_ascii_lowercase());
+    let invalid = || ArmParserError::InvalidRegister {
+        text: text.clone(),
+        suggestion: None,
+        at: at.clone(),
+    };
+    let lanes = || -> Result<u8, crate::Err> {
+        layout.as_str()[..layout.as_str().len() - 1]
+            .parse()
+            .map_err(|_| invalid())
+    };
+    let reg_type = match (layout.as_rule(), inner.next()) {
+        (Rule::arrangement, None) => {
+            let lanes = lanes()?;
+            // the whole register is 64 or 128 bits
+            if !matches!(u32::from(lanes) * size.bits(), 64 | 128) {
+                return Err(invalid());
+            }
+            RegisterType::Vector(Arrangement { lanes, size })
+        }
+        // `.4b` and `.2h` only name the group an indexed element picks
+        (Rule::arrangement, Some(lane)) => {
+            let lanes = lanes()?;
+            let index = lane_index(&lane)?;
+            if u32::from(lanes) * size.bits() != 32 || index >= 4 {
+                return Err(invalid());
+            }
+            RegisterType::LaneGroup(Arrangement { lanes, size }, index)
+        }
+        (Rule::element_size, None) => RegisterType::Element(size),
+        (Rule::element_size, Some(lane)) => {
+            let index = lane_index(&lane)?;
+            if u32::from(index) >= 128 / size.bits() {
+                return Err(invalid());
+            }
+            RegisterType::Lane(size, index)
+        }
+        _ => return Err(invalid()),
+    };
+    Ok(Register::new(reg_type, reg_num))
+}
+
+/// Parses a lane index like the `[2]` of `v1.s[2]`.
+pub(crate) fn lane_index(pair: &pest::iterators::Pair<Rule>) -> Result<u8, crate::Err> {
+    debug_assert_eq!(pair.as_rule(), Rule::lane);
+    let index = pair.clone().into_inner().next().expect("No inner pair");
+    index
+        .as_str()
+        .parse()
+        .map_err(|source| ArmParserError::ParseIntError {
+            text: index.as_str().to_string(),
+            source,
+            at: Snippet::of(&index),
+        })
+}
+
+/// Parses the number following the register prefix, e.g. the `12` of `x12`.
+fn reg_num(pair: &pest::iterators::Pair<Rule>) -> Result<u8, crate::Err> {
+    match pair.as_str()[1..].parse() {
+        Ok(n) if n <= 31 => Ok(n),
+        _ => Err(ArmParserError::InvalidRegister {
+            text: pair.as_str().to_string(),
+            suggestion: None,
+            at: Snippet::of(pair),
+        }),
+    }
+}
+
+/// Every spelling the grammar accepts for a register, used to suggest a fix
+/// for misspelled ones.
+pub static REGISTER_NAMES: LazyLock<Vec<String>> = LazyLock::new(|| {
+    let mut names: Vec<String> = NAMED_REGISTERS.iter().map(|(n, _)| n.to_string()).collect();
+    for (prefix, reg_type) in NUMBERED_REGISTERS {
+        let max = match reg_type {
+            RegisterType::Full | RegisterType::Half => 30,
+            _ => 31,
+        };
+        names.extend((0..=max).map(|n| format!("{prefix}{n}")));
+    }
+    names
+});
+
+/// Suggests the register name closest to `text`.
+pub fn suggest_register(text: &str) -> Option<String> {
+    suggest(text, REGISTER_NAMES.iter().map(String::as_str))
+}
+
 pub const FP: Register = Register::new(RegisterType::Full, 29);
 pub const LR: Register = Register::new(RegisterType::Full, 30);
+pub const XR: Register = Register::new(RegisterType::Full, 8);
+pub const IP0: Register = Register::new(RegisterType::Full, 16);
+pub const IP1: Register = Register::new(RegisterType::Full, 17);
 pub const SP: Register = Register::new(RegisterType::StackPointer, 31);
+pub const WSP: Register = Register::new(RegisterType::WordStackPointer, 31);
 pub const WZR: Register = Register::new(RegisterType::WZR, 31);
 pub const XZR: Register = Register::new(RegisterType::XZR, 31);
 macro_rules! build_regs {
@@ -1,11 +1,15 @@
 use crate::{ARM64Parser, Rule, *};
+use condition::Condition;
+use directive::Directive;
 use expect_test::{expect, Expect};
+use expr::Expr;
+use opcode::Mnemonic;
 use pest::Parser;
 use register::*;
 use std::fmt::Debug;
 fn parse_format<'a, N: Parse<'a> + Debug>(input: &[&'a str], rule: Rule, expect: Expect) {
     let res = input
-        .into_iter()
+        .iter()
         .map(|l| {
             let parsed = ARM64Parser::parse(rule, l).unwrap().next().unwrap();
             let parsed = N::parse(parsed).unwrap();
@@ -16,14 +20,14 @@ fn parse_format<'a, N: Parse<'a> + Debug>(input: &[&'a str], rule: Rule, expect:
 }
 fn check_regs(input: &[&str], expect: &[Register]) {
     let res = input
-        .into_iter()
+        .iter()
         .map(|l| {
             let parsed = ARM64Parser::parse(Rule::register, l)
                 .unwrap()
                 .next()
                 .unwrap();
-            let parsed = Register::parse(parsed).unwrap();
-            parsed
+
+            Register::parse(parsed).unwrap()
         })
         .collect::<Vec<Register>>();
     assert_eq!(res, expect);
@@ -34,6 +38,22 @@ fn parse_src(src: &str) {
         println!("{:?}\n", l);
     });
 }
+fn check_src(src: &str, expect: Expect) {
+    let res = parse_asm(src)
+        .unwrap()
+        .iter()
+        .map(|l| {
+            let Location {
+                line,
+                col,
+                start,
+                end,
+            } = l.loc;
+            format!("{line}:{col} {start}..{end} {:?}\n", l.line)
+        })
+        .collect::<String>();
+    expect.assert_eq(&res);
+}
 #[test]
 fn general_register() {
     check_regs(
@@ -81,19 +101,234 @@ fn float_register() {
     );
 }
 #[test]
+fn register_aliases() {
+    use RegisterType::*;
+    let table = [
+        ("x0", Register::X0),
+        ("x30", Register::X30),
+        ("w0", Register::W0),
+        ("w30", Register::W30),
+        ("sp", SP),
+        ("wsp", WSP),
+        ("xzr", register::XZR),
+        ("wzr", register::WZR),
+        ("fp", Register::X29),
+        ("x29", FP),
+        ("lr", Register::X30),
+        ("x30", LR),
+        ("xr", Register::X8),
+        ("ip0", Register::X16),
+        ("ip1", Register::X17),
+        ("b31", Register::new(Byte, 31)),
+        ("h7", Register::H7),
+        ("s31", Register::S31),
+        ("d15", Register::D15),
+        ("q0", Register::new(Quad, 0)),
+        (
+            "v31.16b",
+            Register::new(
+                Vector(Arrangement {
+                    lanes: 16,
+                    size: ElementSize::B,
+                }),
+                31,
+            ),
+        ),
+        ("v2.d[1]", Register::new(Lane(ElementSize::D, 1), 2)),
+        ("W0", Register::W0),
+        ("SP", SP),
+        ("Xzr", register::XZR),
+        ("D15", Register::D15),
+        ("V2.D[1]", Register::new(Lane(ElementSize::D, 1), 2)),
+    ];
+    let (names, regs): (Vec<_>, Vec<_>) = table.into_iter().unzip();
+    check_regs(&names, &regs);
+
+    for (name, reg) in NAMED_REGISTERS {
+        assert_eq!(Register::from_name(name), Some(*reg), "{name}");
+    }
+    for name in ["x31", "w31", "x32", "q32", "zxr", "x", "ip2", "v0"] {
+        assert_eq!(Register::from_name(name), None, "{name}");
+    }
+    assert!(REGISTER_NAMES
+        .iter()
+        .all(|n| Register::from_name(n).is_some()));
+}
+#[test]
+fn simd_register() {
+    parse_format::<Register>(
+        &[
+            "b0",
+            "q31",
+            "v0.8b",
+            "v1.16b",
+            "v2.4h",
+            "v3.8h",
+            "v4.2s",
+            "v5.4s",
+            "v6.1d",
+            "v7.2d",
+            "v8.1q",
+            "v1.s[2]",
+            "v31.b[15]",
+            "v2.d[ 1 ]",
+            "v0.s",
+            "v2.4b[1]",
+            "v3.2h[3]",
+        ],
+        Rule::register,
+        expect![[r#"
+            Register { reg_type: Byte, reg_num: 0 }
+            Register { reg_type: Quad, reg_num: 31 }
+            Register { reg_type: Vector(Arrangement { lanes: 8, size: B }), reg_num: 0 }
+            Register { reg_type: Vector(Arrangement { lanes: 16, size: B }), reg_num: 1 }
+            Register { reg_type: Vector(Arrangement { lanes: 4, size: H }), reg_num: 2 }
+            Register { reg_type: Vector(Arrangement { lanes: 8, size: H }), reg_num: 3 }
+            Register { reg_type: Vector(Arrangement { lanes: 2, size: S }), reg_num: 4 }
+            Register { reg_type: Vector(Arrangement { lanes: 4, size: S }), reg_num: 5 }
+            Register { reg_type: Vector(Arrangement { lanes: 1, size: D }), reg_num: 6 }
+            Register { reg_type: Vector(Arrangement { lanes: 2, size: D }), reg_num: 7 }
+            Register { reg_type: Vector(Arrangement { lanes: 1, size: Q }), reg_num: 8 }
+            Register { reg_type: Lane(S, 2), reg_num: 1 }
+            Register { reg_type: Lane(B, 15), reg_num: 31 }
+            Register { reg_type: Lane(D, 1), reg_num: 2 }
+            Register { reg_type: Element(S), reg_num: 0 }
+            Register { reg_type: LaneGroup(Arrangement { lanes: 4, size: B }, 1), reg_num: 2 }
+            Register { reg_type: LaneGroup(Arrangement { lanes: 2, size: H }, 3), reg_num: 3 }
+        "#]],
+    );
+}
+#[test]
+fn simd_register_list() {
+    parse_format::<RegisterList>(
+        &[
+            "{v0.16b-v3.16b}",
+            "{v0.4s, v1.4s}",
+            "{v0.s, v1.s}[1]",
+            "{ q0 }",
+        ],
+        Rule::reglist,
+        expect![[r#"
+            RegisterList { regs: [Register { reg_type: Vector(Arrangement { lanes: 16, size: B }), reg_num: 0 }, Register { reg_type: Vector(Arrangement { lanes: 16, size: B }), reg_num: 1 }, Register { reg_type: Vector(Arrangement { lanes: 16, size: B }), reg_num: 2 }, Register { reg_type: Vector(Arrangement { lanes: 16, size: B }), reg_num: 3 }], lane: None }
+            RegisterList { regs: [Register { reg_type: Vector(Arrangement { lanes: 4, size: S }), reg_num: 0 }, Register { reg_type: Vector(Arrangement { lanes: 4, size: S }), reg_num: 1 }], lane: None }
+            RegisterList { regs: [Register { reg_type: Element(S), reg_num: 0 }, Register { reg_type: Element(S), reg_num: 1 }], lane: Some(1) }
+            RegisterList { regs: [Register { reg_type: Quad, reg_num: 0 }], lane: None }
+        "#]],
+    );
+}
+#[test]
 fn immediate() {
     parse_format::<Immediate>(
-        &["#0", "#0x0", "-1", "0x12345678", "0xABCDEF"],
+        &[
+            "#0",
+            "#0x0",
+            "-1",
+            "0x12345678",
+            "0xABCDEF",
+            "#0b1010",
+            "#-0x10",
+            "0X1F",
+            "#'a'",
+            "#'\\n'",
+            "#-'a'",
+            "#0xfffffffffffffff0",
+            "#0xffffffffffffffff",
+            "#-9223372036854775808",
+        ],
         Rule::immediate,
         expect![[r#"
-            Immediate(0)
-            Immediate(0)
-            Immediate(-1)
-            Immediate(305419896)
-            Immediate(11259375)
+            Int(0)
+            Int(0)
+            Int(-1)
+            Int(305419896)
+            Int(11259375)
+            Int(10)
+            Int(-16)
+            Int(31)
+            Char('a')
+            Char('\n')
+            Int(-97)
+            Int(-16)
+            Int(-1)
+            Int(-9223372036854775808)
+        "#]],
+    );
+}
+
+#[test]
+fn immediate_operand() {
+    parse_format::<Instruction>(
+        &[
+            "fmov d0, #1.5e0",
+            "fmov s0, #-2.0",
+            "fcmp d0, #0.0",
+            "fmov d1, 1e-1",
+            "mov w0, #'a'",
+            "mov w0, #'a'+1",
+            "mov x0, #0b1010",
+            "mov x0, #-0x10",
+            "cmp w0, 0X1F",
+            "and x0, x0, #0xfffffffffffffff0",
+            "mov x0, #0xffffffffffffffff",
+        ],
+        Rule::operation,
+        expect![[r#"
+            Instruction { opcode: Opcode("fmov"), condition: None, operands: [Register(Register { reg_type: DoubleFloat, reg_num: 0 }), Immediate(Float(1.5))] }
+            Instruction { opcode: Opcode("fmov"), condition: None, operands: [Register(Register { reg_type: SingleFloat, reg_num: 0 }), Immediate(Float(-2.0))] }
+            Instruction { opcode: Opcode("fcmp"), condition: None, operands: [Register(Register { reg_type: DoubleFloat, reg_num: 0 }), Immediate(Float(0.0))] }
+            Instruction { opcode: Opcode("fmov"), condition: None, operands: [Register(Register { reg_type: DoubleFloat, reg_num: 1 }), Immediate(Float(0.1))] }
+            Instruction { opcode: Opcode("mov"), condition: None, operands: [Register(Register { reg_type: Half, reg_num: 0 }), Immediate(Char('a'))] }
+            Instruction { opcode: Opcode("mov"), condition: None, operands: [Register(Register { reg_type: Half, reg_num: 0 }), Immediate(Int(98))] }
+            Instruction { opcode: Opcode("mov"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Immediate(Int(10))] }
+            Instruction { opcode: Opcode("mov"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Immediate(Int(-16))] }
+            Instruction { opcode: Opcode("cmp"), condition: None, operands: [Register(Register { reg_type: Half, reg_num: 0 }), Immediate(Int(31))] }
+            Instruction { opcode: Opcode("and"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 0 }), Immediate(Int(-16))] }
+            Instruction { opcode: Opcode("mov"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Immediate(Int(-1))] }
         "#]],
     );
 }
+
+#[test]
+fn immediate_range() {
+    let res = [
+        Immediate::Int(4095),
+        Immediate::Int(4096),
+        Immediate::Int(-256),
+        Immediate::Int(-257),
+        Immediate::Char('a'),
+        Immediate::Float(1.5),
+        Immediate::Float(-0.125),
+        Immediate::Float(0.1),
+        Immediate::Int(31),
+        Immediate::Int(0),
+    ]
+    .iter()
+    .map(|imm| {
+        format!(
+            "{imm:?}: u12 {} s9 {} s0 {} u16 {:?} fp8 {:?}\n",
+            imm.fits_unsigned(12),
+            imm.fits_signed(9),
+            imm.fits_signed(0),
+            imm.to_int::<u16>(),
+            imm.to_fp8(),
+        )
+    })
+    .collect::<String>();
+    expect![[r#"
+        Int(4095): u12 true s9 false s0 false u16 Some(4095) fp8 None
+        Int(4096): u12 false s9 false s0 false u16 Some(4096) fp8 None
+        Int(-256): u12 false s9 true s0 false u16 None fp8 None
+        Int(-257): u12 false s9 false s0 false u16 None fp8 None
+        Char('a'): u12 true s9 true s0 false u16 Some(97) fp8 None
+        Float(1.5): u12 false s9 false s0 false u16 None fp8 Some(120)
+        Float(-0.125): u12 false s9 false s0 false u16 None fp8 Some(192)
+        Float(0.1): u12 false s9 false s0 false u16 None fp8 None
+        Int(31): u12 true s9 true s0 false u16 Some(31) fp8 Some(63)
+        Int(0): u12 true s9 true s0 true u16 Some(0) fp8 None
+    "#]]
+    .assert_eq(&res);
+}
+
 #[test]
 fn shift_type() {
     parse_format::<ShiftType>(
@@ -119,12 +354,38 @@ fn shift_reg() {
         ],
         Rule::shifted_register,
         expect![[r#"
-            ShiftedRegister { reg: Register { reg_type: Full, reg_num: 0 }, shift_type: LSL, shift_amount: Some(Immediate(Immediate(0))) }
-            ShiftedRegister { reg: Register { reg_type: Half, reg_num: 0 }, shift_type: LSR, shift_amount: Some(Immediate(Immediate(0))) }
-            ShiftedRegister { reg: Register { reg_type: Full, reg_num: 0 }, shift_type: ASR, shift_amount: Some(Immediate(Immediate(0))) }
-            ShiftedRegister { reg: Register { reg_type: Half, reg_num: 0 }, shift_type: ROR, shift_amount: Some(Immediate(Immediate(0))) }
+            ShiftedRegister { reg: Register { reg_type: Full, reg_num: 0 }, shift_type: LSL, shift_amount: Some(Immediate(Int(0))) }
+            ShiftedRegister { reg: Register { reg_type: Half, reg_num: 0 }, shift_type: LSR, shift_amount: Some(Immediate(Int(0))) }
+            ShiftedRegister { reg: Register { reg_type: Full, reg_num: 0 }, shift_type: ASR, shift_amount: Some(Immediate(Int(0))) }
+            ShiftedRegister { reg: Register { reg_type: Half, reg_num: 0 }, shift_type: ROR, shift_amount: Some(Immediate(Int(0))) }
             ShiftedRegister { reg: Register { reg_type: Full, reg_num: 0 }, shift_type: RRX, shift_amount: Some(Register(Register { reg_type: Full, reg_num: 0 })) }
-            "#]],
+        "#]],
+    );
+}
+#[test]
+fn extend_reg() {
+    parse_format::<ExtendedRegister>(
+        &[
+            "w2, sxtw #2",
+            "w2, uxtw",
+            "x3, sxtx 3",
+            "w0, uxtb #0",
+            "w0,uxth",
+            "w0, sxtb",
+            "w0, sxth #1",
+            "x0, uxtx #4",
+        ],
+        Rule::extended_register,
+        expect![[r#"
+            ExtendedRegister { reg: Register { reg_type: Half, reg_num: 2 }, extend_type: SXTW, shift_amount: Some(Int(2)) }
+            ExtendedRegister { reg: Register { reg_type: Half, reg_num: 2 }, extend_type: UXTW, shift_amount: None }
+            ExtendedRegister { reg: Register { reg_type: Full, reg_num: 3 }, extend_type: SXTX, shift_amount: Some(Int(3)) }
+            ExtendedRegister { reg: Register { reg_type: Half, reg_num: 0 }, extend_type: UXTB, shift_amount: Some(Int(0)) }
+            ExtendedRegister { reg: Register { reg_type: Half, reg_num: 0 }, extend_type: UXTH, shift_amount: None }
+            ExtendedRegister { reg: Register { reg_type: Half, reg_num: 0 }, extend_type: SXTB, shift_amount: None }
+            ExtendedRegister { reg: Register { reg_type: Half, reg_num: 0 }, extend_type: SXTH, shift_amount: Some(Int(1)) }
+            ExtendedRegister { reg: Register { reg_type: Full, reg_num: 0 }, extend_type: UXTX, shift_amount: Some(Int(4)) }
+        "#]],
     );
 }
 #[test]
@@ -134,33 +395,44 @@ fn indirect() {
             "[x0]",
             "[x0, #0]",
             "[x0, w0]",
-            "[x0, w0]!",
+            "[x0, #8]!",
+            "[sp, -48]!",
+            "[x0], #16",
+            "[x0], -16",
+            "[x0], x1",
             "[x0, w0 lsl #0]",
             "[x0,:got_lo12:__stack_chk_guard]",
+            "[x1, w2, uxtw #3]",
+            "[x1, w2, sxtw]",
         ],
         Rule::indirect,
         expect![[r#"
-            Indirect { base: Register { reg_type: Full, reg_num: 0 }, offset: None, writeback: false }
-            Indirect { base: Register { reg_type: Full, reg_num: 0 }, offset: Some(Immediate(Immediate(0))), writeback: false }
-            Indirect { base: Register { reg_type: Full, reg_num: 0 }, offset: Some(Register(Register { reg_type: Half, reg_num: 0 })), writeback: false }
-            Indirect { base: Register { reg_type: Full, reg_num: 0 }, offset: Some(Register(Register { reg_type: Half, reg_num: 0 })), writeback: true }
-            Indirect { base: Register { reg_type: Full, reg_num: 0 }, offset: Some(ShiftedRegister(ShiftedRegister { reg: Register { reg_type: Half, reg_num: 0 }, shift_type: LSL, shift_amount: Some(Immediate(Immediate(0))) })), writeback: false }
-            Indirect { base: Register { reg_type: Full, reg_num: 0 }, offset: Some(ProcLoad(ProcLoad { mode: "got_lo12", target: "__stack_chk_guard" })), writeback: false }
-            "#]],
+            Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: Base }
+            Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: Offset(Immediate(Int(0))) }
+            Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: Offset(Register(Register { reg_type: Half, reg_num: 0 })) }
+            Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: PreIndex(Int(8)) }
+            Indirect { base: Register { reg_type: StackPointer, reg_num: 31 }, mode: PreIndex(Int(-48)) }
+            Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: PostIndex(Int(16)) }
+            Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: PostIndex(Int(-16)) }
+            Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: PostIndexRegister(Register { reg_type: Full, reg_num: 1 }) }
+            Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: Offset(ShiftedRegister(ShiftedRegister { reg: Register { reg_type: Half, reg_num: 0 }, shift_type: LSL, shift_amount: Some(Immediate(Int(0))) })) }
+            Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: Offset(ProcLoad(ProcLoad { modifier: GotLo12, expr: Symbol("__stack_chk_guard") })) }
+            Indirect { base: Register { reg_type: Full, reg_num: 1 }, mode: Offset(ExtendedRegister(ExtendedRegister { reg: Register { reg_type: Half, reg_num: 2 }, extend_type: UXTW, shift_amount: Some(Int(3)) })) }
+            Indirect { base: Register { reg_type: Full, reg_num: 1 }, mode: Offset(ExtendedRegister(ExtendedRegister { reg: Register { reg_type: Half, reg_num: 2 }, extend_type: SXTW, shift_amount: None })) }
+        "#]],
     );
 }
 
 fn check_reg_list(input: &[&str], expect: &[&[Register]]) {
     let res = input
-        .into_iter()
+        .iter()
         .map(|l| {
             let parsed = ARM64Parser::parse(Rule::reglist, l)
                 .unwrap()
                 .next()
                 .unwrap();
 
-            let res = RegisterList::parse(parsed).unwrap().regs;
-            res
+            RegisterList::parse(parsed).unwrap().regs
         })
         .collect::<Vec<Vec<Register>>>();
     assert_eq!(res, expect);
@@ -195,43 +467,966 @@ fn reg_list() {
 
 #[test]
 fn opcode() {
-    parse_format::<Opcode>(
-        &["add", "sub", "mul", "div", "mov", "load", "store", "jmp"],
+    parse_format::<Mnemonic>(
+        &[
+            "add", "SUB", "Mul", "sdiv", "mov", "ldr", "str", "b", "b.ne", "bne", "B.HS", "bcs",
+            "bc.eq", "cbgt", "cble", "cbhlo", "ldaddal",
+        ],
         Rule::opcode,
         expect![[r#"
-            Opcode("add")
-            Opcode("sub")
-            Opcode("mul")
-            Opcode("div")
-            Opcode("mov")
-            Opcode("load")
-            Opcode("store")
-            Opcode("jmp")
+            Mnemonic { opcode: Opcode("add"), condition: None }
+            Mnemonic { opcode: Opcode("sub"), condition: None }
+            Mnemonic { opcode: Opcode("mul"), condition: None }
+            Mnemonic { opcode: Opcode("sdiv"), condition: None }
+            Mnemonic { opcode: Opcode("mov"), condition: None }
+            Mnemonic { opcode: Opcode("ldr"), condition: None }
+            Mnemonic { opcode: Opcode("str"), condition: None }
+            Mnemonic { opcode: Opcode("b"), condition: None }
+            Mnemonic { opcode: Opcode("b"), condition: Some(NE) }
+            Mnemonic { opcode: Opcode("b"), condition: Some(NE) }
+            Mnemonic { opcode: Opcode("b"), condition: Some(HS) }
+            Mnemonic { opcode: Opcode("b"), condition: Some(HS) }
+            Mnemonic { opcode: Opcode("bc"), condition: Some(EQ) }
+            Mnemonic { opcode: Opcode("cb"), condition: Some(GT) }
+            Mnemonic { opcode: Opcode("cb"), condition: Some(LE) }
+            Mnemonic { opcode: Opcode("cbh"), condition: Some(LO) }
+            Mnemonic { opcode: Opcode("ldaddal"), condition: None }
+        "#]],
+    );
+}
+#[test]
+fn condition() {
+    parse_format::<Condition>(
+        &[
+            "eq", "NE", "cs", "hs", "cc", "lo", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt",
+            "gt", "Le", "al", "nv",
+        ],
+        Rule::condition,
+        expect![[r#"
+            EQ
+            NE
+            HS
+            HS
+            LO
+            LO
+            MI
+            PL
+            VS
+            VC
+            HI
+            LS
+            GE
+            LT
+            GT
+            LE
+            AL
+            NV
         "#]],
     );
 }
 #[test]
+fn opcode_table() {
+    let res = [
+        "adrp", "ldrsw", "sxtw", "csel", "fcmpe", "ldaddal", "stadd", "movk", "b", "cb", "ret",
+        "dmb", "msr", "fmov", "mov",
+    ]
+    .iter()
+    .map(|m| {
+        let info = Opcode::lookup(m).unwrap().info();
+        format!(
+            "{m}: {:?} {:?} alias_of={:?} aliases={:?}\n",
+            info.class, info.description, info.alias_of, info.aliases
+        )
+    })
+    .collect::<String>();
+    expect![[r#"
+        adrp: DataProcessing "Form PC-relative address to 4KB page" alias_of=[] aliases=[]
+        ldrsw: LoadStore "Load register signed word (immediate)" alias_of=[] aliases=[]
+        sxtw: DataProcessing "Sign extend word" alias_of=["sbfm"] aliases=[]
+        csel: DataProcessing "Conditional select" alias_of=[] aliases=[]
+        fcmpe: FloatingPoint "Floating-point signaling compare (scalar)" alias_of=[] aliases=[]
+        ldaddal: Atomic "Atomic add on word or doubleword in memory" alias_of=[] aliases=[]
+        stadd: Atomic "Atomic add on word or doubleword in memory, without return" alias_of=["ldadd"] aliases=[]
+        movk: DataProcessing "Move wide with keep" alias_of=[] aliases=[]
+        b: Branch "Branch" alias_of=[] aliases=[]
+        cb: Branch "Compare register with immediate and branch" alias_of=[] aliases=[]
+        ret: Branch "Return from subroutine" alias_of=[] aliases=[]
+        dmb: System "Data memory barrier" alias_of=[] aliases=[]
+        msr: System "Move immediate value to special register" alias_of=[] aliases=["smstart", "smstop"]
+        fmov: FloatingPoint "Floating-point move to or from general-purpose register without conversion" alias_of=[] aliases=[]
+        mov: DataProcessing "Move bitmask immediate value" alias_of=["orr", "movn", "add", "movz", "ins", "umov"] aliases=[]
+    "#]].assert_eq(&res);
+    for made_up in [
+        "load", "store", "jmp", "li", "div", "addeq", "b.xx", "cbz.eq",
+    ] {
+        assert_eq!(Mnemonic::lookup(made_up), None, "{made_up}");
+    }
+}
+#[test]
 fn instruction() {
     parse_format::<Instruction>(
         &[
             "add x0, x1, x2",
+            "add W0, W1, #1",
+            "mov x0, SP",
+            "str x0, [SP, #8]",
             "stp x29, x30, [sp, -48]!",
             "add x0, x0, :lo12:.LC2",
             "bl	puts",
-            "li	x10, 0",
+            "mov	x10, 0",
             "ldp x29, x30, [sp], 16",
+            "b.le .L3",
+            "csel x0, x10, x11, LE",
+            "cset w0, ne",
+            "ccmp x0, 3, 4, ge",
+            "add x0, x1, w2, sxtw #2",
+            "ldr x0, [x1, w2, sxtw 3]",
+            "add v0.4s, v1.4s, v2.4s",
+            "ins v0.s[1], w0",
+            "ld1 {v0.16b-v3.16b}, [x1], 64",
+            "str q0, [x0, 16]",
+            "b lo",
+            "adr x0, hi",
+            "bl cc",
+            "csinc x0, x1, x2, cc",
         ],
         Rule::operation,
         expect![[r#"
-            Instruction { opcode: Opcode("add"), operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 1 }), Register(Register { reg_type: Full, reg_num: 2 })] }
-            Instruction { opcode: Opcode("stp"), operands: [Register(Register { reg_type: Full, reg_num: 29 }), Register(Register { reg_type: Full, reg_num: 30 }), Indirect(Indirect { base: Register { reg_type: Full, reg_num: 31 }, offset: Some(Immediate(Immediate(-48))), writeback: true })] }
-            "#]],
+            Instruction { opcode: Opcode("add"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 1 }), Register(Register { reg_type: Full, reg_num: 2 })] }
+            Instruction { opcode: Opcode("add"), condition: None, operands: [Register(Register { reg_type: Half, reg_num: 0 }), Register(Register { reg_type: Half, reg_num: 1 }), Immediate(Int(1))] }
+            Instruction { opcode: Opcode("mov"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: StackPointer, reg_num: 31 })] }
+            Instruction { opcode: Opcode("str"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Indirect(Indirect { base: Register { reg_type: StackPointer, reg_num: 31 }, mode: Offset(Immediate(Int(8))) })] }
+            Instruction { opcode: Opcode("stp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 29 }), Register(Register { reg_type: Full, reg_num: 30 }), Indirect(Indirect { base: Register { reg_type: StackPointer, reg_num: 31 }, mode: PreIndex(Int(-48)) })] }
+            Instruction { opcode: Opcode("add"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 0 }), ProcLoad(ProcLoad { modifier: Lo12, expr: Symbol(".LC2") })] }
+            Instruction { opcode: Opcode("bl"), condition: None, operands: [LabelTarget("puts")] }
+            Instruction { opcode: Opcode("mov"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 10 }), Immediate(Int(0))] }
+            Instruction { opcode: Opcode("ldp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 29 }), Register(Register { reg_type: Full, reg_num: 30 }), Indirect(Indirect { base: Register { reg_type: StackPointer, reg_num: 31 }, mode: PostIndex(Int(16)) })] }
+            Instruction { opcode: Opcode("b"), condition: Some(LE), operands: [LabelTarget(".L3")] }
+            Instruction { opcode: Opcode("csel"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 10 }), Register(Register { reg_type: Full, reg_num: 11 }), Condition(LE)] }
+            Instruction { opcode: Opcode("cset"), condition: None, operands: [Register(Register { reg_type: Half, reg_num: 0 }), Condition(NE)] }
+            Instruction { opcode: Opcode("ccmp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Immediate(Int(3)), Immediate(Int(4)), Condition(GE)] }
+            Instruction { opcode: Opcode("add"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 1 }), ExtendedRegister(ExtendedRegister { reg: Register { reg_type: Half, reg_num: 2 }, extend_type: SXTW, shift_amount: Some(Int(2)) })] }
+            Instruction { opcode: Opcode("ldr"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Indirect(Indirect { base: Register { reg_type: Full, reg_num: 1 }, mode: Offset(ExtendedRegister(ExtendedRegister { reg: Register { reg_type: Half, reg_num: 2 }, extend_type: SXTW, shift_amount: Some(Int(3)) })) })] }
+            Instruction { opcode: Opcode("add"), condition: None, operands: [Register(Register { reg_type: Vector(Arrangement { lanes: 4, size: S }), reg_num: 0 }), Register(Register { reg_type: Vector(Arrangement { lanes: 4, size: S }), reg_num: 1 }), Register(Register { reg_type: Vector(Arrangement { lanes: 4, size: S }), reg_num: 2 })] }
+            Instruction { opcode: Opcode("ins"), condition: None, operands: [Register(Register { reg_type: Lane(S, 1), reg_num: 0 }), Register(Register { reg_type: Half, reg_num: 0 })] }
+            Instruction { opcode: Opcode("ld1"), condition: None, operands: [RegisterList(RegisterList { regs: [Register { reg_type: Vector(Arrangement { lanes: 16, size: B }), reg_num: 0 }, Register { reg_type: Vector(Arrangement { lanes: 16, size: B }), reg_num: 1 }, Register { reg_type: Vector(Arrangement { lanes: 16, size: B }), reg_num: 2 }, Register { reg_type: Vector(Arrangement { lanes: 16, size: B }), reg_num: 3 }], lane: None }), Indirect(Indirect { base: Register { reg_type: Full, reg_num: 1 }, mode: PostIndex(Int(64)) })] }
+            Instruction { opcode: Opcode("str"), condition: None, operands: [Register(Register { reg_type: Quad, reg_num: 0 }), Indirect(Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: Offset(Immediate(Int(16))) })] }
+            Instruction { opcode: Opcode("b"), condition: None, operands: [LabelTarget("lo")] }
+            Instruction { opcode: Opcode("adr"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), LabelTarget("hi")] }
+            Instruction { opcode: Opcode("bl"), condition: None, operands: [LabelTarget("cc")] }
+            Instruction { opcode: Opcode("csinc"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 1 }), Register(Register { reg_type: Full, reg_num: 2 }), Condition(LO)] }
+        "#]],
     );
 }
 #[test]
 fn parse_asms() {
     let input = r#"add x0,x1,x3
-        std x29,x30, [sp,-48]!
+        stp x29,x30, [sp,-48]!
         add x0,x0,:lo12:.LC2"#;
-    parse_src(&input);
+    parse_src(input);
+}
+
+#[test]
+fn parse_file() {
+    check_src(
+        "\t.text\n\t.align\t2\n\n\t.global\tmain\n\t.type\tmain, %function\nmain:\n\t# stp\tx29, x30, [sp, -16]!\n\tstp\tx29, x30, [sp, -16]! // save frame\n\tmov\tw0, 0 ; ret\n\tbl\tsprintf\n.LC0:\n\t.string\t\"%lu # //\\t\"\n\tldp\tx29, x30, [sp], 16\n",
+        expect![[r#"
+            1:2 1..6 Directive(Text)
+            2:2 8..16 Directive(Align { pow2: 2, fill: None, max: None })
+            4:2 19..31 Directive(Global("main"))
+            5:2 33..54 Directive(Type { symbol: "main", kind: Function })
+            6:1 55..60 Label("main")
+            8:2 90..114 Instruction(Instruction { opcode: Opcode("stp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 29 }), Register(Register { reg_type: Full, reg_num: 30 }), Indirect(Indirect { base: Register { reg_type: StackPointer, reg_num: 31 }, mode: PreIndex(Int(-16)) })] })
+            9:2 130..139 Instruction(Instruction { opcode: Opcode("mov"), condition: None, operands: [Register(Register { reg_type: Half, reg_num: 0 }), Immediate(Int(0))] })
+            9:14 142..145 Instruction(Instruction { opcode: Opcode("ret"), condition: None, operands: [] })
+            10:2 147..157 Instruction(Instruction { opcode: Opcode("bl"), condition: None, operands: [LabelTarget("sprintf")] })
+            11:1 158..163 Label(".LC0")
+            12:2 165..185 Directive(String { zero_terminated: true, values: ["%lu # //\t"] })
+            13:2 187..209 Instruction(Instruction { opcode: Opcode("ldp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 29 }), Register(Register { reg_type: Full, reg_num: 30 }), Indirect(Indirect { base: Register { reg_type: StackPointer, reg_num: 31 }, mode: PostIndex(Int(16)) })] })
+        "#]],
+    );
+}
+
+#[test]
+fn statements() {
+    check_src(
+        "\tnop; add\tx0, x0, #1\n\t.ascii\t\"a;b\" ; ret // c; d\nl: b l;;mov\tx1, #2\n",
+        expect![[r#"
+            1:2 1..4 Instruction(Instruction { opcode: Opcode("nop"), condition: None, operands: [] })
+            1:7 6..20 Instruction(Instruction { opcode: Opcode("add"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 0 }), Immediate(Int(1))] })
+            2:2 22..35 Directive(String { zero_terminated: false, values: ["a;b"] })
+            2:17 37..48 Instruction(Instruction { opcode: Opcode("ret"), condition: None, operands: [] })
+            3:1 49..51 Label("l")
+            3:4 52..55 Instruction(Instruction { opcode: Opcode("b"), condition: None, operands: [LabelTarget("l")] })
+            3:9 57..67 Instruction(Instruction { opcode: Opcode("mov"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 1 }), Immediate(Int(2))] })
+        "#]],
+    );
+}
+
+#[test]
+fn expression() {
+    parse_format::<Expr>(
+        &[
+            "1+2*3",
+            "(1 + 2) * 3",
+            "-(4*8)",
+            ".-.L3",
+            "sym/4-1",
+            "0x10*-2",
+        ],
+        Rule::expr,
+        expect![[r#"
+            Binary(Int(1), Add, Binary(Int(2), Mul, Int(3)))
+            Binary(Binary(Int(1), Add, Int(2)), Mul, Int(3))
+            Neg(Binary(Int(4), Mul, Int(8)))
+            Binary(Here, Sub, Symbol(".L3"))
+            Binary(Binary(Symbol("sym"), Div, Int(4)), Sub, Int(1))
+            Binary(Int(16), Mul, Int(-2))
+        "#]],
+    );
+}
+
+#[test]
+fn reloc_operand() {
+    parse_format::<Instruction>(
+        &[
+            "add x0, x0, :lo12:.LC2+8",
+            "adrp x0, :got:sym",
+            "ldr x0, [x0, #:got_lo12:sym]",
+            "add x0, x0, :tprel_hi12:var, lsl #12",
+            "add x0, x0, #:tprel_lo12_nc:var",
+            "adrp x0, :tlsdesc:var",
+            "movk x0, #:abs_g1_nc:sym, lsl #16",
+            "mov x0, #(4*8)",
+            "adr x0, sym+16",
+            "ldr x0, [x1, #(2*8)+8]",
+            "movk x0, 0x1234, lsl 16",
+        ],
+        Rule::operation,
+        expect![[r#"
+            Instruction { opcode: Opcode("add"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 0 }), ProcLoad(ProcLoad { modifier: Lo12, expr: Binary(Symbol(".LC2"), Add, Int(8)) })] }
+            Instruction { opcode: Opcode("adrp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), ProcLoad(ProcLoad { modifier: Got, expr: Symbol("sym") })] }
+            Instruction { opcode: Opcode("ldr"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Indirect(Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: Offset(ProcLoad(ProcLoad { modifier: GotLo12, expr: Symbol("sym") })) })] }
+            Instruction { opcode: Opcode("add"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 0 }), ShiftedImmediate(ShiftedImmediate { value: ProcLoad(ProcLoad { modifier: TprelHi12, expr: Symbol("var") }), lsl: Int(12) })] }
+            Instruction { opcode: Opcode("add"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 0 }), ProcLoad(ProcLoad { modifier: TprelLo12Nc, expr: Symbol("var") })] }
+            Instruction { opcode: Opcode("adrp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), ProcLoad(ProcLoad { modifier: TlsDesc, expr: Symbol("var") })] }
+            Instruction { opcode: Opcode("movk"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), ShiftedImmediate(ShiftedImmediate { value: ProcLoad(ProcLoad { modifier: AbsG1Nc, expr: Symbol("sym") }), lsl: Int(16) })] }
+            Instruction { opcode: Opcode("mov"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Immediate(Int(32))] }
+            Instruction { opcode: Opcode("adr"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Expr(Binary(Symbol("sym"), Add, Int(16)))] }
+            Instruction { opcode: Opcode("ldr"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Indirect(Indirect { base: Register { reg_type: Full, reg_num: 1 }, mode: Offset(Immediate(Int(24))) })] }
+            Instruction { opcode: Opcode("movk"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), ShiftedImmediate(ShiftedImmediate { value: Immediate(Int(4660)), lsl: Int(16) })] }
+        "#]],
+    );
+}
+
+fn check_errors(input: &[&str], expect: Expect) {
+    let res = input
+        .iter()
+        .map(|src| parse_asm_file("test.s", src).unwrap_err().render())
+        .collect::<String>();
+    expect.assert_eq(&res);
+}
+#[test]
+fn diagnostics() {
+    check_errors(
+        &[
+            "main:\n\taddd\tx0, x1, x2\n",
+            "\tnop; ldr\tx0, [x1",
+            "\tldr\tx0, [x1, wrz]",
+            "\tmov\tx45, 1",
+            "\tmov\tX31, 1",
+            "\tadd\tx31, x31, 1",
+            "\tstp\tx0, x1, [sp, #99999999999999999999]",
+            "\tldr\tx0, [x1",
+            "\tstr\tx0, [x1, x2]!",
+            "\tldp\tx0, x1, [sp, 16], 16",
+            "\tadd\tv0.3s, v1.4s, v2.4s",
+            "\tmov\tw0, v1.s[4]",
+            "\tsdot\tv0.4s, v1.16b, v2.8b[0]",
+            "\tld1\t{v0.4s, v1.4s}[1], [x0]",
+            "\tadd\tx0, x0, :lo13:sym",
+            "\tldr\tx0, [x1, sym-8]",
+        ],
+        expect![[r#"
+            error: unknown mnemonic `addd`
+             --> test.s:2:2
+              |
+            2 | 	addd	x0, x1, x2
+              | 	^^^^ help: did you mean `add`?
+            error: syntax error: expected register
+             --> test.s:1:16
+              |
+            1 | 	nop; ldr	x0, [x1
+              | 	        	     ^
+            error: invalid register `wrz`
+             --> test.s:1:15
+              |
+            1 | 	ldr	x0, [x1, wrz]
+              | 	   	         ^^^ help: did you mean `wzr`?
+            error: invalid register `x45`
+             --> test.s:1:6
+              |
+            1 | 	mov	x45, 1
+              | 	   	^^^
+            error: invalid operand `X31`: register 31 is written `sp` or `xzr`
+             --> test.s:1:6
+              |
+            1 | 	mov	X31, 1
+              | 	   	^^^
+            error: invalid operand `x31`: register 31 is written `sp` or `xzr`
+             --> test.s:1:6
+              |
+            1 | 	add	x31, x31, 1
+              | 	   	^^^
+            error: invalid integer `99999999999999999999`: number too large to fit in target type
+             --> test.s:1:20
+              |
+            1 | 	stp	x0, x1, [sp, #99999999999999999999]
+              | 	   	              ^^^^^^^^^^^^^^^^^^^^
+            error: syntax error: expected register
+             --> test.s:1:11
+              |
+            1 | 	ldr	x0, [x1
+              | 	   	     ^
+            error: invalid operand `[x1, x2]!`: writeback needs an immediate offset
+             --> test.s:1:10
+              |
+            1 | 	str	x0, [x1, x2]!
+              | 	   	    ^^^^^^^^^
+            error: invalid operand `[sp, 16], 16`: post-index can't be combined with an offset
+             --> test.s:1:14
+              |
+            1 | 	ldp	x0, x1, [sp, 16], 16
+              | 	   	        ^^^^^^^^^^^^
+            error: invalid register `v0.3s`
+             --> test.s:1:6
+              |
+            1 | 	add	v0.3s, v1.4s, v2.4s
+              | 	   	^^^^^
+            error: invalid register `v1.s[4]`
+             --> test.s:1:10
+              |
+            1 | 	mov	w0, v1.s[4]
+              | 	   	    ^^^^^^^
+            error: invalid register `v2.8b[0]`
+             --> test.s:1:22
+              |
+            1 | 	sdot	v0.4s, v1.16b, v2.8b[0]
+              | 	    	               ^^^^^^^^
+            error: invalid operand `{v0.4s, v1.4s}[1]`: a lane needs registers like `v0.s`
+             --> test.s:1:6
+              |
+            1 | 	ld1	{v0.4s, v1.4s}[1], [x0]
+              | 	   	^^^^^^^^^^^^^^^^^
+            error: invalid operand `lo13`: unknown relocation operator
+             --> test.s:1:15
+              |
+            1 | 	add	x0, x0, :lo13:sym
+              | 	   	         ^^^^
+            error: invalid operand `sym-8`: offset must be a constant or use a relocation operator
+             --> test.s:1:15
+              |
+            1 | 	ldr	x0, [x1, sym-8]
+              | 	   	         ^^^^^
+        "#]],
+    );
+}
+#[test]
+fn recovering() {
+    let src = "main:\n\taddd\tx0, x1, x2\n\tadd\tx0, x1, x2\n\tstp\tx29 x30, [sp]\n.L2:\tmov\tx0, x45\n\tldp\tx0, x1, {x0-w3}\n\tb\t.L2\n";
+    let (lines, errors) = parse_asm_recovering(src);
+    let res = lines
+        .iter()
+        .map(|l| format!("{}: {:?}\n", l.loc.line, l.line))
+        .chain(errors.iter().map(|e| e.render()))
+        .collect::<String>();
+    expect![[r#"
+        1: Label("main")
+        2: Error("addd\tx0, x1, x2")
+        3: Instruction(Instruction { opcode: Opcode("add"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 1 }), Register(Register { reg_type: Full, reg_num: 2 })] })
+        4: Error("stp\tx29 x30, [sp]")
+        5: Label(".L2")
+        5: Error("mov\tx0, x45")
+        6: Error("ldp\tx0, x1, {x0-w3}")
+        7: Instruction(Instruction { opcode: Opcode("b"), condition: None, operands: [LabelTarget(".L2")] })
+        error: unknown mnemonic `addd`
+         --> <input>:2:2
+          |
+        2 | 	addd	x0, x1, x2
+          | 	^^^^ help: did you mean `add`?
+        error: syntax error: expected end of input or shift type
+         --> <input>:4:10
+          |
+        4 | 	stp	x29 x30, [sp]
+          | 	   	    ^
+        error: invalid register `x45`
+         --> <input>:5:14
+          |
+        5 | .L2:	mov	x0, x45
+          |     	   	    ^^^
+        error: invalid register range `x0-w3`
+         --> <input>:6:15
+          |
+        6 | 	ldp	x0, x1, {x0-w3}
+          | 	   	         ^^^^^
+    "#]]
+    .assert_eq(&res);
+}
+#[test]
+fn directive() {
+    parse_format::<Directive>(
+        &[
+            ".text",
+            ".section .rodata",
+            ".section .rodata.str1.8,\"aMS\",@progbits,1",
+            ".section .note.GNU-stack,\"\",@progbits",
+            ".align 3",
+            ".p2align 4,,15",
+            ".string \"%lu, %lu\\t\\\"\\101\\x42\"",
+            ".dword 0",
+            ".dword 0xffffffffffffffff",
+            ".word .L3-.L2, -8",
+            ".zero 4",
+            ".type main, %function",
+            ".size main, .-main",
+            ".global main",
+            ".file \"float_cmp.c\"",
+            ".file 1 \"float_cmp.c\"",
+            ".loc 1 5 7 prologue_end",
+            ".cfi_def_cfa_offset 16",
+            ".cfi_offset 29, -16",
+            "#APP",
+        ],
+        Rule::directive,
+        expect![[r##"
+            Text
+            Section { name: ".rodata", flags: None, kind: None, args: [] }
+            Section { name: ".rodata.str1.8", flags: Some(SectionFlags { alloc: true, write: false, exec: false, merge: true, strings: true, group: false, tls: false }), kind: Some("progbits"), args: [Int(1)] }
+            Section { name: ".note.GNU-stack", flags: Some(SectionFlags { alloc: false, write: false, exec: false, merge: false, strings: false, group: false, tls: false }), kind: Some("progbits"), args: [] }
+            Align { pow2: 3, fill: None, max: None }
+            Align { pow2: 4, fill: None, max: Some(Int(15)) }
+            String { zero_terminated: true, values: ["%lu, %lu\t\"AB"] }
+            Values { size: 8, values: [Int(0)] }
+            Values { size: 8, values: [Int(-1)] }
+            Values { size: 4, values: [Binary(Symbol(".L3"), Sub, Symbol(".L2")), Int(-8)] }
+            Space { size: Int(4), fill: None }
+            Type { symbol: "main", kind: Function }
+            Size { symbol: "main", size: Binary(Here, Sub, Symbol("main")) }
+            Global("main")
+            File { number: None, name: "float_cmp.c" }
+            File { number: Some(1), name: "float_cmp.c" }
+            Raw { name: ".loc", args: "1 5 7 prologue_end" }
+            Raw { name: ".cfi_def_cfa_offset", args: "16" }
+            Raw { name: ".cfi_offset", args: "29, -16" }
+            Raw { name: "#APP", args: "" }
+        "##]],
+    );
+}
+#[test]
+fn directive_errors() {
+    check_errors(
+        &[
+            ".align x0",
+            ".text .data",
+            ".type main, %func",
+            ".section .rodata,\"aq\"",
+            ".global 1 2 3 4",
+            ".global a, b",
+            ".word 1 2",
+            ".byte 1,2 3",
+            ".cfi_register 29 30",
+        ],
+        expect![[r#"
+            error: invalid `.align` directive: expected a non-negative integer
+             --> test.s:1:8
+              |
+            1 | .align x0
+              |        ^^
+            error: invalid `.text` directive: too many arguments
+             --> test.s:1:7
+              |
+            1 | .text .data
+              |       ^^^^^
+            error: invalid `.type` directive: unknown symbol type
+             --> test.s:1:13
+              |
+            1 | .type main, %func
+              |             ^^^^^
+            error: invalid `.section` directive: unknown section flag
+             --> test.s:1:18
+              |
+            1 | .section .rodata,"aq"
+              |                  ^^^^
+            error: invalid `.global` directive: expected `,` between arguments
+             --> test.s:1:11
+              |
+            1 | .global 1 2 3 4
+              |           ^
+            error: invalid `.global` directive: too many arguments
+             --> test.s:1:12
+              |
+            1 | .global a, b
+              |            ^
+            error: invalid `.word` directive: expected `,` between arguments
+             --> test.s:1:9
+              |
+            1 | .word 1 2
+              |         ^
+            error: invalid `.byte` directive: expected `,` between arguments
+             --> test.s:1:11
+              |
+            1 | .byte 1,2 3
+              |           ^
+            error: invalid `.cfi_register` directive: expected `,` between arguments
+             --> test.s:1:18
+              |
+            1 | .cfi_register 29 30
+              |                  ^^
+        "#]],
+    );
+}
+
+/// Every directive in the examples must be understood, not kept raw.
+#[test]
+fn example_directives() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../asm_examples");
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "asm") {
+            continue;
+        }
+        let src = std::fs::read_to_string(&path).unwrap();
+        for line in src.lines().map(str::trim) {
+            if !line.starts_with('.') || line.ends_with(':') {
+                continue;
+            }
+            let parsed = parse_asm_file(path.to_str().unwrap(), line)
+                .unwrap_or_else(|e| panic!("{}", e.render()));
+            assert!(
+                !matches!(parsed[0].line, Line::Directive(Directive::Raw { .. })),
+                "{line}"
+            );
+        }
+    }
+}
+
+/// The mnemonics of the ARM code the examples were translated from, which is
+/// kept next to each RISC-V instruction in a `# ` comment.
+#[test]
+fn example_opcodes() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../asm_examples");
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "asm") {
+            continue;
+        }
+        let src = std::fs::read_to_string(&path).unwrap();
+        for line in src.lines().filter_map(|l| l.trim().strip_prefix("# ")) {
+            let Some(mnemonic) = line.split_whitespace().next() else {
+                continue;
+            };
+            if mnemonic.starts_with('.') || mnemonic.ends_with(':') {
+                continue;
+            }
+            assert!(Mnemonic::lookup(mnemonic).is_some(), "{line}");
+        }
+    }
+}
+
+fn check_print(input: &[&str], expect: Expect) {
+    let res = input
+        .iter()
+        .flat_map(|src| parse_asm(src).unwrap())
+        .map(|l| {
+            let printed = l.to_string();
+            assert_eq!(parse_asm(&printed).unwrap()[0].line, l.line, "{printed}");
+            format!("{printed}\n")
+        })
+        .collect::<String>();
+    expect.assert_eq(&res);
+}
+#[test]
+fn print_canonical() {
+    check_print(
+        &[
+            "main:\tstp\tfp, lr, [sp, -32]!",
+            "\tldr\tx0, [x1, #:lo12:.LC0+8]",
+            "\tldr\tx0, [sp], 16",
+            "\tldr\tx0, [x1, w2, sxtw 3]",
+            "\tadd\tx0, x1, x2, lsl #3",
+            "\tmovk\tx0, 0x1234, lsl 16",
+            "\tb.ne\t1f",
+            "\tcsel\tw0, w1, wzr, cs",
+            "\tld1\t{v0.s-v1.s}[1], [x0]",
+            "\tfmov\td0, #1.5e-1",
+            "\tmov\tw0, #'\\''",
+            "\tldr\tx0, =0x10",
+            "\tadr\tx0, (a - (b - c)) * -d + -(e+1)",
+            "\tsdot\tv0.4s, v1.16b, v2.4b[1]",
+            "\t.section\t.rodata.str1.8,\"aMS\",@progbits,1",
+            "\t.p2align 4,,15",
+            "\t.string\t\"a\\\"\\t\\0017\\x80\"",
+            "\t.word\t.L3-.L4, 1b",
+            "\t.skip\t8, 0xff",
+            "\t.type\tmain, @function",
+            "\t.file\t1 \"a.c\"",
+            "\t.cfi_def_cfa_offset 16",
+        ],
+        expect![[r#"
+            main:
+            	stp	x29, x30, [sp, #-32]!
+            	ldr	x0, [x1, :lo12:.LC0+8]
+            	ldr	x0, [sp], #16
+            	ldr	x0, [x1, w2, sxtw #3]
+            	add	x0, x1, x2, lsl #3
+            	movk	x0, #4660, lsl #16
+            	b.ne	1f
+            	csel	w0, w1, wzr, hs
+            	ld1	{v0.s, v1.s}[1], [x0]
+            	fmov	d0, #0.15
+            	mov	w0, #'\''
+            	ldr	x0, =16
+            	adr	x0, (a-(b-c))*-d+-(e+1)
+            	sdot	v0.4s, v1.16b, v2.4b[1]
+            	.section	.rodata.str1.8,"aMS",@progbits,1
+            	.align	4,,15
+            	.string	"a\"\t\0017\200"
+            	.word	.L3-.L4, 1b
+            	.space	8, 255
+            	.type	main, %function
+            	.file	1 "a.c"
+            	.cfi_def_cfa_offset	16
+        "#]],
+    );
+}
+
+/// Printing every line the examples parse to reads back as the same line.
+#[test]
+fn print_round_trip() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../asm_examples");
+    let mut count = 0;
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "asm") {
+            continue;
+        }
+        let src = std::fs::read_to_string(&path).unwrap();
+        // the ARM code each RISC-V instruction was translated from is kept
+        // in a `# ` comment
+        let lines = src
+            .lines()
+            .flat_map(|l| [l, l.trim().strip_prefix("# ").unwrap_or("")]);
+        for line in lines {
+            let Ok(parsed) = parse_asm(line) else {
+                continue;
+            };
+            for parsed in parsed {
+                let printed = parsed.to_string();
+                let reparsed = parse_asm(&printed).unwrap_or_else(|e| panic!("{}", e.render()));
+                assert_eq!(reparsed.len(), 1, "{printed}");
+                assert_eq!(reparsed[0].line, parsed.line, "{line} => {printed}");
+                count += 1;
+            }
+        }
+    }
+    assert!(count > 1000, "{count}");
+}
+
+fn check_preprocess(src: &str, expect: Expect) {
+    let pre = preprocess::preprocess(src).unwrap();
+    let res = pre
+        .text
+        .lines()
+        .zip(&pre.origins)
+        .map(|(text, origin)| match origin.expanded_from {
+            Some(from) => format!("{:>2} <{:>2} | {text}\n", origin.loc.line, from.line),
+            None => format!("{:>2}     | {text}\n", origin.loc.line),
+        })
+        .collect::<String>();
+    expect.assert_eq(&res);
+}
+
+#[test]
+fn preprocess_macros() {
+    check_preprocess(
+        r#"	.equ	STACK, 16
+	.set	COUNT, 2
+	.macro	push a, b=x30
+	stp	\a, \b, [sp, -STACK]!
+	.endm
+	.macro	sum dst, srcs:vararg
+	.irp	r, \srcs
+	add	\dst, \dst, \r
+	.endr
+	.endm
+main:	push	x29
+	push	b=x1, a=x0
+	sum	x0, x1, x2
+	.rept	COUNT
+	nop
+	.endr
+	.irpc	n, 01
+	mov	x\n, #'\n'
+	.endr
+	.set	COUNT, COUNT+1
+	mov	x0, COUNT	// COUNT
+	.macro	label name
+\name\()_\@:
+	.exitm
+	nop
+	.endm
+	label	loop
+	label	loop
+	.set	N, 4; mov	x0, N; nop
+"#,
+        expect![[r#"
+            11     | main:
+             4 <11 | 	stp	x29, x30, [sp, -16]!
+             4 <12 | 	stp	x0, x1, [sp, -16]!
+             8 <13 | 	add	x0, x0, x1
+             8 <13 | 	add	x0, x0, x2
+            15 <14 | 	nop
+            15 <14 | 	nop
+            18 <17 | 	mov	x0, #'0'
+            18 <17 | 	mov	x1, #'1'
+            21     | 	mov	x0, 3	// COUNT
+            23 <27 | loop_3:
+            23 <28 | loop_4:
+            29     | 	    	      mov	x0, 4
+            29     | 	    	         	       nop
+        "#]],
+    );
+}
+
+#[test]
+fn preprocess_conditionals() {
+    check_preprocess(
+        r#"	.equ	LEVEL, 2
+	.if	LEVEL > 1 && LEVEL != 3
+	mov	x0, 1
+	.elseif	LEVEL == 1
+	mov	x0, 2
+	.else
+	mov	x0, 3
+	.endif
+	.ifdef	UNDEFINED
+	.if	UNDEFINED
+	.endif
+	mov	x1, 1
+	.else
+	mov	x1, 2
+	.endif
+	.macro	opt reg, val
+	.ifb	\val
+	mov	\reg, xzr
+	.else
+	mov	\reg, \val
+	.endif
+	.ifc	\reg, x2
+	nop
+	.endif
+	.endm
+	opt	x2
+	opt	x3, 7
+"#,
+        expect![[r#"
+             3     | 	mov	x0, 1
+            14     | 	mov	x1, 2
+            18 <26 | 	mov	x2, xzr
+            23 <26 | 	nop
+            20 <27 | 	mov	x3, 7
+        "#]],
+    );
+}
+
+#[test]
+fn preprocess_errors() {
+    let res = [
+        "\t.rept\t2\n\tnop\n",
+        "\t.if\tUNDEFINED\n\t.endif\n",
+        "\t.macro\tm a:req\n\tmov\tx0, \\a\n\t.endm\n\tm\n",
+        "\t.macro\tm\n\taddd\tx0, x0, x1\n\t.endm\n\tnop\n\tm\n",
+        "\t.endif\n",
+        "\t.rept\t2\n\t.exitm\n\t.endr\n\tnop\n",
+        "\tnop; addd\tx0, x0, x1\n",
+    ]
+    .iter()
+    .map(|src| {
+        let e = match preprocess::preprocess(src) {
+            Ok(pre) => pre.parse().unwrap_err(),
+            Err(e) => e,
+        };
+        e.with_file("test.s").render()
+    })
+    .collect::<String>();
+    expect![[r#"
+        error: invalid `.rept` directive: no matching `.endr`
+         --> test.s:1:2
+          |
+        1 | 	.rept	2
+          | 	^^^^^^^
+        error: invalid `.if` directive: `UNDEFINED` is not a constant expression
+         --> test.s:1:2
+          |
+        1 | 	.if	UNDEFINED
+          | 	^^^^^^^^^^^^^
+        error: invalid `m` directive: missing value for `a`
+         --> test.s:4:2
+          |
+        4 | 	m
+          | 	^
+        error: unknown mnemonic `addd`
+         --> test.s:2:2
+          |
+        2 | 	addd	x0, x0, x1
+          | 	^^^^ help: did you mean `add`?
+          = note: in expansion at line 5
+        error: invalid `.endif` directive: no matching `.if`
+         --> test.s:1:2
+          |
+        1 | 	.endif
+          | 	^^^^^^
+        error: invalid `.exitm` directive: not inside a macro
+         --> test.s:2:2
+          |
+        2 | 	.exitm
+          | 	^^^^^^
+          = note: in expansion at line 1
+        error: unknown mnemonic `addd`
+         --> test.s:1:7
+          |
+        1 | 	     addd	x0, x0, x1
+          | 	     ^^^^ help: did you mean `add`?
+    "#]]
+    .assert_eq(&res);
+}
+
+#[test]
+fn local_labels() {
+    let src = "\
+1:\tldaxr\tw1, [x0]
+\tcbnz\tw1, 2f
+\tstlxr\tw2, w3, [x0]
+\tcbnz\tw2, 1b
+2:\tret
+1:\tb\t1b
+\t.word\t1b - .
+\tadrp\tx0, 1f
+1:\tnop
+";
+    let mut lines = parse_asm(src).unwrap();
+    local_label::resolve_local_labels(src, &mut lines).unwrap();
+    let res = lines
+        .iter()
+        .map(|l| format!("{:?}\n", l.line))
+        .collect::<String>();
+    expect![[r#"
+        Label(".L1.0")
+        Instruction(Instruction { opcode: Opcode("ldaxr"), condition: None, operands: [Register(Register { reg_type: Half, reg_num: 1 }), Indirect(Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: Base })] })
+        Instruction(Instruction { opcode: Opcode("cbnz"), condition: None, operands: [Register(Register { reg_type: Half, reg_num: 1 }), LabelTarget(".L2.0")] })
+        Instruction(Instruction { opcode: Opcode("stlxr"), condition: None, operands: [Register(Register { reg_type: Half, reg_num: 2 }), Register(Register { reg_type: Half, reg_num: 3 }), Indirect(Indirect { base: Register { reg_type: Full, reg_num: 0 }, mode: Base })] })
+        Instruction(Instruction { opcode: Opcode("cbnz"), condition: None, operands: [Register(Register { reg_type: Half, reg_num: 2 }), LabelTarget(".L1.0")] })
+        Label(".L2.0")
+        Instruction(Instruction { opcode: Opcode("ret"), condition: None, operands: [] })
+        Label(".L1.1")
+        Instruction(Instruction { opcode: Opcode("b"), condition: None, operands: [LabelTarget(".L1.1")] })
+        Directive(Values { size: 4, values: [Binary(Symbol(".L1.1"), Sub, Here)] })
+        Instruction(Instruction { opcode: Opcode("adrp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), LabelTarget(".L1.2")] })
+        Label(".L1.2")
+        Instruction(Instruction { opcode: Opcode("nop"), condition: None, operands: [] })
+    "#]].assert_eq(&res);
+
+    let res = ["\tb\t3f\n", "\tnop\n3:\tb\t3b\n\tcbz\tx0, 3f\n"]
+        .iter()
+        .map(|src| {
+            let mut lines = parse_asm(src).unwrap();
+            local_label::resolve_local_labels(src, &mut lines)
+                .unwrap_err()
+                .with_file("test.s")
+                .render()
+        })
+        .collect::<String>();
+    expect![[r#"
+        error: invalid operand `3f`: no `3:` label after it
+         --> test.s:1:4
+          |
+        1 | 	b	3f
+          | 	 	^^
+        error: invalid operand `3f`: no `3:` label after it
+         --> test.s:3:10
+          |
+        3 | 	cbz	x0, 3f
+          | 	   	    ^^
+    "#]]
+    .assert_eq(&res);
+}
+
+#[test]
+fn objdump_listing() {
+    let src = "
+hello:     file format elf64-littleaarch64
+
+
+Disassembly of section .text:
+
+0000000000400580 <main>:
+  400580:\ta9bf7bfd \tstp\tx29, x30, [sp, #-16]!
+  400584:\t910003fd \tmov\tx29, sp
+  400588:\t90000000 \tadrp\tx0, 400000 <_init-0x3e8>
+  40058c:\t91194000 \tadd\tx0, x0, #0x650
+  400590:\t97ffffa8 \tbl\t400430 <puts@plt>
+  400594:\t34000040 \tcbz\tw0, 40059c <main+0x1c>
+  400598:\td10083ff \tsub\tsp, sp, #0x20
+  40059c:\ta8c17bfd \tldp\tx29, x30, [sp], #16
+  4005a0:\td65f03c0 \tret
+\t...
+
+00000000004005b0 <helper>:
+  4005b0:\tb.ne\t4005b0 <helper>  // b.any
+       4:\t90000000     \tadrp\tx0, 0x0 <helper+0x4>
+\t\t0000000000000004:  R_AARCH64_ADR_PREL_PG_HI21\t.rodata.str1.1
+\t\t\t4: R_AARCH64_ADR_PREL_PG_HI21\t.rodata.str1.1
+";
+    let listing = objdump::objdump(src);
+    let res = listing
+        .parse()
+        .unwrap()
+        .iter()
+        .map(|l| {
+            let address = l.address.map_or(String::new(), |a| format!("{a:x}"));
+            let encoding = l.encoding.map_or(String::new(), |e| format!("{e:08x}"));
+            format!("{address:>6} {encoding:>8} {:?}\n", l.line.line)
+        })
+        .collect::<String>();
+    expect![[r#"
+                        Directive(Section { name: ".text", flags: None, kind: None, args: [] })
+        400580          Label("main")
+        400580 a9bf7bfd Instruction(Instruction { opcode: Opcode("stp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 29 }), Register(Register { reg_type: Full, reg_num: 30 }), Indirect(Indirect { base: Register { reg_type: StackPointer, reg_num: 31 }, mode: PreIndex(Int(-16)) })] })
+        400584 910003fd Instruction(Instruction { opcode: Opcode("mov"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 29 }), Register(Register { reg_type: StackPointer, reg_num: 31 })] })
+        400588 90000000 Instruction(Instruction { opcode: Opcode("adrp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Expr(Binary(Symbol("_init"), Sub, Int(1000)))] })
+        40058c 91194000 Instruction(Instruction { opcode: Opcode("add"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Register(Register { reg_type: Full, reg_num: 0 }), Immediate(Int(1616))] })
+        400590 97ffffa8 Instruction(Instruction { opcode: Opcode("bl"), condition: None, operands: [LabelTarget("puts_plt")] })
+        400594 34000040 Instruction(Instruction { opcode: Opcode("cbz"), condition: None, operands: [Register(Register { reg_type: Half, reg_num: 0 }), Expr(Binary(Symbol("main"), Add, Int(28)))] })
+        400598 d10083ff Instruction(Instruction { opcode: Opcode("sub"), condition: None, operands: [Register(Register { reg_type: StackPointer, reg_num: 31 }), Register(Register { reg_type: StackPointer, reg_num: 31 }), Immediate(Int(32))] })
+        40059c a8c17bfd Instruction(Instruction { opcode: Opcode("ldp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 29 }), Register(Register { reg_type: Full, reg_num: 30 }), Indirect(Indirect { base: Register { reg_type: StackPointer, reg_num: 31 }, mode: PostIndex(Int(16)) })] })
+        4005a0 d65f03c0 Instruction(Instruction { opcode: Opcode("ret"), condition: None, operands: [] })
+        4005b0          Label("helper")
+        4005b0          Instruction(Instruction { opcode: Opcode("b"), condition: Some(NE), operands: [LabelTarget("helper")] })
+             4 90000000 Instruction(Instruction { opcode: Opcode("adrp"), condition: None, operands: [Register(Register { reg_type: Full, reg_num: 0 }), Expr(Binary(Symbol("helper"), Add, Int(4)))] })
+    "#]].assert_eq(&res);
+
+    let src = "0000000000400580 <main>:\n  400580:\td10083ff \tsubb\tsp, sp, #0x20\n";
+    let res = objdump::objdump(src)
+        .parse()
+        .unwrap_err()
+        .with_file("test.dis")
+        .render();
+    expect![[r#"
+        error: unknown mnemonic `subb`
+         --> test.dis:2:2
+          |
+        2 | 	subb	sp, sp, #0x20
+          | 	^^^^ help: did you mean `ssbb`?
+    "#]]
+    .assert_eq(&res);
 }
@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Debug, Clone)]
 pub struct Instruction {
     pub opcode: Opcode,
@@ -9,7 +11,7 @@ pub struct Instruction {
 pub enum Opcode {
 
     /// Arithmetic
-    Add,Adds,Sub,Subs,Mul,Div,Umaddl,
+    Add,Adds,Sub,Subs,Mul,Div,Udiv,Umaddl,Madd,Msub,
 
     /// Bitwise
     And,Or,Xor,Not,
@@ -23,38 +25,103 @@ pub enum Opcode {
     Mov,Mvn,Mvk,
 
     /// Load/Store
-    Ldr,Str,
+    Ldr,Str,Ldrb,Ldrh,Ldrsb,Ldrsh,Ldrsw,Strb,Strh,Ldp,Stp,
+
+    /// Address
+    Adr,Adrp,
 
     /// Branch
-    B,Ble,Blt,Bge,Bgt,Beq,Bne,Bpl,Bhi,Cbnz,Csel,Cset,Bl,
+    B,Ble,Blt,Bge,Bgt,Beq,Bne,Bpl,Bhi,Bhs,Blo,Bmi,Bvs,Bvc,Bls,Bal,Bnv,
+    Cbz,Cbnz,Tbz,Tbnz,Bl,Br,Blr,Ret,
+
+    /// Conditional select
+    Csel,Cset,Csinc,Csinv,Csneg,
 
     /// Floating-point
     Fmov,
     Ucvtf,Scvtf,
     Fcmp,Fcmpe,
-    Fadd,Fsub,Fmul,Fdiv,Fneg,Fsqrt,
+    Fadd,Fsub,Fmul,Fdiv,Fneg,Fabs,Fsqrt,
     Fmsub,Fnmadd,Fnmul,Fmuladd,
 
     /// Atomic
     Ldaxr,Stlxr,Ldar,Stlr,
-    //TODO: Atomic Operations
+
+    /// LSE atomic read-modify-write
+    Ldadd(Order),Ldclr(Order),Ldeor(Order),Ldset(Order),
+    Ldsmax(Order),Ldsmin(Order),Ldumax(Order),Ldumin(Order),
+    Swp(Order),Cas(Order),
     Nop,
 }
 
+/// Memory ordering of an LSE atomic, from the `a`, `l` or `al` suffix of
+/// e.g. `ldaddal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Order {
+    Relaxed,
+    Acquire,
+    Release,
+    AcqRel,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Operand {
     /// Register
     Reg(Regoperand),
     ///Imm
     Imm {
-        imm: u16,
+        imm: i64,
         shift: Option<(u8, Shift)>,
     },
+    /// Immediate filled in from a symbol's address, as in `:lo12:buf`
+    Reloc(Reloc),
 
     /// Addressing
     Addressing(Addressing),
     /// Label
     Label(String),
+    /// Condition tested by `csel` and `cset`
+    Cond(Cond),
+}
+
+/// A condition on the NZCV flags, in the order of its 4-bit encoding.
+#[rustfmt::skip]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cond {
+    Eq,Ne,Hs,Lo,Mi,Pl,Vs,Vc,Hi,Ls,Ge,Lt,Gt,Le,Al,Nv,
+}
+
+/// A symbol's address, under the operator picking the part of it wanted.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Reloc {
+    pub operator: RelocOperator,
+    pub symbol: String,
+    pub addend: i64,
+}
+
+/// Relocation operator, as in `:lo12:`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RelocOperator {
+    /// Low 12 bits of the address, for `add` and load/store offsets
+    Lo12,
+    /// Page of the address, without the overflow check of a plain `adrp`
+    PgHi21Nc,
+    /// Page of the symbol's GOT entry
+    Got,
+    /// Low 12 bits of the GOT entry's address
+    GotLo12,
+    /// 16-bit chunks of the address for `movz`/`movk`, with `Nc` skipping
+    /// the overflow check and `S` for signed values
+    AbsG0,
+    AbsG0Nc,
+    AbsG0S,
+    AbsG1,
+    AbsG1Nc,
+    AbsG1S,
+    AbsG2,
+    AbsG2Nc,
+    AbsG2S,
+    AbsG3,
 }
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Regoperand {
@@ -88,10 +155,15 @@ pub enum Extend {
 pub enum Addressing {
     BaseRegister(Register),
     Offset {
-        offset: u64,
+        offset: i64,
         reg: Register,
         index: Option<Index>,
     },
+    /// `[reg, #:lo12:sym]`
+    Reloc {
+        reg: Register,
+        reloc: Reloc,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -122,27 +194,17 @@ pub enum Special {
     Xzr,
     Wzr,
     SP,
+    /// The 32-bit view of the stack pointer
+    Wsp,
     LR,
 }
 
 impl Register {
     pub fn is_word(&self) -> bool {
-        match self {
-            Register::General { ty, .. } => match ty {
-                General::W => true,
-                _ => false,
-            },
-            _ => false,
-        }
+        matches!(self, Register::General { ty: General::W, .. })
     }
     pub fn is_fword(&self) -> bool {
-        match self {
-            Register::General { ty, .. } => match ty {
-                General::S => true,
-                _ => false,
-            },
-            _ => false,
-        }
+        matches!(self, Register::General { ty: General::S, .. })
     }
 }
 impl Regoperand {
@@ -161,3 +223,330 @@ impl Regoperand {
         }
     }
 }
+
+impl Opcode {
+    /// The `b.cond` that branches on `cond`.
+    pub fn branch_on(cond: Cond) -> Opcode {
+        match cond {
+            Cond::Eq => Opcode::Beq,
+            Cond::Ne => Opcode::Bne,
+            Cond::Hs => Opcode::Bhs,
+            Cond::Lo => Opcode::Blo,
+            Cond::Mi => Opcode::Bmi,
+            Cond::Pl => Opcode::Bpl,
+            Cond::Vs => Opcode::Bvs,
+            Cond::Vc => Opcode::Bvc,
+            Cond::Hi => Opcode::Bhi,
+            Cond::Ls => Opcode::Bls,
+            Cond::Ge => Opcode::Bge,
+            Cond::Lt => Opcode::Blt,
+            Cond::Gt => Opcode::Bgt,
+            Cond::Le => Opcode::Ble,
+            Cond::Al => Opcode::Bal,
+            Cond::Nv => Opcode::Bnv,
+        }
+    }
+}
+
+impl Instruction {
+    /// The mnemonic GNU as knows the instruction by. Moves of a shifted or
+    /// relocated immediate can't use the `mov` alias and are written
+    /// `movz`/`movn`.
+    pub fn mnemonic(&self) -> String {
+        match (&self.opcode, self.operand.get(1)) {
+            (Opcode::Mov, Some(Operand::Imm { shift: Some(_), .. } | Operand::Reloc(_))) => {
+                "movz".to_string()
+            }
+            (Opcode::Mvn, Some(Operand::Imm { .. } | Operand::Reloc(_))) => "movn".to_string(),
+            (opcode, _) => opcode.to_string(),
+        }
+    }
+}
+
+/// Prints the instruction in GNU as syntax.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        for (i, operand) in self.operand.iter().enumerate() {
+            let sep = if i == 0 { "\t" } else { ", " };
+            match (&self.opcode, operand) {
+                // the zero `fcmp` compares with is a floating-point one
+                (
+                    Opcode::Fcmp | Opcode::Fcmpe,
+                    Operand::Imm {
+                        imm: 0,
+                        shift: None,
+                    },
+                ) => write!(f, "{sep}#0.0")?,
+                _ => write!(f, "{sep}{operand}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Opcode::*;
+        let (name, order) = match self {
+            Add => ("add", None),
+            Adds => ("adds", None),
+            Sub => ("sub", None),
+            Subs => ("subs", None),
+            Mul => ("mul", None),
+            Div => ("sdiv", None),
+            Udiv => ("udiv", None),
+            Umaddl => ("umaddl", None),
+            Madd => ("madd", None),
+            Msub => ("msub", None),
+            And => ("and", None),
+            Or => ("orr", None),
+            Xor => ("eor", None),
+            Not | Mvn => ("mvn", None),
+            Lsl => ("lsl", None),
+            Lsr => ("lsr", None),
+            Asr => ("asr", None),
+            Cmp => ("cmp", None),
+            Mov => ("mov", None),
+            Mvk => ("movk", None),
+            Ldr => ("ldr", None),
+            Str => ("str", None),
+            Ldrb => ("ldrb", None),
+            Ldrh => ("ldrh", None),
+            Ldrsb => ("ldrsb", None),
+            Ldrsh => ("ldrsh", None),
+            Ldrsw => ("ldrsw", None),
+            Strb => ("strb", None),
+            Strh => ("strh", None),
+            Ldp => ("ldp", None),
+            Stp => ("stp", None),
+            Adr => ("adr", None),
+            Adrp => ("adrp", None),
+            B => ("b", None),
+            Ble => ("b.le", None),
+            Blt => ("b.lt", None),
+            Bge => ("b.ge", None),
+            Bgt => ("b.gt", None),
+            Beq => ("b.eq", None),
+            Bne => ("b.ne", None),
+            Bpl => ("b.pl", None),
+            Bhi => ("b.hi", None),
+            Bhs => ("b.hs", None),
+            Blo => ("b.lo", None),
+            Bmi => ("b.mi", None),
+            Bvs => ("b.vs", None),
+            Bvc => ("b.vc", None),
+            Bls => ("b.ls", None),
+            Bal => ("b.al", None),
+            Bnv => ("b.nv", None),
+            Cbz => ("cbz", None),
+            Cbnz => ("cbnz", None),
+            Tbz => ("tbz", None),
+            Tbnz => ("tbnz", None),
+            Bl => ("bl", None),
+            Br => ("br", None),
+            Blr => ("blr", None),
+            Ret => ("ret", None),
+            Csel => ("csel", None),
+            Cset => ("cset", None),
+            Csinc => ("csinc", None),
+            Csinv => ("csinv", None),
+            Csneg => ("csneg", None),
+            Fmov => ("fmov", None),
+            Ucvtf => ("ucvtf", None),
+            Scvtf => ("scvtf", None),
+            Fcmp => ("fcmp", None),
+            Fcmpe => ("fcmpe", None),
+            Fadd => ("fadd", None),
+            Fsub => ("fsub", None),
+            Fmul => ("fmul", None),
+            Fdiv => ("fdiv", None),
+            Fneg => ("fneg", None),
+            Fabs => ("fabs", None),
+            Fsqrt => ("fsqrt", None),
+            Fmsub => ("fmsub", None),
+            Fnmadd => ("fnmadd", None),
+            Fnmul => ("fnmul", None),
+            Fmuladd => ("fmadd", None),
+            Ldaxr => ("ldaxr", None),
+            Stlxr => ("stlxr", None),
+            Ldar => ("ldar", None),
+            Stlr => ("stlr", None),
+            Ldadd(order) => ("ldadd", Some(order)),
+            Ldclr(order) => ("ldclr", Some(order)),
+            Ldeor(order) => ("ldeor", Some(order)),
+            Ldset(order) => ("ldset", Some(order)),
+            Ldsmax(order) => ("ldsmax", Some(order)),
+            Ldsmin(order) => ("ldsmin", Some(order)),
+            Ldumax(order) => ("ldumax", Some(order)),
+            Ldumin(order) => ("ldumin", Some(order)),
+            Swp(order) => ("swp", Some(order)),
+            Cas(order) => ("cas", Some(order)),
+            Nop => ("nop", None),
+        };
+        let suffix = match order {
+            None | Some(Order::Relaxed) => "",
+            Some(Order::Acquire) => "a",
+            Some(Order::Release) => "l",
+            Some(Order::AcqRel) => "al",
+        };
+        write!(f, "{name}{suffix}")
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Reg(reg) => write!(f, "{reg}"),
+            Operand::Imm { imm, shift } => {
+                write!(f, "#{imm}")?;
+                match shift {
+                    Some((amount, shift)) => write!(f, ", {shift} #{amount}"),
+                    None => Ok(()),
+                }
+            }
+            Operand::Reloc(reloc) => write!(f, "#{reloc}"),
+            Operand::Addressing(addressing) => write!(f, "{addressing}"),
+            Operand::Label(label) => write!(f, "{label}"),
+            Operand::Cond(cond) => write!(f, "{cond}"),
+        }
+    }
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Cond::Eq => "eq",
+            Cond::Ne => "ne",
+            Cond::Hs => "hs",
+            Cond::Lo => "lo",
+            Cond::Mi => "mi",
+            Cond::Pl => "pl",
+            Cond::Vs => "vs",
+            Cond::Vc => "vc",
+            Cond::Hi => "hi",
+            Cond::Ls => "ls",
+            Cond::Ge => "ge",
+            Cond::Lt => "lt",
+            Cond::Gt => "gt",
+            Cond::Le => "le",
+            Cond::Al => "al",
+            Cond::Nv => "nv",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl fmt::Display for Reloc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.operator, self.symbol)?;
+        match self.addend {
+            0 => Ok(()),
+            addend if addend < 0 => write!(f, "{addend}"),
+            addend => write!(f, "+{addend}"),
+        }
+    }
+}
+
+impl fmt::Display for RelocOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            RelocOperator::Lo12 => "lo12",
+            RelocOperator::PgHi21Nc => "pg_hi21_nc",
+            RelocOperator::Got => "got",
+            RelocOperator::GotLo12 => "got_lo12",
+            RelocOperator::AbsG0 => "abs_g0",
+            RelocOperator::AbsG0Nc => "abs_g0_nc",
+            RelocOperator::AbsG0S => "abs_g0_s",
+            RelocOperator::AbsG1 => "abs_g1",
+            RelocOperator::AbsG1Nc => "abs_g1_nc",
+            RelocOperator::AbsG1S => "abs_g1_s",
+            RelocOperator::AbsG2 => "abs_g2",
+            RelocOperator::AbsG2Nc => "abs_g2_nc",
+            RelocOperator::AbsG2S => "abs_g2_s",
+            RelocOperator::AbsG3 => "abs_g3",
+        };
+        write!(f, ":{name}:")
+    }
+}
+
+impl fmt::Display for Regoperand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Regoperand::Reg(reg) => write!(f, "{reg}"),
+            Regoperand::ShiftReg(reg, (amount, shift)) => write!(f, "{reg}, {shift} #{amount}"),
+            Regoperand::ExtendReg(reg, (0, extend)) => write!(f, "{reg}, {extend}"),
+            Regoperand::ExtendReg(reg, (amount, extend)) => {
+                write!(f, "{reg}, {extend} #{amount}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Shift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Shift::Lsl => "lsl",
+            Shift::Lsr => "lsr",
+            Shift::Asr => "asr",
+            Shift::Ror => "ror",
+            Shift::Uxtb => "uxtb",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Extend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Extend::Uxtb => "uxtb",
+            Extend::Uxth => "uxth",
+            Extend::Uxtw => "uxtw",
+            Extend::Lsl => "lsl",
+            Extend::Uxtx => "uxtx",
+            Extend::Sxtb => "sxtb",
+            Extend::Sxth => "sxth",
+            Extend::Sxtw => "sxtw",
+            Extend::Sxtx => "sxtx",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Addressing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Addressing::BaseRegister(reg) => write!(f, "[{reg}]"),
+            Addressing::Offset { offset, reg, index } => match index {
+                None => write!(f, "[{reg}, #{offset}]"),
+                Some(Index::Pre) => write!(f, "[{reg}, #{offset}]!"),
+                Some(Index::Post) => write!(f, "[{reg}], #{offset}"),
+            },
+            Addressing::Reloc { reg, reloc } => write!(f, "[{reg}, #{reloc}]"),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::General { ty, n } => {
+                let prefix = match ty {
+                    General::X => 'x',
+                    General::W => 'w',
+                    General::B => 'b',
+                    General::H => 'h',
+                    General::S => 's',
+                    General::D => 'd',
+                    General::Q => 'q',
+                };
+                write!(f, "{prefix}{n}")
+            }
+            Register::Special(Special::Xzr) => f.write_str("xzr"),
+            Register::Special(Special::Wzr) => f.write_str("wzr"),
+            Register::Special(Special::SP) => f.write_str("sp"),
+            Register::Special(Special::Wsp) => f.write_str("wsp"),
+            Register::Special(Special::LR) => f.write_str("x30"),
+        }
+    }
+}
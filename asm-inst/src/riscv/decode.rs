@@ -0,0 +1,1058 @@
+//! Decoding of RV64GC machine code, with Zba and Zbb, into [`Instruction`]s.
+//!
+//! Each 32-bit word is looked up in a table of masks and match values that
+//! gives its opcode and operand layout. Compressed instructions are first
+//! expanded to the word they stand for, so they come out in their base form.
+//! The aliases `objdump` prints are applied last where the model has an
+//! opcode for them: `addi a0, a1, 0` decodes as `mv a0, a1`, `addi a0,
+//! zero, 5` as `li a0, 5` and `jal zero, ...` as `j ...`. Other `jal` and
+//! `jalr` forms and the CSR instructions stay in their base form, as in
+//! `jalr ra, 0(a0)` for `objdump`'s `jalr a0`.
+
+use super::encode::{
+    b_type, i_type, is_exact, j_type, r_type, s_type, u_type, AMO, AUIPC, BRANCH, JAL, JALR, LOAD,
+    LOAD_FP, LUI, MADD, MISC_MEM, MSUB, NMADD, NMSUB, OP, OP_32, OP_FP, OP_IMM, OP_IMM_32, STORE,
+    STORE_FP, SYSTEM,
+};
+use super::{
+    AmoOrdering, AmoWidth, FpType, Immediate, Instruction, IntWidth, Opcode, Operands, Precision,
+    Register, RoundingMode,
+};
+
+/// A word that doesn't decode into the riscv model.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{address:#x}: {kind} ({word:#010x})")]
+pub struct DecodeError {
+    pub address: u64,
+    /// The instruction word, or halfword for a compressed instruction
+    pub word: u32,
+    pub kind: DecodeErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DecodeErrorKind {
+    /// Reserved, or of an extension the decoder doesn't know
+    #[error("unknown encoding")]
+    Unknown,
+    /// A valid instruction the model has no opcode for
+    #[error("unsupported instruction `{0}`")]
+    Unsupported(String),
+    /// The bytes ran out within the instruction
+    #[error("truncated instruction")]
+    Truncated,
+}
+
+/// Name of the label that [`decode`] gives branch targets at `address`.
+pub fn label_at(address: u64) -> String {
+    format!(".L{address:x}")
+}
+
+/// Decodes the instruction `word`, found at `address`. A word whose low
+/// two bits aren't both set is a compressed instruction in the low half.
+/// Branch and jump targets become labels named by [`label_at`].
+pub fn decode(word: u32, address: u64) -> Result<Instruction, DecodeError> {
+    let (word, full) = match word & 0b11 {
+        0b11 => (word, Some(word)),
+        _ => (word & 0xffff, expand(word as u16)),
+    };
+    full.ok_or(DecodeErrorKind::Unknown)
+        .and_then(|full| decode_full(full, address))
+        .map(alias)
+        .map_err(|kind| DecodeError {
+            address,
+            word,
+            kind,
+        })
+}
+
+/// Decodes the instructions in `bytes`, the first of them at `address`,
+/// each with its address.
+pub fn decode_bytes(bytes: &[u8], address: u64) -> Vec<(u64, Result<Instruction, DecodeError>)> {
+    let mut res = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let at = address + offset as u64;
+        let rest = &bytes[offset..];
+        let len = if rest[0] & 0b11 == 0b11 { 4 } else { 2 };
+        let mut word = [0; 4];
+        if rest.len() < len {
+            word[..rest.len()].copy_from_slice(rest);
+            let kind = DecodeErrorKind::Truncated;
+            let word = u32::from_le_bytes(word);
+            res.push((
+                at,
+                Err(DecodeError {
+                    address: at,
+                    word,
+                    kind,
+                }),
+            ));
+            break;
+        }
+        word[..len].copy_from_slice(&rest[..len]);
+        res.push((at, decode(u32::from_le_bytes(word), at)));
+        offset += len;
+    }
+    res
+}
+
+#[derive(Clone, Copy)]
+enum Class {
+    X,
+    F,
+}
+
+/// How the fields of a word become operands.
+#[derive(Clone, Copy)]
+enum Form {
+    /// `rd, rs1, rs2`
+    R,
+    /// `rd, rs1`, as [`Operands::R`] with a zero `rs2`
+    Unary,
+    /// `rd, rs1, imm`, for arithmetic, loads and `jalr`
+    I,
+    /// `rd, rs1, shamt`
+    Shift,
+    /// `rs2, imm(rs1)`
+    Store,
+    /// `rs1, rs2, target`
+    Branch,
+    /// `rd, imm`, the upper 20 bits
+    Upper,
+    /// `rd, target`
+    Jal,
+    /// `rd, csr, rs1`, where `rs1` is a 5-bit constant for `csrr*i`
+    Csr,
+    /// `rd, rs2, (rs1)`, with the width and ordering in the word; `rs2` is
+    /// zero for `lr`
+    Amo,
+    /// A floating-point operation, in the format of its `fmt` field. `rs2`
+    /// says whether it has a second source.
+    Fp { rd: Class, rs1: Class, rs2: bool },
+    /// `rd, rs1, rs2, rs3` of the fused multiply-adds
+    R4,
+    /// `rd, imm(rs1)` into a float register
+    FpLoad,
+    /// `rs2, imm(rs1)` from a float register
+    FpStore,
+    /// No operands
+    None,
+}
+
+enum Op {
+    Known(Opcode, Form),
+    /// `fcvt`, between the register classes given
+    Convert {
+        rd: Class,
+        rs1: Class,
+    },
+    /// A valid instruction the model has no opcode for
+    Unsupported(&'static str),
+}
+
+// masks of the fields that select an instruction
+const MASK_R: u32 = 0xfe00_707f;
+const MASK_I: u32 = 0x0000_707f;
+const MASK_U: u32 = 0x0000_007f;
+const MASK_SHIFT6: u32 = 0xfc00_707f;
+const MASK_IMM: u32 = 0xfff0_707f;
+// the atomics leave out `aq`, `rl` and the bit of `funct3` picking the width
+const MASK_AMO: u32 = 0xf800_607f;
+const MASK_LR: u32 = 0xf9f0_607f;
+// the FP operations leave out `fmt`, and `rm` where they round
+const MASK_FP: u32 = 0xf800_007f;
+const MASK_FP_F3: u32 = 0xf800_707f;
+const MASK_FP_RS2: u32 = 0xf9f0_007f;
+const MASK_FP_RS2_F3: u32 = 0xf9f0_707f;
+const MASK_ALL: u32 = 0xffff_ffff;
+
+const fn r(opcode: u32, funct3: u32, funct7: u32) -> u32 {
+    funct7 << 25 | funct3 << 12 | opcode
+}
+
+/// An I-type word with the whole immediate given.
+const fn imm(opcode: u32, funct3: u32, imm: u32) -> u32 {
+    imm << 20 | funct3 << 12 | opcode
+}
+
+const fn amo(funct5: u32) -> u32 {
+    funct5 << 27 | 0b010 << 12 | AMO
+}
+
+const fn fp(funct5: u32, funct3: u32) -> u32 {
+    funct5 << 27 | funct3 << 12 | OP_FP
+}
+
+/// A float operation on two float sources.
+const FP_ARITH: Form = Form::Fp {
+    rd: Class::F,
+    rs1: Class::F,
+    rs2: true,
+};
+const FP_UNARY: Form = Form::Fp {
+    rd: Class::F,
+    rs1: Class::F,
+    rs2: false,
+};
+const FP_COMPARE: Form = Form::Fp {
+    rd: Class::X,
+    rs1: Class::F,
+    rs2: true,
+};
+const FP_TO_X: Form = Form::Fp {
+    rd: Class::X,
+    rs1: Class::F,
+    rs2: false,
+};
+const FP_FROM_X: Form = Form::Fp {
+    rd: Class::F,
+    rs1: Class::X,
+    rs2: false,
+};
+
+use AmoOrdering::Relaxed;
+use AmoWidth::W;
+use Class::{F, X};
+use Op::{Known, Unsupported};
+use Precision::D;
+
+#[rustfmt::skip]
+const TABLE: &[(u32, u32, Op)] = &[
+    (MASK_R, r(OP, 0, 0x00), Known(Opcode::Add, Form::R)),
+    (MASK_R, r(OP, 0, 0x20), Known(Opcode::Sub, Form::R)),
+    (MASK_R, r(OP, 1, 0x00), Known(Opcode::Sll, Form::R)),
+    (MASK_R, r(OP, 2, 0x00), Known(Opcode::Slt, Form::R)),
+    (MASK_R, r(OP, 3, 0x00), Known(Opcode::Sltu, Form::R)),
+    (MASK_R, r(OP, 4, 0x00), Known(Opcode::Xor, Form::R)),
+    (MASK_R, r(OP, 5, 0x00), Known(Opcode::Srl, Form::R)),
+    (MASK_R, r(OP, 5, 0x20), Known(Opcode::Sra, Form::R)),
+    (MASK_R, r(OP, 6, 0x00), Known(Opcode::Or, Form::R)),
+    (MASK_R, r(OP, 7, 0x00), Known(Opcode::And, Form::R)),
+    (MASK_R, r(OP_32, 0, 0x00), Known(Opcode::Addw, Form::R)),
+    (MASK_R, r(OP_32, 0, 0x20), Known(Opcode::Subw, Form::R)),
+    (MASK_R, r(OP_32, 1, 0x00), Known(Opcode::Sllw, Form::R)),
+    (MASK_R, r(OP_32, 5, 0x00), Known(Opcode::Srlw, Form::R)),
+    (MASK_R, r(OP_32, 5, 0x20), Known(Opcode::Sraw, Form::R)),
+
+    (MASK_I, r(OP_IMM, 0, 0), Known(Opcode::Addi, Form::I)),
+    (MASK_I, r(OP_IMM, 2, 0), Known(Opcode::Slti, Form::I)),
+    (MASK_I, r(OP_IMM, 3, 0), Known(Opcode::Sltiu, Form::I)),
+    (MASK_I, r(OP_IMM, 4, 0), Known(Opcode::Xori, Form::I)),
+    (MASK_I, r(OP_IMM, 6, 0), Known(Opcode::Ori, Form::I)),
+    (MASK_I, r(OP_IMM, 7, 0), Known(Opcode::Andi, Form::I)),
+    (MASK_I, r(OP_IMM_32, 0, 0), Known(Opcode::Addiw, Form::I)),
+    (MASK_SHIFT6, r(OP_IMM, 1, 0x00), Known(Opcode::Slli, Form::Shift)),
+    (MASK_SHIFT6, r(OP_IMM, 5, 0x00), Known(Opcode::Srli, Form::Shift)),
+    (MASK_SHIFT6, r(OP_IMM, 5, 0x20), Known(Opcode::Srai, Form::Shift)),
+    (MASK_R, r(OP_IMM_32, 1, 0x00), Known(Opcode::Slliw, Form::Shift)),
+    (MASK_R, r(OP_IMM_32, 5, 0x00), Known(Opcode::Srliw, Form::Shift)),
+    (MASK_R, r(OP_IMM_32, 5, 0x20), Known(Opcode::Sraiw, Form::Shift)),
+    (MASK_U, LUI, Known(Opcode::Lui, Form::Upper)),
+    (MASK_U, AUIPC, Known(Opcode::Auipc, Form::Upper)),
+
+    (MASK_I, r(LOAD, 0, 0), Known(Opcode::Lb, Form::I)),
+    (MASK_I, r(LOAD, 1, 0), Known(Opcode::Lh, Form::I)),
+    (MASK_I, r(LOAD, 2, 0), Known(Opcode::Lw, Form::I)),
+    (MASK_I, r(LOAD, 3, 0), Known(Opcode::Ld, Form::I)),
+    (MASK_I, r(LOAD, 4, 0), Known(Opcode::Lbu, Form::I)),
+    (MASK_I, r(LOAD, 5, 0), Known(Opcode::Lhu, Form::I)),
+    (MASK_I, r(LOAD, 6, 0), Known(Opcode::Lwu, Form::I)),
+    (MASK_I, r(STORE, 0, 0), Known(Opcode::Sb, Form::Store)),
+    (MASK_I, r(STORE, 1, 0), Known(Opcode::Sh, Form::Store)),
+    (MASK_I, r(STORE, 2, 0), Known(Opcode::Sw, Form::Store)),
+    (MASK_I, r(STORE, 3, 0), Known(Opcode::Sd, Form::Store)),
+
+    (MASK_I, r(BRANCH, 0, 0), Known(Opcode::Beq, Form::Branch)),
+    (MASK_I, r(BRANCH, 1, 0), Known(Opcode::Bne, Form::Branch)),
+    (MASK_I, r(BRANCH, 4, 0), Known(Opcode::Blt, Form::Branch)),
+    (MASK_I, r(BRANCH, 5, 0), Known(Opcode::Bge, Form::Branch)),
+    (MASK_I, r(BRANCH, 6, 0), Known(Opcode::Bltu, Form::Branch)),
+    (MASK_I, r(BRANCH, 7, 0), Known(Opcode::Bgeu, Form::Branch)),
+    (MASK_U, JAL, Known(Opcode::Jal, Form::Jal)),
+    (MASK_I, r(JALR, 0, 0), Known(Opcode::Jalr, Form::I)),
+
+    (MASK_ALL, SYSTEM, Known(Opcode::Ecall, Form::None)),
+    (MASK_ALL, imm(SYSTEM, 0, 1), Known(Opcode::Ebreak, Form::None)),
+    (MASK_I, r(SYSTEM, 1, 0), Known(Opcode::Csrrw, Form::Csr)),
+    (MASK_I, r(SYSTEM, 2, 0), Known(Opcode::Csrrs, Form::Csr)),
+    (MASK_I, r(SYSTEM, 3, 0), Known(Opcode::Csrrc, Form::Csr)),
+    (MASK_I, r(SYSTEM, 5, 0), Known(Opcode::Csrrwi, Form::Csr)),
+    (MASK_I, r(SYSTEM, 6, 0), Known(Opcode::Csrrsi, Form::Csr)),
+    (MASK_I, r(SYSTEM, 7, 0), Known(Opcode::Csrrci, Form::Csr)),
+    // `fence` is only modelled with all of `iorw` on both sides
+    (MASK_ALL, imm(MISC_MEM, 0, 0x0ff), Known(Opcode::Fence, Form::None)),
+    (MASK_ALL, imm(MISC_MEM, 0, 0x010), Known(Opcode::Pause, Form::None)),
+    (MASK_I, r(MISC_MEM, 0, 0), Unsupported("fence")),
+    (MASK_ALL, r(MISC_MEM, 1, 0), Known(Opcode::Fencei, Form::None)),
+
+    (MASK_R, r(OP, 0, 0x01), Known(Opcode::Mul, Form::R)),
+    (MASK_R, r(OP, 1, 0x01), Known(Opcode::Mulh, Form::R)),
+    (MASK_R, r(OP, 2, 0x01), Known(Opcode::Mulhsu, Form::R)),
+    (MASK_R, r(OP, 3, 0x01), Known(Opcode::Mulhu, Form::R)),
+    (MASK_R, r(OP, 4, 0x01), Known(Opcode::Div, Form::R)),
+    (MASK_R, r(OP, 5, 0x01), Known(Opcode::Divu, Form::R)),
+    (MASK_R, r(OP, 6, 0x01), Known(Opcode::Rem, Form::R)),
+    (MASK_R, r(OP, 7, 0x01), Known(Opcode::Remu, Form::R)),
+    (MASK_R, r(OP_32, 0, 0x01), Known(Opcode::Mulw, Form::R)),
+    (MASK_R, r(OP_32, 4, 0x01), Known(Opcode::Divw, Form::R)),
+    (MASK_R, r(OP_32, 5, 0x01), Known(Opcode::Divuw, Form::R)),
+    (MASK_R, r(OP_32, 6, 0x01), Known(Opcode::Remw, Form::R)),
+    (MASK_R, r(OP_32, 7, 0x01), Known(Opcode::Remuw, Form::R)),
+
+    (MASK_LR, amo(0b00010), Known(Opcode::Lr(W, Relaxed), Form::Amo)),
+    (MASK_AMO, amo(0b00011), Known(Opcode::Sc(W, Relaxed), Form::Amo)),
+    (MASK_AMO, amo(0b00001), Known(Opcode::Amoswap(W, Relaxed), Form::Amo)),
+    (MASK_AMO, amo(0b00000), Known(Opcode::Amoadd(W, Relaxed), Form::Amo)),
+    (MASK_AMO, amo(0b00100), Known(Opcode::Amoxor(W, Relaxed), Form::Amo)),
+    (MASK_AMO, amo(0b01100), Known(Opcode::Amoand(W, Relaxed), Form::Amo)),
+    (MASK_AMO, amo(0b01000), Known(Opcode::Amoor(W, Relaxed), Form::Amo)),
+    (MASK_AMO, amo(0b10000), Known(Opcode::Amomin(W, Relaxed), Form::Amo)),
+    (MASK_AMO, amo(0b10100), Known(Opcode::Amomax(W, Relaxed), Form::Amo)),
+    (MASK_AMO, amo(0b11000), Known(Opcode::Amominu(W, Relaxed), Form::Amo)),
+    (MASK_AMO, amo(0b11100), Known(Opcode::Amomaxu(W, Relaxed), Form::Amo)),
+
+    (MASK_I, r(LOAD_FP, 2, 0), Known(Opcode::Flw, Form::FpLoad)),
+    (MASK_I, r(LOAD_FP, 3, 0), Known(Opcode::Fld, Form::FpLoad)),
+    (MASK_I, r(STORE_FP, 2, 0), Known(Opcode::Fsw, Form::FpStore)),
+    (MASK_I, r(STORE_FP, 3, 0), Known(Opcode::Fsd, Form::FpStore)),
+    (MASK_FP, fp(0b00000, 0), Known(Opcode::Fadd(D, None), FP_ARITH)),
+    (MASK_FP, fp(0b00001, 0), Known(Opcode::Fsub(D, None), FP_ARITH)),
+    (MASK_FP, fp(0b00010, 0), Known(Opcode::Fmul(D, None), FP_ARITH)),
+    (MASK_FP, fp(0b00011, 0), Known(Opcode::Fdiv(D, None), FP_ARITH)),
+    (MASK_FP_RS2, fp(0b01011, 0), Known(Opcode::Fsqrt(D, None), FP_UNARY)),
+    (MASK_FP_F3, fp(0b00100, 0), Known(Opcode::Fsgnj(D), FP_ARITH)),
+    (MASK_FP_F3, fp(0b00100, 1), Known(Opcode::Fsgnjn(D), FP_ARITH)),
+    (MASK_FP_F3, fp(0b00100, 2), Known(Opcode::Fsgnjx(D), FP_ARITH)),
+    (MASK_FP_F3, fp(0b00101, 0), Known(Opcode::Fmin(D), FP_ARITH)),
+    (MASK_FP_F3, fp(0b00101, 1), Known(Opcode::Fmax(D), FP_ARITH)),
+    (MASK_FP_F3, fp(0b10100, 2), Known(Opcode::Feq(D), FP_COMPARE)),
+    (MASK_FP_F3, fp(0b10100, 1), Known(Opcode::Flt(D), FP_COMPARE)),
+    (MASK_FP_F3, fp(0b10100, 0), Known(Opcode::Fle(D), FP_COMPARE)),
+    (MASK_FP_RS2_F3, fp(0b11100, 1), Known(Opcode::Fclass(D), FP_TO_X)),
+    (MASK_FP_RS2_F3, fp(0b11100, 0), Known(Opcode::Fmv(D), FP_TO_X)),
+    (MASK_FP_RS2_F3, fp(0b11110, 0), Known(Opcode::Fmv(D), FP_FROM_X)),
+    (MASK_FP, fp(0b01000, 0), Op::Convert { rd: F, rs1: F }),
+    (MASK_FP, fp(0b11000, 0), Op::Convert { rd: X, rs1: F }),
+    (MASK_FP, fp(0b11010, 0), Op::Convert { rd: F, rs1: X }),
+    (MASK_U, MADD, Known(Opcode::Fmadd(D, None), Form::R4)),
+    (MASK_U, MSUB, Known(Opcode::Fmsub(D, None), Form::R4)),
+    (MASK_U, NMSUB, Known(Opcode::Fnmsub(D, None), Form::R4)),
+    (MASK_U, NMADD, Known(Opcode::Fnmadd(D, None), Form::R4)),
+
+    (MASK_R, r(OP, 2, 0x10), Known(Opcode::Sh1add, Form::R)),
+    (MASK_R, r(OP, 4, 0x10), Known(Opcode::Sh2add, Form::R)),
+    (MASK_R, r(OP, 6, 0x10), Known(Opcode::Sh3add, Form::R)),
+    (MASK_R, r(OP_32, 0, 0x04), Known(Opcode::AddUw, Form::R)),
+    (MASK_R, r(OP_32, 2, 0x10), Known(Opcode::Sh1addUw, Form::R)),
+    (MASK_R, r(OP_32, 4, 0x10), Known(Opcode::Sh2addUw, Form::R)),
+    (MASK_R, r(OP_32, 6, 0x10), Known(Opcode::Sh3addUw, Form::R)),
+    (MASK_SHIFT6, r(OP_IMM_32, 1, 0x04), Known(Opcode::SlliUw, Form::Shift)),
+
+    (MASK_R, r(OP, 7, 0x20), Known(Opcode::Andn, Form::R)),
+    (MASK_R, r(OP, 6, 0x20), Known(Opcode::Orn, Form::R)),
+    (MASK_R, r(OP, 4, 0x20), Known(Opcode::Xnor, Form::R)),
+    (MASK_IMM, imm(OP_IMM, 1, 0x600), Known(Opcode::Clz, Form::Unary)),
+    (MASK_IMM, imm(OP_IMM, 1, 0x601), Known(Opcode::Ctz, Form::Unary)),
+    (MASK_IMM, imm(OP_IMM, 1, 0x602), Known(Opcode::Cpop, Form::Unary)),
+    (MASK_IMM, imm(OP_IMM, 1, 0x604), Known(Opcode::SextB, Form::Unary)),
+    (MASK_IMM, imm(OP_IMM, 1, 0x605), Known(Opcode::SextH, Form::Unary)),
+    (MASK_IMM, imm(OP_IMM_32, 1, 0x600), Known(Opcode::Clzw, Form::Unary)),
+    (MASK_IMM, imm(OP_IMM_32, 1, 0x601), Known(Opcode::Ctzw, Form::Unary)),
+    (MASK_IMM, imm(OP_IMM_32, 1, 0x602), Known(Opcode::Cpopw, Form::Unary)),
+    (MASK_IMM, imm(OP_32, 4, 0x080), Known(Opcode::ZextH, Form::Unary)),
+    (MASK_IMM, imm(OP_IMM, 5, 0x287), Known(Opcode::OrcB, Form::Unary)),
+    (MASK_IMM, imm(OP_IMM, 5, 0x6b8), Known(Opcode::Rev8, Form::Unary)),
+    (MASK_R, r(OP, 6, 0x05), Known(Opcode::Max, Form::R)),
+    (MASK_R, r(OP, 7, 0x05), Known(Opcode::Maxu, Form::R)),
+    (MASK_R, r(OP, 4, 0x05), Known(Opcode::Min, Form::R)),
+    (MASK_R, r(OP, 5, 0x05), Known(Opcode::Minu, Form::R)),
+    (MASK_R, r(OP, 1, 0x30), Known(Opcode::Rol, Form::R)),
+    (MASK_R, r(OP, 5, 0x30), Known(Opcode::Ror, Form::R)),
+    (MASK_R, r(OP_32, 1, 0x30), Known(Opcode::Rolw, Form::R)),
+    (MASK_R, r(OP_32, 5, 0x30), Known(Opcode::Rorw, Form::R)),
+    (MASK_SHIFT6, r(OP_IMM, 5, 0x30), Known(Opcode::Rori, Form::Shift)),
+    (MASK_R, r(OP_IMM_32, 5, 0x30), Known(Opcode::Roriw, Form::Shift)),
+];
+
+/// The format an `fmt` field, or the `rs2` of an `fcvt` between formats,
+/// stands for.
+fn precision(field: u32) -> Result<Precision, DecodeErrorKind> {
+    match field {
+        0b00 => Ok(Precision::S),
+        0b01 => Ok(Precision::D),
+        0b10 => Ok(Precision::H),
+        // quad precision
+        _ => Err(DecodeErrorKind::Unknown),
+    }
+}
+
+fn width(field: u32) -> Result<IntWidth, DecodeErrorKind> {
+    match field {
+        0 => Ok(IntWidth::W),
+        1 => Ok(IntWidth::Wu),
+        2 => Ok(IntWidth::L),
+        3 => Ok(IntWidth::Lu),
+        _ => Err(DecodeErrorKind::Unknown),
+    }
+}
+
+/// The rounding mode in an `rm` field, or `None` if it is the one the
+/// assembler picks when none is written.
+fn rounding_mode(field: u32, exact: bool) -> Result<Option<RoundingMode>, DecodeErrorKind> {
+    let mode = match field {
+        0b000 => RoundingMode::Rne,
+        0b001 => RoundingMode::Rtz,
+        0b010 => RoundingMode::Rdn,
+        0b011 => RoundingMode::Rup,
+        0b100 => RoundingMode::Rmm,
+        0b111 => RoundingMode::Dyn,
+        _ => return Err(DecodeErrorKind::Unknown),
+    };
+    let default = match exact {
+        true => RoundingMode::Rne,
+        false => RoundingMode::Dyn,
+    };
+    Ok((mode != default).then_some(mode))
+}
+
+/// The atomic `opcode` with the width and ordering of `w`.
+fn atomic(opcode: Opcode, w: Word) -> Opcode {
+    use Opcode::*;
+    let width = match w.bits(12, 12) {
+        0 => AmoWidth::W,
+        _ => AmoWidth::D,
+    };
+    let order = match w.bits(26, 25) {
+        0b00 => AmoOrdering::Relaxed,
+        0b01 => AmoOrdering::Rl,
+        0b10 => AmoOrdering::Aq,
+        _ => AmoOrdering::AqRl,
+    };
+    match opcode {
+        Lr(..) => Lr(width, order),
+        Sc(..) => Sc(width, order),
+        Amoswap(..) => Amoswap(width, order),
+        Amoadd(..) => Amoadd(width, order),
+        Amoxor(..) => Amoxor(width, order),
+        Amoand(..) => Amoand(width, order),
+        Amoor(..) => Amoor(width, order),
+        Amomin(..) => Amomin(width, order),
+        Amomax(..) => Amomax(width, order),
+        Amominu(..) => Amominu(width, order),
+        Amomaxu(..) => Amomaxu(width, order),
+        opcode => opcode,
+    }
+}
+
+/// `opcode` in the format and rounding mode of `w`.
+fn qualify(opcode: Opcode, w: Word) -> Result<Opcode, DecodeErrorKind> {
+    use Opcode::*;
+    let p = precision(w.bits(26, 25))?;
+    let rm = || rounding_mode(w.bits(14, 12), false);
+    Ok(match opcode {
+        Fadd(..) => Fadd(p, rm()?),
+        Fsub(..) => Fsub(p, rm()?),
+        Fmul(..) => Fmul(p, rm()?),
+        Fdiv(..) => Fdiv(p, rm()?),
+        Fsqrt(..) => Fsqrt(p, rm()?),
+        Fmadd(..) => Fmadd(p, rm()?),
+        Fmsub(..) => Fmsub(p, rm()?),
+        Fnmadd(..) => Fnmadd(p, rm()?),
+        Fnmsub(..) => Fnmsub(p, rm()?),
+        Fsgnj(_) => Fsgnj(p),
+        Fsgnjn(_) => Fsgnjn(p),
+        Fsgnjx(_) => Fsgnjx(p),
+        Fmin(_) => Fmin(p),
+        Fmax(_) => Fmax(p),
+        Feq(_) => Feq(p),
+        Flt(_) => Flt(p),
+        Fle(_) => Fle(p),
+        Fclass(_) => Fclass(p),
+        Fmv(_) => Fmv(p),
+        opcode => opcode,
+    })
+}
+
+#[derive(Clone, Copy)]
+struct Word(u32);
+
+impl Word {
+    /// Bits `hi` down to `lo`, inclusive.
+    fn bits(self, hi: u32, lo: u32) -> u32 {
+        (self.0 >> lo) & ((1 << (hi - lo + 1)) - 1)
+    }
+
+    /// Bits `hi` down to `lo` as a two's complement number.
+    fn signed(self, hi: u32, lo: u32) -> i64 {
+        let width = hi - lo + 1;
+        let value = i64::from(self.bits(hi, lo));
+        (value << (64 - width)) >> (64 - width)
+    }
+
+    fn rd(self) -> u32 {
+        self.bits(11, 7)
+    }
+
+    fn rs1(self) -> u32 {
+        self.bits(19, 15)
+    }
+
+    fn rs2(self) -> u32 {
+        self.bits(24, 20)
+    }
+
+    fn imm_i(self) -> i64 {
+        self.signed(31, 20)
+    }
+
+    fn imm_s(self) -> i64 {
+        self.signed(31, 25) << 5 | i64::from(self.bits(11, 7))
+    }
+
+    fn imm_b(self) -> i64 {
+        self.signed(31, 31) << 12
+            | i64::from(self.bits(7, 7) << 11 | self.bits(30, 25) << 5 | self.bits(11, 8) << 1)
+    }
+
+    fn imm_j(self) -> i64 {
+        self.signed(31, 31) << 20
+            | i64::from(self.bits(19, 12) << 12 | self.bits(20, 20) << 11 | self.bits(30, 21) << 1)
+    }
+}
+
+fn reg(class: Class, n: u32) -> Register {
+    match class {
+        Class::X => Register::X(n as u8),
+        Class::F => Register::F(n as u8),
+    }
+}
+
+fn x(n: u32) -> Register {
+    Register::X(n as u8)
+}
+
+fn decode_full(word: u32, address: u64) -> Result<Instruction, DecodeErrorKind> {
+    let (_, _, op) = TABLE
+        .iter()
+        .find(|(mask, bits, _)| word & mask == *bits)
+        .ok_or(DecodeErrorKind::Unknown)?;
+    let w = Word(word);
+    let (opcode, form) = match op {
+        Known(opcode, form @ (Form::Fp { .. } | Form::R4)) => (qualify(opcode.clone(), w)?, *form),
+        Known(opcode, Form::Amo) => (atomic(opcode.clone(), w), Form::Amo),
+        Known(opcode, form) => (opcode.clone(), *form),
+        Op::Convert { rd, rs1 } => return convert(w, *rd, *rs1),
+        Unsupported(name) => return Err(DecodeErrorKind::Unsupported(name.to_string())),
+    };
+    let target = |offset: i64| Immediate::Label(label_at(address.wrapping_add_signed(offset)));
+    let operands = match form {
+        Form::R => Operands::R {
+            rd: x(w.rd()),
+            rs1: x(w.rs1()),
+            rs2: x(w.rs2()),
+        },
+        Form::Unary => Operands::R {
+            rd: x(w.rd()),
+            rs1: x(w.rs1()),
+            rs2: Register::ZERO,
+        },
+        Form::I => Operands::I {
+            rd: x(w.rd()),
+            rs1: x(w.rs1()),
+            imm: Immediate::Number(w.imm_i()),
+        },
+        Form::Shift => Operands::I {
+            rd: x(w.rd()),
+            rs1: x(w.rs1()),
+            imm: Immediate::Number(i64::from(w.bits(25, 20))),
+        },
+        Form::Store => Operands::S {
+            rs1: x(w.rs1()),
+            rs2: x(w.rs2()),
+            imm: Immediate::Number(w.imm_s()),
+        },
+        Form::Branch => Operands::S {
+            rs1: x(w.rs1()),
+            rs2: x(w.rs2()),
+            imm: target(w.imm_b()),
+        },
+        // `lui` is built with the zero `rs1` the translator gives it
+        Form::Upper if opcode == Opcode::Lui => Operands::I {
+            rd: x(w.rd()),
+            rs1: Register::ZERO,
+            imm: Immediate::Number(i64::from(w.bits(31, 12))),
+        },
+        Form::Upper => Operands::U {
+            rd: x(w.rd()),
+            imm: Immediate::Number(i64::from(w.bits(31, 12))),
+        },
+        Form::Jal => Operands::U {
+            rd: x(w.rd()),
+            imm: target(w.imm_j()),
+        },
+        Form::Csr => Operands::I {
+            rd: x(w.rd()),
+            rs1: x(w.rs1()),
+            imm: Immediate::Number(i64::from(w.bits(31, 20))),
+        },
+        Form::Amo => Operands::Amo {
+            rd: x(w.rd()),
+            rs2: x(w.rs2()),
+            rs1: x(w.rs1()),
+        },
+        Form::Fp { rd, rs1, rs2 } => Operands::R {
+            rd: reg(rd, w.rd()),
+            rs1: reg(rs1, w.rs1()),
+            rs2: match rs2 {
+                true => Register::F(w.rs2() as u8),
+                false => Register::ZERO,
+            },
+        },
+        Form::R4 => Operands::R4 {
+            rd: Register::F(w.rd() as u8),
+            rs1: Register::F(w.rs1() as u8),
+            rs2: Register::F(w.rs2() as u8),
+            rs3: Register::F(w.bits(31, 27) as u8),
+        },
+        Form::FpLoad => Operands::I {
+            rd: Register::F(w.rd() as u8),
+            rs1: x(w.rs1()),
+            imm: Immediate::Number(w.imm_i()),
+        },
+        Form::FpStore => Operands::S {
+            rs1: x(w.rs1()),
+            rs2: Register::F(w.rs2() as u8),
+            imm: Immediate::Number(w.imm_s()),
+        },
+        Form::None => Operands::Nop,
+    };
+    Ok(Instruction { opcode, operands })
+}
+
+/// An `fcvt` from an `rs1` of class `from` to an `rd` of class `to`.
+fn convert(w: Word, to: Class, from: Class) -> Result<Instruction, DecodeErrorKind> {
+    let format = FpType::Float(precision(w.bits(26, 25))?);
+    let (to_type, from_type) = match (to, from) {
+        (Class::F, Class::F) => (format, FpType::Float(precision(w.rs2())?)),
+        (Class::X, _) => (FpType::Int(width(w.rs2())?), format),
+        (_, Class::X) => (format, FpType::Int(width(w.rs2())?)),
+    };
+    if to_type == from_type {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    let rm = rounding_mode(w.bits(14, 12), is_exact(to_type, from_type))?;
+    Ok(Instruction::new_r(
+        Opcode::Fcvt(to_type, from_type, rm),
+        reg(to, w.rd()),
+        reg(from, w.rs1()),
+        Register::ZERO,
+    ))
+}
+
+/// The alias `objdump` would print `inst` as, if the model has it.
+fn alias(inst: Instruction) -> Instruction {
+    use Opcode::*;
+    const ZERO: Register = Register::ZERO;
+    let zero = Immediate::Number(0);
+    match (&inst.opcode, &inst.operands) {
+        (
+            Addi,
+            Operands::I {
+                rd: ZERO,
+                rs1: ZERO,
+                imm,
+            },
+        ) if *imm == zero => Instruction::new_nop(),
+        (Addi, Operands::I { rd, rs1: ZERO, imm }) => Instruction::new_u(Li, *rd, imm.clone()),
+        (Addi, Operands::I { rd, rs1, imm }) if *imm == zero => {
+            Instruction::new_r(Mv, *rd, *rs1, ZERO)
+        }
+        (Xori, Operands::I { rd, rs1, imm }) if *imm == Immediate::Number(-1) => {
+            Instruction::new_r(Not, *rd, *rs1, ZERO)
+        }
+        (Sub, Operands::R { rd, rs1: ZERO, rs2 }) => Instruction::new_r(Neg, *rd, *rs2, ZERO),
+        (Subw, Operands::R { rd, rs1: ZERO, rs2 }) => Instruction::new_r(Negw, *rd, *rs2, ZERO),
+        (
+            Jalr,
+            Operands::I {
+                rd: ZERO,
+                rs1: Register::X(1),
+                imm,
+            },
+        ) if *imm == zero => Instruction::new_ret(),
+        (Jal, Operands::U { rd: ZERO, imm }) => Instruction::new_u(J, ZERO, imm.clone()),
+        (Beq | Bne | Blt | Bge, Operands::S { rs1, rs2, imm }) if [*rs1, *rs2].contains(&ZERO) => {
+            let (opcode, rs) = match (&inst.opcode, *rs2 == ZERO) {
+                (Beq, true) => (Beqz, *rs1),
+                (Bne, true) => (Bnez, *rs1),
+                (Blt, true) => (Bltz, *rs1),
+                (Bge, true) => (Bgez, *rs1),
+                (Blt, false) => (Bgtz, *rs2),
+                (Bge, false) => (Blez, *rs2),
+                _ => return inst,
+            };
+            Instruction::new_s(opcode, rs, ZERO, imm.clone())
+        }
+        (Fsgnj(p), Operands::R { rd, rs1, rs2 }) if rs1 == rs2 => {
+            Instruction::new_r(Fmv(*p), *rd, *rs1, ZERO)
+        }
+        _ => inst,
+    }
+}
+
+/// The 32-bit word a compressed instruction stands for, or `None` if it is
+/// reserved. `c.mv` expands to `addi rd, rs2, 0` so that it decodes as `mv`.
+pub fn expand(half: u16) -> Option<u32> {
+    let h = Word(u32::from(half));
+    let bit = |n: u32| h.bits(n, n);
+    let rd = h.bits(11, 7);
+    let rs2 = h.bits(6, 2);
+    // the 3-bit register fields name x8-x15
+    let rd_short = h.bits(4, 2) + 8;
+    let rs1_short = h.bits(9, 7) + 8;
+    let imm6 = h.signed(12, 12) << 5 | i64::from(rs2);
+    let shamt = bit(12) << 5 | rs2;
+    let shift = |funct3: u32, funct6: u32, rd: u32| {
+        funct6 << 26 | shamt << 20 | rd << 15 | funct3 << 12 | rd << 7 | OP_IMM
+    };
+    // offsets of the doubleword and word loads and stores
+    let off_d = i64::from(h.bits(12, 10) << 3 | h.bits(6, 5) << 6);
+    let off_w = i64::from(h.bits(12, 10) << 3 | bit(6) << 2 | bit(5) << 6);
+    let off_dsp = i64::from(bit(12) << 5 | h.bits(6, 5) << 3 | h.bits(4, 2) << 6);
+    let off_wsp = i64::from(bit(12) << 5 | h.bits(6, 4) << 2 | h.bits(3, 2) << 6);
+    let off_sdsp = i64::from(h.bits(12, 10) << 3 | h.bits(9, 7) << 6);
+    let off_swsp = i64::from(h.bits(12, 9) << 2 | h.bits(8, 7) << 6);
+
+    let word = match (h.bits(1, 0), h.bits(15, 13)) {
+        (0b00, 0b000) => {
+            let imm = h.bits(12, 11) << 4 | h.bits(10, 7) << 6 | bit(6) << 2 | bit(5) << 3;
+            if imm == 0 {
+                return None;
+            }
+            i_type(OP_IMM, 0, rd_short, 2, i64::from(imm))
+        }
+        (0b00, 0b001) => i_type(LOAD_FP, 3, rd_short, rs1_short, off_d),
+        (0b00, 0b010) => i_type(LOAD, 2, rd_short, rs1_short, off_w),
+        (0b00, 0b011) => i_type(LOAD, 3, rd_short, rs1_short, off_d),
+        (0b00, 0b101) => s_type(STORE_FP, 3, rs1_short, rd_short, off_d),
+        (0b00, 0b110) => s_type(STORE, 2, rs1_short, rd_short, off_w),
+        (0b00, 0b111) => s_type(STORE, 3, rs1_short, rd_short, off_d),
+
+        (0b01, 0b000) => i_type(OP_IMM, 0, rd, rd, imm6),
+        (0b01, 0b001) if rd != 0 => i_type(OP_IMM_32, 0, rd, rd, imm6),
+        (0b01, 0b010) => i_type(OP_IMM, 0, rd, 0, imm6),
+        (0b01, 0b011) if rd == 2 => {
+            let imm = h.signed(12, 12) << 9
+                | i64::from(bit(6) << 4 | bit(5) << 6 | h.bits(4, 3) << 7 | bit(2) << 5);
+            if imm == 0 {
+                return None;
+            }
+            i_type(OP_IMM, 0, 2, 2, imm)
+        }
+        (0b01, 0b011) if imm6 != 0 => u_type(LUI, rd, imm6 & 0xfffff),
+        (0b01, 0b100) => match (h.bits(11, 10), bit(12), h.bits(6, 5)) {
+            (0b00, _, _) => Ok(shift(5, 0, rs1_short)),
+            (0b01, _, _) => Ok(shift(5, 0b010000, rs1_short)),
+            (0b10, _, _) => i_type(OP_IMM, 7, rs1_short, rs1_short, imm6),
+            (_, 0, op) => {
+                let (funct3, funct7) = [(0, 0x20), (4, 0), (6, 0), (7, 0)][op as usize];
+                Ok(r_type(OP, funct3, funct7, rs1_short, rs1_short, rd_short))
+            }
+            (_, _, 0b00) => Ok(r_type(OP_32, 0, 0x20, rs1_short, rs1_short, rd_short)),
+            (_, _, 0b01) => Ok(r_type(OP_32, 0, 0, rs1_short, rs1_short, rd_short)),
+            _ => return None,
+        },
+        (0b01, 0b101) => {
+            let imm = h.signed(12, 12) << 11
+                | i64::from(
+                    bit(11) << 4
+                        | h.bits(10, 9) << 8
+                        | bit(8) << 10
+                        | bit(7) << 6
+                        | bit(6) << 7
+                        | h.bits(5, 3) << 1
+                        | bit(2) << 5,
+                );
+            j_type(0, imm)
+        }
+        (0b01, funct3 @ (0b110 | 0b111)) => {
+            let imm = h.signed(12, 12) << 8
+                | i64::from(
+                    h.bits(11, 10) << 3 | h.bits(6, 5) << 6 | h.bits(4, 3) << 1 | bit(2) << 5,
+                );
+            b_type(funct3 & 1, rs1_short, 0, imm)
+        }
+
+        (0b10, 0b000) => Ok(shift(1, 0, rd)),
+        (0b10, 0b001) => i_type(LOAD_FP, 3, rd, 2, off_dsp),
+        (0b10, 0b010) if rd != 0 => i_type(LOAD, 2, rd, 2, off_wsp),
+        (0b10, 0b011) if rd != 0 => i_type(LOAD, 3, rd, 2, off_dsp),
+        (0b10, 0b100) => match (bit(12), rd, rs2) {
+            (0, 0, _) => return None,
+            (0, rs1, 0) => i_type(JALR, 0, 0, rs1, 0),
+            (0, rd, rs2) => i_type(OP_IMM, 0, rd, rs2, 0),
+            (_, 0, 0) => Ok(imm(SYSTEM, 0, 1)),
+            (_, rs1, 0) => i_type(JALR, 0, 1, rs1, 0),
+            (_, rd, rs2) => Ok(r_type(OP, 0, 0, rd, rd, rs2)),
+        },
+        (0b10, 0b101) => s_type(STORE_FP, 3, 2, rs2, off_sdsp),
+        (0b10, 0b110) => s_type(STORE, 2, 2, rs2, off_swsp),
+        (0b10, 0b111) => s_type(STORE, 3, 2, rs2, off_sdsp),
+        _ => return None,
+    };
+    word.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::riscv::Encoder;
+    use expect_test::expect;
+
+    /// Code assembled by `llvm-mc -mattr=+m,+a,+f,+d,+c,+zba,+zbb,+zfh`, as the
+    /// halfwords and words `llvm-objdump` shows.
+    ///
+    /// The listing below is what `llvm-objdump -d --no-show-raw-insn` with the
+    /// same `--mattr` prints for it, with labels for branch targets and the
+    /// base forms the module documentation names.
+    const CODE: &str = "
+        00c58533 9532 8c0d 9d2d 9f1d 407352b3 00c5b533 0505 5565 7ff58513 7139 0800
+        357d 893d ffd5a513 0055b513 0ff5e513 1502 957d 8085 0035d29b 41f5d29b 657d
+        01234537 00000317 6522 41c8 fff5c503 00635283 00c36283 ec06 c588 fea59f23
+        00530023 f8b50ee3 f8b51ce3 2062e663 2062f463 20050263 20051063 1e029e63
+        1e054c63 1e055a63 1ea05863 1ea04663 bf85 1e6000ef 9502 8282 010502e7 8082
+        00000073 9002 0ff0000f 0000100f 00302573 00351073 0012d573 30047073 02c58533
+        02c59533 02c5a533 02c5b533 02c5853b 02c5c533 02c5d533 02c5c53b 02c5d53b
+        02c5e533 02c5f533 02c5e53b 02c5f53b 1005b52f 18c5b52f 08c5b52f 00c5b52f
+        20c5b52f 60c5b52f 40c5b52f 80c5b52f a0c5b52f c0c5b52f e0c5b52f 1405a52f
+        1ac5a52f 06b525af 86b535af 08c5a52f 2522 290c 0045b007 0045a507 a42a a90c
+        fea5ae27 02b57553 0ac5f553 1220f053 1ab57553 5a05f553 22c58553 22b51553
+        22b52553 22b58553 d2257553 c2257553 42058553 e2058553 f2058553 00c5f553
+        0cc59553 10c58553 5805f553 2ac58553 28c59553 24b58553 a2b52553 a0b51553
+        a4b50553 e0059553 c0051553 c2157553 c4352553 d0057553 d2150553 d0254553
+        4015f553 4405f553 42258553 e0058553 f4058553 6ac5f543 68c59547 6cc5f54b
+        6ac5854f 852e 840a 40b00533 40b0053b fff5c513 0001 20c5a533 20c5c533
+        20c5e533 08c5853b 20c5a53b 20c5c53b 20c5e53b 0a15951b 40c5f533 40c5e533
+        40c5c533 60059513 6005951b 60159513 6015951b 60259513 6025951b 0ac5e533
+        0ac5f533 0ac5c533 0ac5d533 60459513 60559513 0805c53b 60c59533 60c5953b
+        60c5d533 6285d513 6075d51b 60c5d53b 2875d513 6b85d513 8082
+    ";
+
+    fn bytes(code: &str) -> Vec<u8> {
+        code.split_whitespace()
+            .flat_map(|word| {
+                let value = u32::from_str_radix(word, 16).unwrap();
+                value.to_le_bytes().into_iter().take(word.len() / 2)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decode_listing() {
+        let bytes = bytes(CODE);
+        let mut listing = String::new();
+        for (address, inst) in decode_bytes(&bytes, 0) {
+            let inst = inst.unwrap();
+            listing += &format!("{address:04x}:\t{inst}\n");
+            // what decodes without labels encodes back to the same bytes
+            let code = Encoder::default().encode(&inst).unwrap();
+            if code.relocs.is_empty() {
+                let start = address as usize;
+                assert_eq!(code.bytes, bytes[start..start + code.bytes.len()], "{inst}");
+            }
+        }
+        expect![[r#"
+            0000:	add	a0, a1, a2
+            0004:	add	a0, a0, a2
+            0006:	sub	s0, s0, a1
+            0008:	addw	a0, a0, a1
+            000a:	subw	a4, a4, a5
+            000c:	sra	t0, t1, t2
+            0010:	sltu	a0, a1, a2
+            0014:	addi	a0, a0, 1
+            0016:	li	a0, -7
+            0018:	addi	a0, a1, 2047
+            001c:	addi	sp, sp, -64
+            001e:	addi	s0, sp, 16
+            0020:	addiw	a0, a0, -1
+            0022:	andi	a0, a0, 15
+            0024:	slti	a0, a1, -3
+            0028:	sltiu	a0, a1, 5
+            002c:	ori	a0, a1, 255
+            0030:	slli	a0, a0, 32
+            0032:	srai	a0, a0, 63
+            0034:	srli	s1, s1, 1
+            0036:	srliw	t0, a1, 3
+            003a:	sraiw	t0, a1, 31
+            003e:	lui	a0, 31
+            0040:	lui	a0, 4660
+            0044:	auipc	t1, 0
+            0048:	ld	a0, 8(sp)
+            004a:	lw	a0, 4(a1)
+            004c:	lbu	a0, -1(a1)
+            0050:	lhu	t0, 6(t1)
+            0054:	lwu	t0, 12(t1)
+            0058:	sd	ra, 24(sp)
+            005a:	sw	a0, 8(a1)
+            005c:	sh	a0, -2(a1)
+            0060:	sb	t0, 0(t1)
+            0064:	beq	a0, a1, .L0
+            0068:	bne	a0, a1, .L0
+            006c:	bltu	t0, t1, .L278
+            0070:	bgeu	t0, t1, .L278
+            0074:	beqz	a0, .L278
+            0078:	bnez	a0, .L278
+            007c:	bnez	t0, .L278
+            0080:	bltz	a0, .L278
+            0084:	bgez	a0, .L278
+            0088:	blez	a0, .L278
+            008c:	bgtz	a0, .L278
+            0090:	j	.L0
+            0092:	jal	ra, .L278
+            0096:	jalr	ra, 0(a0)
+            0098:	jalr	zero, 0(t0)
+            009a:	jalr	t0, 16(a0)
+            009e:	ret
+            00a0:	ecall
+            00a4:	ebreak
+            00a6:	fence
+            00aa:	fence.i
+            00ae:	csrrs	a0, 3, zero
+            00b2:	csrrw	zero, 3, a0
+            00b6:	csrrwi	a0, 1, 5
+            00ba:	csrrci	zero, 768, 8
+            00be:	mul	a0, a1, a2
+            00c2:	mulh	a0, a1, a2
+            00c6:	mulhsu	a0, a1, a2
+            00ca:	mulhu	a0, a1, a2
+            00ce:	mulw	a0, a1, a2
+            00d2:	div	a0, a1, a2
+            00d6:	divu	a0, a1, a2
+            00da:	divw	a0, a1, a2
+            00de:	divuw	a0, a1, a2
+            00e2:	rem	a0, a1, a2
+            00e6:	remu	a0, a1, a2
+            00ea:	remw	a0, a1, a2
+            00ee:	remuw	a0, a1, a2
+            00f2:	lr.d	a0, (a1)
+            00f6:	sc.d	a0, a2, (a1)
+            00fa:	amoswap.d	a0, a2, (a1)
+            00fe:	amoadd.d	a0, a2, (a1)
+            0102:	amoxor.d	a0, a2, (a1)
+            0106:	amoand.d	a0, a2, (a1)
+            010a:	amoor.d	a0, a2, (a1)
+            010e:	amomin.d	a0, a2, (a1)
+            0112:	amomax.d	a0, a2, (a1)
+            0116:	amominu.d	a0, a2, (a1)
+            011a:	amomaxu.d	a0, a2, (a1)
+            011e:	lr.w.aq	a0, (a1)
+            0122:	sc.w.rl	a0, a2, (a1)
+            0126:	amoadd.w.aqrl	a1, a1, (a0)
+            012a:	amomin.d.aqrl	a1, a1, (a0)
+            012e:	amoswap.w	a0, a2, (a1)
+            0132:	fld	fa0, 8(sp)
+            0134:	fld	fa1, 16(a0)
+            0136:	fld	ft0, 4(a1)
+            013a:	flw	fa0, 4(a1)
+            013e:	fsd	fa0, 8(sp)
+            0140:	fsd	fa1, 16(a0)
+            0142:	fsw	fa0, -4(a1)
+            0146:	fadd.d	fa0, fa0, fa1
+            014a:	fsub.d	fa0, fa1, fa2
+            014e:	fmul.d	ft0, ft1, ft2
+            0152:	fdiv.d	fa0, fa0, fa1
+            0156:	fsqrt.d	fa0, fa1
+            015a:	fsgnj.d	fa0, fa1, fa2
+            015e:	fsgnjn.d	fa0, fa0, fa1
+            0162:	fsgnjx.d	fa0, fa0, fa1
+            0166:	fmv.d	fa0, fa1
+            016a:	fcvt.d.l	fa0, a0
+            016e:	fcvt.l.d	a0, fa0
+            0172:	fcvt.d.s	fa0, fa1
+            0176:	fmv.x.d	a0, fa1
+            017a:	fmv.d.x	fa0, a1
+            017e:	fadd.s	fa0, fa1, fa2
+            0182:	fsub.h	fa0, fa1, fa2, rtz
+            0186:	fmul.s	fa0, fa1, fa2, rne
+            018a:	fsqrt.s	fa0, fa1
+            018e:	fmin.d	fa0, fa1, fa2
+            0192:	fmax.s	fa0, fa1, fa2
+            0196:	fmv.h	fa0, fa1
+            019a:	feq.d	a0, fa0, fa1
+            019e:	flt.s	a0, fa0, fa1
+            01a2:	fle.h	a0, fa0, fa1
+            01a6:	fclass.s	a0, fa1
+            01aa:	fcvt.w.s	a0, fa0, rtz
+            01ae:	fcvt.wu.d	a0, fa0
+            01b2:	fcvt.lu.h	a0, fa0, rdn
+            01b6:	fcvt.s.w	fa0, a0
+            01ba:	fcvt.d.wu	fa0, a0
+            01be:	fcvt.s.l	fa0, a0, rmm
+            01c2:	fcvt.s.d	fa0, fa1
+            01c6:	fcvt.h.s	fa0, fa1
+            01ca:	fcvt.d.h	fa0, fa1
+            01ce:	fmv.x.w	a0, fa1
+            01d2:	fmv.h.x	fa0, a1
+            01d6:	fmadd.d	fa0, fa1, fa2, fa3
+            01da:	fmsub.s	fa0, fa1, fa2, fa3, rtz
+            01de:	fnmsub.h	fa0, fa1, fa2, fa3
+            01e2:	fnmadd.d	fa0, fa1, fa2, fa3, rne
+            01e6:	mv	a0, a1
+            01e8:	mv	s0, sp
+            01ea:	neg	a0, a1
+            01ee:	negw	a0, a1
+            01f2:	not	a0, a1
+            01f6:	nop
+            01f8:	sh1add	a0, a1, a2
+            01fc:	sh2add	a0, a1, a2
+            0200:	sh3add	a0, a1, a2
+            0204:	add.uw	a0, a1, a2
+            0208:	sh1add.uw	a0, a1, a2
+            020c:	sh2add.uw	a0, a1, a2
+            0210:	sh3add.uw	a0, a1, a2
+            0214:	slli.uw	a0, a1, 33
+            0218:	andn	a0, a1, a2
+            021c:	orn	a0, a1, a2
+            0220:	xnor	a0, a1, a2
+            0224:	clz	a0, a1
+            0228:	clzw	a0, a1
+            022c:	ctz	a0, a1
+            0230:	ctzw	a0, a1
+            0234:	cpop	a0, a1
+            0238:	cpopw	a0, a1
+            023c:	max	a0, a1, a2
+            0240:	maxu	a0, a1, a2
+            0244:	min	a0, a1, a2
+            0248:	minu	a0, a1, a2
+            024c:	sext.b	a0, a1
+            0250:	sext.h	a0, a1
+            0254:	zext.h	a0, a1
+            0258:	rol	a0, a1, a2
+            025c:	rolw	a0, a1, a2
+            0260:	ror	a0, a1, a2
+            0264:	rori	a0, a1, 40
+            0268:	roriw	a0, a1, 7
+            026c:	rorw	a0, a1, a2
+            0270:	orc.b	a0, a1
+            0274:	rev8	a0, a1
+            0278:	ret
+        "#]]
+        .assert_eq(&listing);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let error = |bytes: &[u8]| match &decode_bytes(bytes, 0x100)[..] {
+            [.., (_, Err(err))] => err.to_string(),
+            res => panic!("decoded {res:?}"),
+        };
+        expect!["0x100: unknown encoding (0x00000000)"].assert_eq(&error(&[0, 0]));
+        expect!["0x100: unsupported instruction `fence` (0x0330000f)"]
+            .assert_eq(&error(&[0x0f, 0x00, 0x30, 0x03]));
+        expect!["0x100: unknown encoding (0x06b57553)"]
+            .assert_eq(&error(&[0x53, 0x75, 0xb5, 0x06]));
+        expect!["0x100: unknown encoding (0x02b55553)"]
+            .assert_eq(&error(&[0x53, 0x55, 0xb5, 0x02]));
+        expect!["0x100: truncated instruction (0x00008533)"].assert_eq(&error(&[0x33, 0x85]));
+    }
+}
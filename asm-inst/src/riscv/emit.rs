@@ -0,0 +1,711 @@
+//! GNU `as` text for RISC-V instructions.
+//!
+//! Labels print bare, and relocation immediates under their operator, as
+//! in `%hi(msg)`.
+
+use std::fmt::{self, Display, Formatter};
+
+use super::{
+    AmoOrdering, AmoWidth, FpType, Immediate, Instruction, IntWidth, Opcode, Operands, Precision,
+    Register, RelocOperator, RoundingMode,
+};
+
+/// How registers are spelled in emitted text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RegisterNames {
+    /// `a0`, `sp`, `fa0`
+    #[default]
+    Abi,
+    /// `x10`, `x2`, `f10`
+    Numeric,
+}
+
+impl Register {
+    pub fn name(&self, names: RegisterNames) -> String {
+        match (names, self) {
+            (RegisterNames::Numeric, Register::X(n)) => format!("x{n}"),
+            (RegisterNames::Numeric, Register::F(n)) => format!("f{n}"),
+            _ => self.abi_name().to_string(),
+        }
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self, Register::F(_))
+    }
+
+    /// The register's number, or 0 for `pc`.
+    fn number(&self) -> u8 {
+        match self {
+            Register::X(n) | Register::F(n) => *n,
+            Register::PC => 0,
+        }
+    }
+}
+
+impl Opcode {
+    /// The mnemonic `as` knows the opcode by. `rd` and `rs1` pick the
+    /// direction of `fmv` and `fcvt`.
+    pub fn mnemonic(&self, rd: Option<Register>, rs1: Option<Register>) -> String {
+        let to_float = rd.is_some_and(|r| r.is_float());
+        let from_float = rs1.is_some_and(|r| r.is_float());
+        let name = match self {
+            Opcode::Add => "add",
+            Opcode::Addi => "addi",
+            Opcode::Sub => "sub",
+            Opcode::Lui => "lui",
+            Opcode::Auipc => "auipc",
+            Opcode::Addw => "addw",
+            Opcode::Addiw => "addiw",
+            Opcode::Subw => "subw",
+            Opcode::Sll => "sll",
+            Opcode::Slli => "slli",
+            Opcode::Srl => "srl",
+            Opcode::Srli => "srli",
+            Opcode::Sra => "sra",
+            Opcode::Srai => "srai",
+            Opcode::Sllw => "sllw",
+            Opcode::Slliw => "slliw",
+            Opcode::Srlw => "srlw",
+            Opcode::Srliw => "srliw",
+            Opcode::Sraw => "sraw",
+            Opcode::Sraiw => "sraiw",
+            Opcode::Xor => "xor",
+            Opcode::Xori => "xori",
+            Opcode::And => "and",
+            Opcode::Andi => "andi",
+            Opcode::Or => "or",
+            Opcode::Ori => "ori",
+            Opcode::Slt => "slt",
+            Opcode::Slti => "slti",
+            Opcode::Sltu => "sltu",
+            Opcode::Sltiu => "sltiu",
+            Opcode::Beq => "beq",
+            Opcode::Bne => "bne",
+            Opcode::Blt => "blt",
+            Opcode::Bge => "bge",
+            Opcode::Bltu => "bltu",
+            Opcode::Bgeu => "bgeu",
+            Opcode::Beqz => "beqz",
+            Opcode::Bnez => "bnez",
+            Opcode::Blez => "blez",
+            Opcode::Bgez => "bgez",
+            Opcode::Bltz => "bltz",
+            Opcode::Bgtz => "bgtz",
+            Opcode::Bgt => "bgt",
+            Opcode::Ble => "ble",
+            Opcode::Bgtu => "bgtu",
+            Opcode::Bleu => "bleu",
+            Opcode::Jal => "jal",
+            Opcode::Jalr => "jalr",
+            Opcode::Ecall => "ecall",
+            Opcode::Ebreak => "ebreak",
+            Opcode::Fence => "fence",
+            Opcode::Fencei => "fence.i",
+            Opcode::Lb => "lb",
+            Opcode::Lh => "lh",
+            Opcode::Lbu => "lbu",
+            Opcode::Lhu => "lhu",
+            Opcode::Lw => "lw",
+            Opcode::Lwu => "lwu",
+            Opcode::Ld => "ld",
+            Opcode::Sb => "sb",
+            Opcode::Sh => "sh",
+            Opcode::Sw => "sw",
+            Opcode::Sd => "sd",
+            Opcode::Csrrw => "csrrw",
+            Opcode::Csrrs => "csrrs",
+            Opcode::Csrrc => "csrrc",
+            Opcode::Csrrwi => "csrrwi",
+            Opcode::Csrrsi => "csrrsi",
+            Opcode::Csrrci => "csrrci",
+            Opcode::Mul => "mul",
+            Opcode::Mulh => "mulh",
+            Opcode::Mulhsu => "mulhsu",
+            Opcode::Mulhu => "mulhu",
+            Opcode::Mulw => "mulw",
+            Opcode::Div => "div",
+            Opcode::Divu => "divu",
+            Opcode::Divw => "divw",
+            Opcode::Divuw => "divuw",
+            Opcode::Rem => "rem",
+            Opcode::Remu => "remu",
+            Opcode::Remw => "remw",
+            Opcode::Remuw => "remuw",
+            Opcode::Lr(..) => "lr",
+            Opcode::Sc(..) => "sc",
+            Opcode::Amoswap(..) => "amoswap",
+            Opcode::Amoadd(..) => "amoadd",
+            Opcode::Amoxor(..) => "amoxor",
+            Opcode::Amoand(..) => "amoand",
+            Opcode::Amoor(..) => "amoor",
+            Opcode::Amomin(..) => "amomin",
+            Opcode::Amomax(..) => "amomax",
+            Opcode::Amominu(..) => "amominu",
+            Opcode::Amomaxu(..) => "amomaxu",
+            Opcode::Flw => "flw",
+            Opcode::Fld => "fld",
+            Opcode::Fsw => "fsw",
+            Opcode::Fsd => "fsd",
+            Opcode::Fcvt(to, from, _) => return format!("fcvt.{to}.{from}"),
+            Opcode::Fmv(precision) => {
+                return match (to_float, from_float) {
+                    (true, false) => format!("fmv.{}.x", precision.bits_name()),
+                    (false, true) => format!("fmv.x.{}", precision.bits_name()),
+                    _ => format!("fmv.{precision}"),
+                };
+            }
+            Opcode::Fadd(..) => "fadd",
+            Opcode::Fsub(..) => "fsub",
+            Opcode::Fmul(..) => "fmul",
+            Opcode::Fdiv(..) => "fdiv",
+            Opcode::Fsqrt(..) => "fsqrt",
+            Opcode::Fmadd(..) => "fmadd",
+            Opcode::Fmsub(..) => "fmsub",
+            Opcode::Fnmadd(..) => "fnmadd",
+            Opcode::Fnmsub(..) => "fnmsub",
+            Opcode::Fsgnj(_) => "fsgnj",
+            Opcode::Fsgnjn(_) => "fsgnjn",
+            Opcode::Fsgnjx(_) => "fsgnjx",
+            Opcode::Fmin(_) => "fmin",
+            Opcode::Fmax(_) => "fmax",
+            Opcode::Feq(_) => "feq",
+            Opcode::Flt(_) => "flt",
+            Opcode::Fle(_) => "fle",
+            Opcode::Fclass(_) => "fclass",
+            Opcode::Sh1add => "sh1add",
+            Opcode::Sh2add => "sh2add",
+            Opcode::Sh3add => "sh3add",
+            Opcode::AddUw => "add.uw",
+            Opcode::Sh1addUw => "sh1add.uw",
+            Opcode::Sh2addUw => "sh2add.uw",
+            Opcode::Sh3addUw => "sh3add.uw",
+            Opcode::SlliUw => "slli.uw",
+            Opcode::Andn => "andn",
+            Opcode::Orn => "orn",
+            Opcode::Xnor => "xnor",
+            Opcode::Clz => "clz",
+            Opcode::Clzw => "clzw",
+            Opcode::Ctz => "ctz",
+            Opcode::Ctzw => "ctzw",
+            Opcode::Cpop => "cpop",
+            Opcode::Cpopw => "cpopw",
+            Opcode::Max => "max",
+            Opcode::Maxu => "maxu",
+            Opcode::Min => "min",
+            Opcode::Minu => "minu",
+            Opcode::SextB => "sext.b",
+            Opcode::SextH => "sext.h",
+            Opcode::ZextH => "zext.h",
+            Opcode::Rol => "rol",
+            Opcode::Rolw => "rolw",
+            Opcode::Ror => "ror",
+            Opcode::Rori => "rori",
+            Opcode::Roriw => "roriw",
+            Opcode::Rorw => "rorw",
+            Opcode::OrcB => "orc.b",
+            Opcode::Rev8 => "rev8",
+            Opcode::La => "la",
+            Opcode::Lla => "lla",
+            Opcode::Lga => "lga",
+            Opcode::Li => "li",
+            Opcode::Mv => "mv",
+            Opcode::Nop => "nop",
+            Opcode::Not => "not",
+            Opcode::Neg => "neg",
+            Opcode::Negw => "negw",
+            Opcode::J => "j",
+            Opcode::Ret => "ret",
+            Opcode::Call => "call",
+            Opcode::Tail => "tail",
+            Opcode::Pause => "pause",
+        };
+        match (self.precision(), self.atomic()) {
+            (Some(precision), _) => format!("{name}.{precision}"),
+            (_, Some((width, ordering))) => format!("{name}.{width}{ordering}"),
+            _ => name.to_string(),
+        }
+    }
+
+    /// Width and ordering of an atomic, which its mnemonic ends in.
+    fn atomic(&self) -> Option<(AmoWidth, AmoOrdering)> {
+        match self {
+            Opcode::Lr(width, ordering)
+            | Opcode::Sc(width, ordering)
+            | Opcode::Amoswap(width, ordering)
+            | Opcode::Amoadd(width, ordering)
+            | Opcode::Amoxor(width, ordering)
+            | Opcode::Amoand(width, ordering)
+            | Opcode::Amoor(width, ordering)
+            | Opcode::Amomin(width, ordering)
+            | Opcode::Amomax(width, ordering)
+            | Opcode::Amominu(width, ordering)
+            | Opcode::Amomaxu(width, ordering) => Some((*width, *ordering)),
+            _ => None,
+        }
+    }
+
+    /// Format of an FP operation, which its mnemonic ends in.
+    fn precision(&self) -> Option<Precision> {
+        match self {
+            Opcode::Fadd(p, _)
+            | Opcode::Fsub(p, _)
+            | Opcode::Fmul(p, _)
+            | Opcode::Fdiv(p, _)
+            | Opcode::Fsqrt(p, _)
+            | Opcode::Fmadd(p, _)
+            | Opcode::Fmsub(p, _)
+            | Opcode::Fnmadd(p, _)
+            | Opcode::Fnmsub(p, _)
+            | Opcode::Fsgnj(p)
+            | Opcode::Fsgnjn(p)
+            | Opcode::Fsgnjx(p)
+            | Opcode::Fmin(p)
+            | Opcode::Fmax(p)
+            | Opcode::Feq(p)
+            | Opcode::Flt(p)
+            | Opcode::Fle(p)
+            | Opcode::Fclass(p)
+            | Opcode::Fmv(p) => Some(*p),
+            _ => None,
+        }
+    }
+
+    /// Rounding mode written after the operands.
+    fn rounding_mode(&self) -> Option<RoundingMode> {
+        match self {
+            Opcode::Fcvt(_, _, rm)
+            | Opcode::Fadd(_, rm)
+            | Opcode::Fsub(_, rm)
+            | Opcode::Fmul(_, rm)
+            | Opcode::Fdiv(_, rm)
+            | Opcode::Fsqrt(_, rm)
+            | Opcode::Fmadd(_, rm)
+            | Opcode::Fmsub(_, rm)
+            | Opcode::Fnmadd(_, rm)
+            | Opcode::Fnmsub(_, rm) => *rm,
+            _ => None,
+        }
+    }
+
+    fn is_memory(&self) -> bool {
+        matches!(
+            self,
+            Opcode::Jalr
+                | Opcode::Lb
+                | Opcode::Lh
+                | Opcode::Lbu
+                | Opcode::Lhu
+                | Opcode::Lw
+                | Opcode::Lwu
+                | Opcode::Ld
+                | Opcode::Sb
+                | Opcode::Sh
+                | Opcode::Sw
+                | Opcode::Sd
+                | Opcode::Flw
+                | Opcode::Fld
+                | Opcode::Fsw
+                | Opcode::Fsd
+        )
+    }
+
+    fn is_csr(&self) -> bool {
+        matches!(
+            self,
+            Opcode::Csrrw
+                | Opcode::Csrrs
+                | Opcode::Csrrc
+                | Opcode::Csrrwi
+                | Opcode::Csrrsi
+                | Opcode::Csrrci
+        )
+    }
+
+    /// `csrrwi` and friends, which take a 5-bit constant where the others
+    /// take `rs1`.
+    fn is_csr_immediate(&self) -> bool {
+        matches!(self, Opcode::Csrrwi | Opcode::Csrrsi | Opcode::Csrrci)
+    }
+
+    /// Opcodes written with a destination and one source only.
+    fn is_unary(&self) -> bool {
+        matches!(
+            self,
+            Opcode::Mv
+                | Opcode::Not
+                | Opcode::Neg
+                | Opcode::Negw
+                | Opcode::Fsqrt(..)
+                | Opcode::Fcvt(..)
+                | Opcode::Fmv(_)
+                | Opcode::Fclass(_)
+                | Opcode::Clz
+                | Opcode::Clzw
+                | Opcode::Ctz
+                | Opcode::Ctzw
+                | Opcode::Cpop
+                | Opcode::Cpopw
+                | Opcode::SextB
+                | Opcode::SextH
+                | Opcode::ZextH
+                | Opcode::OrcB
+                | Opcode::Rev8
+        )
+    }
+
+    /// Opcodes written with a single register, the one to test or load.
+    fn takes_one_register(&self) -> bool {
+        matches!(
+            self,
+            Opcode::Beqz
+                | Opcode::Bnez
+                | Opcode::Blez
+                | Opcode::Bgez
+                | Opcode::Bltz
+                | Opcode::Bgtz
+                | Opcode::Lui
+                | Opcode::Auipc
+                | Opcode::La
+                | Opcode::Lla
+                | Opcode::Lga
+                | Opcode::Li
+        )
+    }
+}
+
+impl Precision {
+    /// The letter `fmv` gives the bits of a float in an integer register.
+    fn bits_name(&self) -> &'static str {
+        match self {
+            Precision::H => "h",
+            Precision::S => "w",
+            Precision::D => "d",
+        }
+    }
+}
+
+impl Display for Precision {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Precision::H => "h",
+            Precision::S => "s",
+            Precision::D => "d",
+        })
+    }
+}
+
+impl Display for IntWidth {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            IntWidth::W => "w",
+            IntWidth::Wu => "wu",
+            IntWidth::L => "l",
+            IntWidth::Lu => "lu",
+        })
+    }
+}
+
+impl Display for FpType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            FpType::Float(precision) => precision.fmt(f),
+            FpType::Int(width) => width.fmt(f),
+        }
+    }
+}
+
+impl Display for RoundingMode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RoundingMode::Rne => "rne",
+            RoundingMode::Rtz => "rtz",
+            RoundingMode::Rdn => "rdn",
+            RoundingMode::Rup => "rup",
+            RoundingMode::Rmm => "rmm",
+            RoundingMode::Dyn => "dyn",
+        })
+    }
+}
+
+impl Display for RelocOperator {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RelocOperator::Hi => "hi",
+            RelocOperator::Lo => "lo",
+            RelocOperator::PcrelHi => "pcrel_hi",
+            RelocOperator::PcrelLo => "pcrel_lo",
+        })
+    }
+}
+
+impl Display for AmoWidth {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            AmoWidth::W => "w",
+            AmoWidth::D => "d",
+        })
+    }
+}
+
+/// The suffix after the width, with its leading dot.
+impl Display for AmoOrdering {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            AmoOrdering::Relaxed => "",
+            AmoOrdering::Aq => ".aq",
+            AmoOrdering::Rl => ".rl",
+            AmoOrdering::AqRl => ".aqrl",
+        })
+    }
+}
+
+/// An [`Instruction`] with the register names to print it with.
+pub struct Emit<'a> {
+    inst: &'a Instruction,
+    names: RegisterNames,
+}
+
+impl Instruction {
+    /// The instruction as `as` reads it, with registers spelled as `names`
+    /// says. `Display` uses ABI names.
+    pub fn emit(&self, names: RegisterNames) -> Emit<'_> {
+        Emit { inst: self, names }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.emit(RegisterNames::Abi).fmt(f)
+    }
+}
+
+impl Display for Emit<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let op = &self.inst.opcode;
+        let reg = |r: &Register| r.name(self.names);
+        let imm = |imm: &Immediate| match imm {
+            Immediate::Number(n) => n.to_string(),
+            Immediate::Label(label) => label.clone(),
+            Immediate::Reloc {
+                operator,
+                symbol,
+                addend,
+            } => match addend {
+                0 => format!("%{operator}({symbol})"),
+                addend if *addend < 0 => format!("%{operator}({symbol}{addend})"),
+                addend => format!("%{operator}({symbol}+{addend})"),
+            },
+        };
+        let (rd, rs1) = match &self.inst.operands {
+            Operands::R { rd, rs1, .. } | Operands::I { rd, rs1, .. } => (Some(*rd), Some(*rs1)),
+            Operands::U { rd, .. } => (Some(*rd), None),
+            Operands::R4 { rd, rs1, .. } | Operands::Amo { rd, rs1, .. } => (Some(*rd), Some(*rs1)),
+            Operands::S { .. } | Operands::Nop | Operands::Label(_) => (None, None),
+        };
+        let mnemonic = op.mnemonic(rd, rs1);
+        let mut operands = match &self.inst.operands {
+            Operands::Amo { rd, rs1, .. } if matches!(op, Opcode::Lr(..)) => {
+                vec![reg(rd), format!("({})", reg(rs1))]
+            }
+            Operands::Amo { rd, rs2, rs1 } => vec![reg(rd), reg(rs2), format!("({})", reg(rs1))],
+            Operands::R { rd, rs1, .. } if op.is_unary() => vec![reg(rd), reg(rs1)],
+            Operands::R { rd, rs1, rs2 } => vec![reg(rd), reg(rs1), reg(rs2)],
+            Operands::R4 { rd, rs1, rs2, rs3 } => vec![reg(rd), reg(rs1), reg(rs2), reg(rs3)],
+            Operands::I { rd, rs1, imm: i } if op.is_memory() => {
+                vec![reg(rd), format!("{}({})", imm(i), reg(rs1))]
+            }
+            Operands::I { rd, rs1, .. } if op.is_unary() => vec![reg(rd), reg(rs1)],
+            // `lui` is built with a zero `rs1` it doesn't have
+            Operands::I { rd, imm: i, .. } if op.takes_one_register() => vec![reg(rd), imm(i)],
+            Operands::I { rd, rs1, imm: i } if op.is_csr() => {
+                let source = match op.is_csr_immediate() {
+                    true => rs1.number().to_string(),
+                    false => reg(rs1),
+                };
+                vec![reg(rd), imm(i), source]
+            }
+            Operands::I { rd, rs1, imm: i } => vec![reg(rd), reg(rs1), imm(i)],
+            Operands::S { rs1, rs2, imm: i } if op.is_memory() => {
+                vec![reg(rs2), format!("{}({})", imm(i), reg(rs1))]
+            }
+            Operands::S { rs1, imm: i, .. } if op.takes_one_register() => vec![reg(rs1), imm(i)],
+            Operands::S { rs1, rs2, imm: i } => vec![reg(rs1), reg(rs2), imm(i)],
+            Operands::U { imm: i, .. } if matches!(op, Opcode::J | Opcode::Call | Opcode::Tail) => {
+                vec![imm(i)]
+            }
+            Operands::U { rd, imm: i } => vec![reg(rd), imm(i)],
+            Operands::Nop => vec![],
+            Operands::Label(label) => vec![label.clone()],
+        };
+        operands.extend(op.rounding_mode().map(|rm| rm.to_string()));
+        f.write_str(&mnemonic)?;
+        if !operands.is_empty() {
+            write!(f, "\t{}", operands.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A sequence of instructions as the lines of an assembly file.
+pub fn emit(insts: &[Instruction], names: RegisterNames) -> String {
+    insts
+        .iter()
+        .map(|inst| format!("\t{}\n", inst.emit(names)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+    use AmoOrdering::{AqRl, Relaxed};
+    use FpType::{Float, Int};
+    use IntWidth::{Wu, L};
+    use Precision::{D, H, S};
+    use RelocOperator::{Hi, Lo, PcrelHi, PcrelLo};
+    use RoundingMode::{Dyn, Rne, Rtz};
+
+    #[test]
+    fn emit_forms() {
+        let (a0, a1, a2, sp) = (
+            Register::X(10),
+            Register::X(11),
+            Register::X(12),
+            Register::X(2),
+        );
+        let (fa0, fa1) = (Register::F(10), Register::F(11));
+        let label = |s: &str| Immediate::Label(s.to_string());
+        let reloc = |operator, s: &str| Immediate::Reloc {
+            operator,
+            symbol: s.to_string(),
+            addend: 0,
+        };
+        let insts = [
+            Instruction::new_r(Opcode::Add, a0, a1, a2),
+            Instruction::new_i(Opcode::Addi, a0, sp, Immediate::Number(-16)),
+            Instruction::new_i(Opcode::Lui, a0, Register::ZERO, reloc(Hi, "msg")),
+            Instruction::new_i(Opcode::Addi, a0, a0, reloc(Lo, "msg")),
+            Instruction::new_u(Opcode::Auipc, a1, reloc(PcrelHi, ".L1")),
+            Instruction::new_i(Opcode::Addi, a1, a1, reloc(PcrelLo, ".L1")),
+            Instruction::new_i(Opcode::Ld, a0, sp, Immediate::Number(8)),
+            Instruction::new_i(Opcode::Lw, a0, a1, reloc(Lo, "counter")),
+            Instruction::new_i(
+                Opcode::Addi,
+                a0,
+                a0,
+                Immediate::Reloc {
+                    operator: Lo,
+                    symbol: ".LC2".to_string(),
+                    addend: 8,
+                },
+            ),
+            Instruction::new_s(Opcode::Sd, sp, a1, Immediate::Number(-8)),
+            Instruction::new_s(Opcode::Beq, a0, a1, label(".L2")),
+            Instruction::new_s(Opcode::Bnez, a0, Register::ZERO, label(".L2")),
+            Instruction::new_u(Opcode::Jal, Register::ZERO, label("main")),
+            Instruction::new_i(Opcode::Jalr, Register::X(1), a0, Immediate::Number(0)),
+            Instruction::new_i(Opcode::Csrrs, a0, Register::ZERO, Immediate::Number(3)),
+            Instruction::new_i(Opcode::Csrrwi, a0, Register::X(5), Immediate::Number(1)),
+            Instruction::new_amo(Opcode::Lr(AmoWidth::D, Relaxed), a0, Register::ZERO, a1),
+            Instruction::new_amo(Opcode::Sc(AmoWidth::D, Relaxed), a0, a2, a1),
+            Instruction::new_amo(Opcode::Amoadd(AmoWidth::W, AqRl), a1, a1, a0),
+            Instruction::new_amo(Opcode::Amomin(AmoWidth::D, AqRl), a1, a1, a0),
+            Instruction::new_r(Opcode::Fadd(D, None), fa0, fa0, fa1),
+            Instruction::new_r(
+                Opcode::Fcvt(Float(D), Int(L), None),
+                fa0,
+                a0,
+                Register::ZERO,
+            ),
+            Instruction::new_r(
+                Opcode::Fcvt(Int(L), Float(D), None),
+                a0,
+                fa0,
+                Register::ZERO,
+            ),
+            Instruction::new_r(Opcode::Fmv(D), a0, fa1, Register::ZERO),
+            Instruction::new_r(Opcode::Fmv(S), fa0, a1, Register::ZERO),
+            Instruction::new_r(
+                Opcode::Fcvt(Int(Wu), Float(S), Some(Rtz)),
+                a0,
+                fa0,
+                Register::ZERO,
+            ),
+            Instruction::new_r(
+                Opcode::Fcvt(Float(S), Float(H), None),
+                fa0,
+                fa1,
+                Register::ZERO,
+            ),
+            Instruction::new_r4(Opcode::Fmadd(D, Some(Rne)), fa0, fa1, fa0, fa1),
+            Instruction::new_r4(Opcode::Fnmsub(S, None), fa0, fa1, fa0, fa1),
+            Instruction::new_r(Opcode::Fsqrt(H, Some(Dyn)), fa0, fa1, Register::ZERO),
+            Instruction::new_r(Opcode::Feq(D), a0, fa0, fa1),
+            Instruction::new_r(Opcode::Fclass(S), a0, fa1, Register::ZERO),
+            Instruction::new_r(Opcode::Fmax(H), fa0, fa0, fa1),
+            Instruction::new_r(Opcode::Mv, a0, a1, Register::ZERO),
+            Instruction::new_u(Opcode::La, a0, label("table")),
+            Instruction::new_u(Opcode::Li, a0, Immediate::Number(281474972516352)),
+            Instruction::new_label(Opcode::Call, "puts".to_string()),
+            Instruction::new_u(Opcode::J, Register::ZERO, label(".L3")),
+            Instruction {
+                opcode: Opcode::Fencei,
+                operands: Operands::Nop,
+            },
+            Instruction::new_ret(),
+        ];
+        expect![[r#"
+            add	a0, a1, a2
+            addi	a0, sp, -16
+            lui	a0, %hi(msg)
+            addi	a0, a0, %lo(msg)
+            auipc	a1, %pcrel_hi(.L1)
+            addi	a1, a1, %pcrel_lo(.L1)
+            ld	a0, 8(sp)
+            lw	a0, %lo(counter)(a1)
+            addi	a0, a0, %lo(.LC2+8)
+            sd	a1, -8(sp)
+            beq	a0, a1, .L2
+            bnez	a0, .L2
+            jal	zero, main
+            jalr	ra, 0(a0)
+            csrrs	a0, 3, zero
+            csrrwi	a0, 1, 5
+            lr.d	a0, (a1)
+            sc.d	a0, a2, (a1)
+            amoadd.w.aqrl	a1, a1, (a0)
+            amomin.d.aqrl	a1, a1, (a0)
+            fadd.d	fa0, fa0, fa1
+            fcvt.d.l	fa0, a0
+            fcvt.l.d	a0, fa0
+            fmv.x.d	a0, fa1
+            fmv.w.x	fa0, a1
+            fcvt.wu.s	a0, fa0, rtz
+            fcvt.s.h	fa0, fa1
+            fmadd.d	fa0, fa1, fa0, fa1, rne
+            fnmsub.s	fa0, fa1, fa0, fa1
+            fsqrt.h	fa0, fa1, dyn
+            feq.d	a0, fa0, fa1
+            fclass.s	a0, fa1
+            fmax.h	fa0, fa0, fa1
+            mv	a0, a1
+            la	a0, table
+            li	a0, 281474972516352
+            call	puts
+            j	.L3
+            fence.i
+            ret
+        "#]]
+        .assert_eq(&insts.iter().map(|i| format!("{i}\n")).collect::<String>());
+        expect![[r#"
+            	lw	x10, %lo(counter)(x11)
+            	amoadd.w.aqrl	x11, x11, (x10)
+            	fadd.d	f10, f10, f11
+        "#]]
+        .assert_eq(
+            &[7, 18, 20]
+                .map(|i| format!("{}\n", insts[i].emit(RegisterNames::Numeric)))
+                .concat(),
+        );
+    }
+}
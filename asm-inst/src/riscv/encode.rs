@@ -0,0 +1,1535 @@
+//! RV64GC machine code for RISC-V instructions.
+//!
+//! Pseudo-instructions expand to what `as` would give them: `call` to an
+//! `auipc`/`jalr` pair and `li` to the shortest sequence building the
+//! constant. `la` and `lla` are left out, as their `%pcrel_lo` needs a label
+//! on the `auipc` that [`Code`] has no way to define.
+//! Label and relocation immediates encode as zero and leave a [`Reloc`] for
+//! the linker to fill in. Instructions that need one are never compressed,
+//! as the compressed forms have relocation types of their own.
+
+use super::{
+    AmoOrdering, AmoWidth, FpType, Immediate, Instruction, IntWidth, Opcode, Operands, Precision,
+    Register, RelocOperator, RoundingMode,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("`{inst}`: {kind}")]
+pub struct EncodeError {
+    /// The instruction, as `as` would read it
+    pub inst: String,
+    pub kind: EncodeErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EncodeErrorKind {
+    /// An opcode with no encoding in this operand layout
+    #[error("no encoding for this operand layout")]
+    Unsupported,
+    #[error("immediate {0} out of range")]
+    ImmediateRange(i64),
+    #[error("register `{0}` can't be encoded here")]
+    Register(&'static str),
+    #[error("a label can't be encoded here")]
+    Label,
+    #[error("`%{0}` can't be encoded here")]
+    Reloc(RelocOperator),
+}
+
+/// ELF relocation types the encoder leaves for label immediates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocKind {
+    /// `%hi(sym)` of `lui`
+    Hi20,
+    /// `%lo(sym)` of an I-type instruction
+    Lo12I,
+    /// `%lo(sym)` of a store
+    Lo12S,
+    /// `%pcrel_hi(sym)` of `auipc`
+    PcrelHi20,
+    /// `%pcrel_lo(label)` of an I-type instruction
+    PcrelLo12I,
+    /// `%pcrel_lo(label)` of a store
+    PcrelLo12S,
+    /// Target of a conditional branch
+    Branch,
+    /// Target of `jal`
+    Jal,
+    /// Target of an `auipc`/`jalr` pair, as `call` and `tail` expand to
+    Call,
+}
+
+impl RelocKind {
+    /// The `R_RISCV_*` number.
+    pub fn elf_type(&self) -> u32 {
+        match self {
+            RelocKind::Branch => 16,
+            RelocKind::Jal => 17,
+            RelocKind::Call => 18,
+            RelocKind::PcrelHi20 => 23,
+            RelocKind::PcrelLo12I => 24,
+            RelocKind::PcrelLo12S => 25,
+            RelocKind::Hi20 => 26,
+            RelocKind::Lo12I => 27,
+            RelocKind::Lo12S => 28,
+        }
+    }
+}
+
+/// A field of the code the linker fills in with the address of `symbol`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reloc {
+    /// Byte offset of the instruction the field is in
+    pub offset: usize,
+    pub kind: RelocKind,
+    pub symbol: String,
+    /// Added to the symbol's address, as the `r_addend` of a RELA entry
+    pub addend: i64,
+}
+
+/// Encoded instructions, in little-endian byte order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Code {
+    pub bytes: Vec<u8>,
+    pub relocs: Vec<Reloc>,
+}
+
+impl Code {
+    fn push_word(&mut self, word: u32, compressed: bool) {
+        match compressed.then(|| compress(word)).flatten() {
+            Some(half) => self.bytes.extend(half.to_le_bytes()),
+            None => self.bytes.extend(word.to_le_bytes()),
+        }
+    }
+}
+
+/// Encodes instructions one after the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encoder {
+    /// Whether to use 16-bit forms where they exist
+    pub compressed: bool,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Encoder { compressed: true }
+    }
+}
+
+impl Encoder {
+    pub fn encode(&self, inst: &Instruction) -> Result<Code, EncodeError> {
+        let mut code = Code::default();
+        self.encode_into(&mut code, inst)?;
+        Ok(code)
+    }
+
+    /// Encodes `insts` in order, with relocation offsets from the start of
+    /// the first.
+    pub fn assemble(&self, insts: &[Instruction]) -> Result<Code, EncodeError> {
+        let mut code = Code::default();
+        for inst in insts {
+            self.encode_into(&mut code, inst)?;
+        }
+        Ok(code)
+    }
+
+    fn encode_into(&self, code: &mut Code, inst: &Instruction) -> Result<(), EncodeError> {
+        let error = |kind| EncodeError {
+            inst: inst.to_string(),
+            kind,
+        };
+        let words = words(inst).map_err(error)?;
+        // a `CALL` relocation covers the `jalr` after its `auipc` as well
+        let mut pair = false;
+        for (word, reloc) in words {
+            let offset = code.bytes.len();
+            match reloc {
+                Some((kind, symbol, addend)) => {
+                    code.relocs.push(Reloc {
+                        offset,
+                        kind,
+                        symbol: symbol.to_string(),
+                        addend,
+                    });
+                    code.push_word(word, false);
+                    pair = kind == RelocKind::Call;
+                }
+                None => {
+                    code.push_word(word, self.compressed && !pair);
+                    pair = false;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The operands of an instruction by role, whatever layout they came in.
+struct Fields<'a> {
+    rd: Option<Register>,
+    rs1: Option<Register>,
+    rs2: Option<Register>,
+    rs3: Option<Register>,
+    imm: Option<Imm<'a>>,
+}
+
+#[derive(Clone, Copy)]
+enum Imm<'a> {
+    Value(i64),
+    Label(&'a str),
+    Reloc(RelocOperator, &'a str, i64),
+}
+
+/// A relocation left for a field: its kind, symbol and addend.
+type Target<'a> = (RelocKind, &'a str, i64);
+
+/// What an immediate field holds until the linker fills it in.
+type Field<'a> = (i64, Option<Target<'a>>);
+
+/// Relocations of the lower 12 bits of an I-type or S-type immediate.
+const LO_I: &[(RelocOperator, RelocKind)] = &[
+    (RelocOperator::Lo, RelocKind::Lo12I),
+    (RelocOperator::PcrelLo, RelocKind::PcrelLo12I),
+];
+const LO_S: &[(RelocOperator, RelocKind)] = &[
+    (RelocOperator::Lo, RelocKind::Lo12S),
+    (RelocOperator::PcrelLo, RelocKind::PcrelLo12S),
+];
+
+impl<'a> Fields<'a> {
+    fn of(operands: &'a Operands) -> Self {
+        let imm = |imm: &'a Immediate| match imm {
+            Immediate::Number(n) => Imm::Value(*n),
+            Immediate::Label(label) => Imm::Label(label),
+            Immediate::Reloc {
+                operator,
+                symbol,
+                addend,
+            } => Imm::Reloc(*operator, symbol, *addend),
+        };
+        let rs3 = match operands {
+            Operands::R4 { rs3, .. } => Some(*rs3),
+            _ => None,
+        };
+        let (rd, rs1, rs2, imm) = match operands {
+            Operands::R { rd, rs1, rs2 } => (Some(*rd), Some(*rs1), Some(*rs2), None),
+            Operands::I { rd, rs1, imm: i } => (Some(*rd), Some(*rs1), None, Some(imm(i))),
+            Operands::S { rs1, rs2, imm: i } => (None, Some(*rs1), Some(*rs2), Some(imm(i))),
+            Operands::U { rd, imm: i } => (Some(*rd), None, None, Some(imm(i))),
+            Operands::Label(label) => (None, None, None, Some(Imm::Label(label))),
+            Operands::R4 { rd, rs1, rs2, .. } | Operands::Amo { rd, rs1, rs2 } => {
+                (Some(*rd), Some(*rs1), Some(*rs2), None)
+            }
+            Operands::Nop => (None, None, None, None),
+        };
+        Fields {
+            rd,
+            rs1,
+            rs2,
+            rs3,
+            imm,
+        }
+    }
+
+    fn rd(&self) -> Result<u32, EncodeErrorKind> {
+        x(self.rd.ok_or(EncodeErrorKind::Unsupported)?)
+    }
+
+    fn rs1(&self) -> Result<u32, EncodeErrorKind> {
+        x(self.rs1.ok_or(EncodeErrorKind::Unsupported)?)
+    }
+
+    fn rs2(&self) -> Result<u32, EncodeErrorKind> {
+        x(self.rs2.ok_or(EncodeErrorKind::Unsupported)?)
+    }
+
+    /// The single register of `beqz`, `lui` and friends, in whichever field
+    /// the layout has it.
+    fn only(&self) -> Result<u32, EncodeErrorKind> {
+        x(self.rd.or(self.rs1).ok_or(EncodeErrorKind::Unsupported)?)
+    }
+
+    fn imm(&self) -> Result<Imm<'a>, EncodeErrorKind> {
+        self.imm.ok_or(EncodeErrorKind::Unsupported)
+    }
+
+    /// A constant immediate, for the instructions that can't take a label.
+    fn value(&self) -> Result<i64, EncodeErrorKind> {
+        match self.imm()? {
+            Imm::Value(n) => Ok(n),
+            Imm::Label(_) => Err(EncodeErrorKind::Label),
+            Imm::Reloc(operator, ..) => Err(EncodeErrorKind::Reloc(operator)),
+        }
+    }
+
+    /// A constant, or a relocation with one of the operators in `kinds`.
+    fn reloc(&self, kinds: &[(RelocOperator, RelocKind)]) -> Result<Field<'a>, EncodeErrorKind> {
+        match self.imm()? {
+            Imm::Reloc(operator, symbol, addend) => kinds
+                .iter()
+                .find(|(op, _)| *op == operator)
+                .map(|(_, kind)| (0, Some((*kind, symbol, addend))))
+                .ok_or(EncodeErrorKind::Reloc(operator)),
+            _ => Ok((self.value()?, None)),
+        }
+    }
+
+    /// A constant offset, or a label for a `kind` relocation to reach.
+    fn target(&self, kind: RelocKind) -> Result<Field<'a>, EncodeErrorKind> {
+        match self.imm()? {
+            Imm::Label(label) => Ok((0, Some((kind, label, 0)))),
+            _ => Ok((self.value()?, None)),
+        }
+    }
+}
+
+/// Number of an integer register.
+fn x(reg: Register) -> Result<u32, EncodeErrorKind> {
+    match reg {
+        Register::X(n) => Ok(u32::from(n)),
+        reg => Err(EncodeErrorKind::Register(reg.abi_name())),
+    }
+}
+
+/// Number of a floating-point register.
+fn f(reg: Option<Register>) -> Result<u32, EncodeErrorKind> {
+    match reg.ok_or(EncodeErrorKind::Unsupported)? {
+        Register::F(n) => Ok(u32::from(n)),
+        reg => Err(EncodeErrorKind::Register(reg.abi_name())),
+    }
+}
+
+type Word<'a> = (u32, Option<Target<'a>>);
+
+pub(super) const OP: u32 = 0x33;
+pub(super) const OP_32: u32 = 0x3b;
+pub(super) const OP_IMM: u32 = 0x13;
+pub(super) const OP_IMM_32: u32 = 0x1b;
+pub(super) const LOAD: u32 = 0x03;
+pub(super) const STORE: u32 = 0x23;
+pub(super) const LOAD_FP: u32 = 0x07;
+pub(super) const STORE_FP: u32 = 0x27;
+pub(super) const BRANCH: u32 = 0x63;
+pub(super) const LUI: u32 = 0x37;
+pub(super) const AUIPC: u32 = 0x17;
+pub(super) const JAL: u32 = 0x6f;
+pub(super) const JALR: u32 = 0x67;
+pub(super) const SYSTEM: u32 = 0x73;
+pub(super) const MISC_MEM: u32 = 0x0f;
+pub(super) const AMO: u32 = 0x2f;
+pub(super) const OP_FP: u32 = 0x53;
+
+/// Rounding mode field of FP instructions that round, taken from `frm`.
+const DYN: u32 = 0b111;
+pub(super) const MADD: u32 = 0x43;
+pub(super) const MSUB: u32 = 0x47;
+pub(super) const NMSUB: u32 = 0x4b;
+pub(super) const NMADD: u32 = 0x4f;
+const RA: u32 = 1;
+const T1: u32 = 6;
+
+/// The `aq` and `rl` bits at the bottom of an atomic's `funct7`.
+pub(super) fn ordering(ordering: AmoOrdering) -> u32 {
+    match ordering {
+        AmoOrdering::Relaxed => 0b00,
+        AmoOrdering::Rl => 0b01,
+        AmoOrdering::Aq => 0b10,
+        AmoOrdering::AqRl => 0b11,
+    }
+}
+
+/// The `fmt` field of FP operations, and `rs2` of `fcvt` between formats.
+pub(super) fn format(precision: Precision) -> u32 {
+    match precision {
+        Precision::S => 0b00,
+        Precision::D => 0b01,
+        Precision::H => 0b10,
+    }
+}
+
+/// The `rs2` field of `fcvt` to or from an integer.
+pub(super) fn width(width: IntWidth) -> u32 {
+    match width {
+        IntWidth::W => 0,
+        IntWidth::Wu => 1,
+        IntWidth::L => 2,
+        IntWidth::Lu => 3,
+    }
+}
+
+/// Whether every value converts exactly, which makes `rne` the default
+/// rounding mode rather than `dyn`: widening, or a 32-bit integer to double.
+pub(super) fn is_exact(to: FpType, from: FpType) -> bool {
+    match (to, from) {
+        (FpType::Float(to), FpType::Float(from)) => to > from,
+        (FpType::Float(Precision::D), FpType::Int(IntWidth::W | IntWidth::Wu)) => true,
+        _ => false,
+    }
+}
+
+/// The `rm` field for `rm`, with the default for exact or rounding operations.
+fn rounding(rm: &Option<RoundingMode>, exact: bool) -> u32 {
+    match rm {
+        None if exact => 0b000,
+        None => DYN,
+        Some(RoundingMode::Rne) => 0b000,
+        Some(RoundingMode::Rtz) => 0b001,
+        Some(RoundingMode::Rdn) => 0b010,
+        Some(RoundingMode::Rup) => 0b011,
+        Some(RoundingMode::Rmm) => 0b100,
+        Some(RoundingMode::Dyn) => DYN,
+    }
+}
+
+pub(super) fn r_type(opcode: u32, funct3: u32, funct7: u32, rd: u32, rs1: u32, rs2: u32) -> u32 {
+    funct7 << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | opcode
+}
+
+fn check(value: i64, bits: u32, align: u32) -> Result<u32, EncodeErrorKind> {
+    let half = 1i64 << (bits - 1);
+    match (-half..half).contains(&value) && value % (1 << align) == 0 {
+        true => Ok(value as u32 & ((1 << bits) - 1)),
+        false => Err(EncodeErrorKind::ImmediateRange(value)),
+    }
+}
+
+pub(super) fn i_type(
+    opcode: u32,
+    funct3: u32,
+    rd: u32,
+    rs1: u32,
+    imm: i64,
+) -> Result<u32, EncodeErrorKind> {
+    Ok(check(imm, 12, 0)? << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | opcode)
+}
+
+pub(super) fn s_type(
+    opcode: u32,
+    funct3: u32,
+    rs1: u32,
+    rs2: u32,
+    imm: i64,
+) -> Result<u32, EncodeErrorKind> {
+    let imm = check(imm, 12, 0)?;
+    Ok((imm >> 5) << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | (imm & 0x1f) << 7 | opcode)
+}
+
+pub(super) fn b_type(funct3: u32, rs1: u32, rs2: u32, offset: i64) -> Result<u32, EncodeErrorKind> {
+    let imm = check(offset, 13, 1)?;
+    let bit = |i: u32| (imm >> i) & 1;
+    let hi = bit(12) << 6 | (imm >> 5) & 0x3f;
+    let lo = ((imm >> 1) & 0xf) << 1 | bit(11);
+    Ok(hi << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | lo << 7 | BRANCH)
+}
+
+/// `imm` is the unsigned 20-bit field, as `as` reads it.
+pub(super) fn u_type(opcode: u32, rd: u32, imm: i64) -> Result<u32, EncodeErrorKind> {
+    match (0..1 << 20).contains(&imm) {
+        true => Ok((imm as u32) << 12 | rd << 7 | opcode),
+        false => Err(EncodeErrorKind::ImmediateRange(imm)),
+    }
+}
+
+pub(super) fn j_type(rd: u32, offset: i64) -> Result<u32, EncodeErrorKind> {
+    let imm = check(offset, 21, 1)?;
+    let field =
+        (imm >> 20) << 19 | ((imm >> 1) & 0x3ff) << 9 | ((imm >> 11) & 1) << 8 | (imm >> 12) & 0xff;
+    Ok(field << 12 | rd << 7 | JAL)
+}
+
+fn words(inst: &Instruction) -> Result<Vec<Word<'_>>, EncodeErrorKind> {
+    use Opcode::*;
+    let fields = Fields::of(&inst.operands);
+    let op = &inst.opcode;
+
+    let r = |opcode, funct3, funct7| -> Result<u32, EncodeErrorKind> {
+        Ok(r_type(
+            opcode,
+            funct3,
+            funct7,
+            fields.rd()?,
+            fields.rs1()?,
+            fields.rs2()?,
+        ))
+    };
+    let imm = |opcode, funct3| -> Result<Word, EncodeErrorKind> {
+        let (imm, reloc) = fields.reloc(LO_I)?;
+        Ok((
+            i_type(opcode, funct3, fields.rd()?, fields.rs1()?, imm)?,
+            reloc,
+        ))
+    };
+    let shift = |opcode: u32, funct3: u32, high: u32, bits: u32| -> Result<u32, EncodeErrorKind> {
+        let shamt = fields.value()?;
+        if !(0..1 << bits).contains(&shamt) {
+            return Err(EncodeErrorKind::ImmediateRange(shamt));
+        }
+        Ok(high << 26
+            | (shamt as u32) << 20
+            | fields.rs1()? << 15
+            | funct3 << 12
+            | fields.rd()? << 7
+            | opcode)
+    };
+    let store = |funct3| -> Result<Word, EncodeErrorKind> {
+        let (imm, reloc) = fields.reloc(LO_S)?;
+        Ok((
+            s_type(STORE, funct3, fields.rs1()?, fields.rs2()?, imm)?,
+            reloc,
+        ))
+    };
+    let load_fp = |funct3| -> Result<Word, EncodeErrorKind> {
+        let (imm, reloc) = fields.reloc(LO_I)?;
+        Ok((
+            i_type(LOAD_FP, funct3, f(fields.rd)?, fields.rs1()?, imm)?,
+            reloc,
+        ))
+    };
+    let store_fp = |funct3| -> Result<Word, EncodeErrorKind> {
+        let (imm, reloc) = fields.reloc(LO_S)?;
+        Ok((
+            s_type(STORE_FP, funct3, fields.rs1()?, f(fields.rs2)?, imm)?,
+            reloc,
+        ))
+    };
+    // Zbb's one-source operations, told apart by what would be the immediate
+    let unary = |opcode: u32, funct3: u32, imm: u32| -> Result<u32, EncodeErrorKind> {
+        Ok(imm << 20 | fields.rs1()? << 15 | funct3 << 12 | fields.rd()? << 7 | opcode)
+    };
+    let branch = |funct3, rs1: u32, rs2: u32| -> Result<Word, EncodeErrorKind> {
+        let (offset, reloc) = fields.target(RelocKind::Branch)?;
+        Ok((b_type(funct3, rs1, rs2, offset)?, reloc))
+    };
+    let jump = |rd| -> Result<Word, EncodeErrorKind> {
+        let (offset, reloc) = fields.target(RelocKind::Jal)?;
+        Ok((j_type(rd, offset)?, reloc))
+    };
+    let upper = |opcode, operator, kind| -> Result<Word, EncodeErrorKind> {
+        let (imm, reloc) = fields.reloc(&[(operator, kind)])?;
+        Ok((u_type(opcode, fields.only()?, imm)?, reloc))
+    };
+    let call = |rd: u32, via: u32| -> Result<Vec<Word>, EncodeErrorKind> {
+        let Imm::Label(target) = fields.imm()? else {
+            return Err(EncodeErrorKind::Unsupported);
+        };
+        Ok(vec![
+            (u_type(AUIPC, via, 0)?, Some((RelocKind::Call, target, 0))),
+            (i_type(JALR, 0, rd, via, 0)?, None),
+        ])
+    };
+    let amo =
+        |funct5: u32, width: &AmoWidth, order: &AmoOrdering| -> Result<u32, EncodeErrorKind> {
+            let Operands::Amo { rd, rs2, rs1 } = inst.operands else {
+                return Err(EncodeErrorKind::Unsupported);
+            };
+            let rs2 = match op {
+                Lr(..) => 0,
+                _ => x(rs2)?,
+            };
+            let funct3 = match width {
+                AmoWidth::W => 0b010,
+                AmoWidth::D => 0b011,
+            };
+            Ok(r_type(
+                AMO,
+                funct3,
+                funct5 << 2 | ordering(*order),
+                x(rd)?,
+                x(rs1)?,
+                rs2,
+            ))
+        };
+    let fp = |funct5: u32,
+              p: Precision,
+              funct3: u32,
+              rs2: Option<u32>|
+     -> Result<u32, EncodeErrorKind> {
+        let rs2 = match rs2 {
+            Some(rs2) => rs2,
+            None => f(fields.rs2)?,
+        };
+        Ok(r_type(
+            OP_FP,
+            funct3,
+            funct5 << 2 | format(p),
+            f(fields.rd)?,
+            f(fields.rs1)?,
+            rs2,
+        ))
+    };
+    let compare = |funct5: u32, p: Precision, funct3: u32| -> Result<u32, EncodeErrorKind> {
+        let (rs1, rs2) = (f(fields.rs1)?, f(fields.rs2)?);
+        Ok(r_type(
+            OP_FP,
+            funct3,
+            funct5 << 2 | format(p),
+            fields.rd()?,
+            rs1,
+            rs2,
+        ))
+    };
+    let fma =
+        |opcode: u32, p: Precision, rm: &Option<RoundingMode>| -> Result<u32, EncodeErrorKind> {
+            let funct7 = f(fields.rs3)? << 2 | format(p);
+            let (rd, rs1, rs2) = (f(fields.rd)?, f(fields.rs1)?, f(fields.rs2)?);
+            Ok(r_type(opcode, rounding(rm, false), funct7, rd, rs1, rs2))
+        };
+    let to_float = matches!(fields.rd, Some(Register::F(_)));
+    let from_float = matches!(fields.rs1, Some(Register::F(_)));
+    let plain = |word: Result<u32, EncodeErrorKind>| word.map(|word| vec![(word, None)]);
+
+    match op {
+        Add => plain(r(OP, 0, 0)),
+        Sub => plain(r(OP, 0, 0x20)),
+        Sll => plain(r(OP, 1, 0)),
+        Slt => plain(r(OP, 2, 0)),
+        Sltu => plain(r(OP, 3, 0)),
+        Xor => plain(r(OP, 4, 0)),
+        Srl => plain(r(OP, 5, 0)),
+        Sra => plain(r(OP, 5, 0x20)),
+        Or => plain(r(OP, 6, 0)),
+        And => plain(r(OP, 7, 0)),
+        Addw => plain(r(OP_32, 0, 0)),
+        Subw => plain(r(OP_32, 0, 0x20)),
+        Sllw => plain(r(OP_32, 1, 0)),
+        Srlw => plain(r(OP_32, 5, 0)),
+        Sraw => plain(r(OP_32, 5, 0x20)),
+        Mul => plain(r(OP, 0, 1)),
+        Mulh => plain(r(OP, 1, 1)),
+        Mulhsu => plain(r(OP, 2, 1)),
+        Mulhu => plain(r(OP, 3, 1)),
+        Div => plain(r(OP, 4, 1)),
+        Divu => plain(r(OP, 5, 1)),
+        Rem => plain(r(OP, 6, 1)),
+        Remu => plain(r(OP, 7, 1)),
+        Mulw => plain(r(OP_32, 0, 1)),
+        Divw => plain(r(OP_32, 4, 1)),
+        Divuw => plain(r(OP_32, 5, 1)),
+        Remw => plain(r(OP_32, 6, 1)),
+        Remuw => plain(r(OP_32, 7, 1)),
+
+        Sh1add => plain(r(OP, 2, 0x10)),
+        Sh2add => plain(r(OP, 4, 0x10)),
+        Sh3add => plain(r(OP, 6, 0x10)),
+        AddUw => plain(r(OP_32, 0, 0x04)),
+        Sh1addUw => plain(r(OP_32, 2, 0x10)),
+        Sh2addUw => plain(r(OP_32, 4, 0x10)),
+        Sh3addUw => plain(r(OP_32, 6, 0x10)),
+        Andn => plain(r(OP, 7, 0x20)),
+        Orn => plain(r(OP, 6, 0x20)),
+        Xnor => plain(r(OP, 4, 0x20)),
+        Max => plain(r(OP, 6, 0x05)),
+        Maxu => plain(r(OP, 7, 0x05)),
+        Min => plain(r(OP, 4, 0x05)),
+        Minu => plain(r(OP, 5, 0x05)),
+        Rol => plain(r(OP, 1, 0x30)),
+        Ror => plain(r(OP, 5, 0x30)),
+        Rolw => plain(r(OP_32, 1, 0x30)),
+        Rorw => plain(r(OP_32, 5, 0x30)),
+        Clz => plain(unary(OP_IMM, 1, 0x600)),
+        Ctz => plain(unary(OP_IMM, 1, 0x601)),
+        Cpop => plain(unary(OP_IMM, 1, 0x602)),
+        SextB => plain(unary(OP_IMM, 1, 0x604)),
+        SextH => plain(unary(OP_IMM, 1, 0x605)),
+        Clzw => plain(unary(OP_IMM_32, 1, 0x600)),
+        Ctzw => plain(unary(OP_IMM_32, 1, 0x601)),
+        Cpopw => plain(unary(OP_IMM_32, 1, 0x602)),
+        ZextH => plain(unary(OP_32, 4, 0x080)),
+        OrcB => plain(unary(OP_IMM, 5, 0x287)),
+        Rev8 => plain(unary(OP_IMM, 5, 0x6b8)),
+
+        Addi => Ok(vec![imm(OP_IMM, 0)?]),
+        Slti => plain(i_type(
+            OP_IMM,
+            2,
+            fields.rd()?,
+            fields.rs1()?,
+            fields.value()?,
+        )),
+        Sltiu => plain(i_type(
+            OP_IMM,
+            3,
+            fields.rd()?,
+            fields.rs1()?,
+            fields.value()?,
+        )),
+        Xori => plain(i_type(
+            OP_IMM,
+            4,
+            fields.rd()?,
+            fields.rs1()?,
+            fields.value()?,
+        )),
+        Ori => plain(i_type(
+            OP_IMM,
+            6,
+            fields.rd()?,
+            fields.rs1()?,
+            fields.value()?,
+        )),
+        Andi => plain(i_type(
+            OP_IMM,
+            7,
+            fields.rd()?,
+            fields.rs1()?,
+            fields.value()?,
+        )),
+        Addiw => Ok(vec![imm(OP_IMM_32, 0)?]),
+        Slli => plain(shift(OP_IMM, 1, 0, 6)),
+        Srli => plain(shift(OP_IMM, 5, 0, 6)),
+        Srai => plain(shift(OP_IMM, 5, 0b010000, 6)),
+        Slliw => plain(shift(OP_IMM_32, 1, 0, 5)),
+        Srliw => plain(shift(OP_IMM_32, 5, 0, 5)),
+        Sraiw => plain(shift(OP_IMM_32, 5, 0b010000, 5)),
+        Rori => plain(shift(OP_IMM, 5, 0b011000, 6)),
+        Roriw => plain(shift(OP_IMM_32, 5, 0b011000, 5)),
+        SlliUw => plain(shift(OP_IMM_32, 1, 0b000010, 6)),
+
+        Lui => Ok(vec![upper(LUI, RelocOperator::Hi, RelocKind::Hi20)?]),
+        Auipc => Ok(vec![upper(
+            AUIPC,
+            RelocOperator::PcrelHi,
+            RelocKind::PcrelHi20,
+        )?]),
+
+        Lb => Ok(vec![imm(LOAD, 0)?]),
+        Lh => Ok(vec![imm(LOAD, 1)?]),
+        Lw => Ok(vec![imm(LOAD, 2)?]),
+        Ld => Ok(vec![imm(LOAD, 3)?]),
+        Lbu => Ok(vec![imm(LOAD, 4)?]),
+        Lhu => Ok(vec![imm(LOAD, 5)?]),
+        Lwu => Ok(vec![imm(LOAD, 6)?]),
+        Sb => Ok(vec![store(0)?]),
+        Sh => Ok(vec![store(1)?]),
+        Sw => Ok(vec![store(2)?]),
+        Sd => Ok(vec![store(3)?]),
+
+        Beq => Ok(vec![branch(0, fields.rs1()?, fields.rs2()?)?]),
+        Bne => Ok(vec![branch(1, fields.rs1()?, fields.rs2()?)?]),
+        Blt => Ok(vec![branch(4, fields.rs1()?, fields.rs2()?)?]),
+        Bge => Ok(vec![branch(5, fields.rs1()?, fields.rs2()?)?]),
+        Bltu => Ok(vec![branch(6, fields.rs1()?, fields.rs2()?)?]),
+        Bgeu => Ok(vec![branch(7, fields.rs1()?, fields.rs2()?)?]),
+        // the swapped forms compare the other way around
+        Bgt => Ok(vec![branch(4, fields.rs2()?, fields.rs1()?)?]),
+        Ble => Ok(vec![branch(5, fields.rs2()?, fields.rs1()?)?]),
+        Bgtu => Ok(vec![branch(6, fields.rs2()?, fields.rs1()?)?]),
+        Bleu => Ok(vec![branch(7, fields.rs2()?, fields.rs1()?)?]),
+        Beqz => Ok(vec![branch(0, fields.only()?, 0)?]),
+        Bnez => Ok(vec![branch(1, fields.only()?, 0)?]),
+        Bltz => Ok(vec![branch(4, fields.only()?, 0)?]),
+        Bgez => Ok(vec![branch(5, fields.only()?, 0)?]),
+        Blez => Ok(vec![branch(5, 0, fields.only()?)?]),
+        Bgtz => Ok(vec![branch(4, 0, fields.only()?)?]),
+
+        Jal => Ok(vec![jump(fields.rd()?)?]),
+        J => Ok(vec![jump(0)?]),
+        Jalr => Ok(vec![imm(JALR, 0)?]),
+        Ret => plain(i_type(JALR, 0, 0, RA, 0)),
+        Call => call(RA, RA),
+        Tail => call(0, T1),
+
+        Ecall => plain(Ok(SYSTEM)),
+        Ebreak => plain(Ok(1 << 20 | SYSTEM)),
+        Fence => plain(Ok(0x0ff0_0000 | MISC_MEM)),
+        Fencei => plain(Ok(1 << 12 | MISC_MEM)),
+        Pause => plain(Ok(0x0100_0000 | MISC_MEM)),
+        Nop => plain(i_type(OP_IMM, 0, 0, 0, 0)),
+
+        Csrrw | Csrrs | Csrrc | Csrrwi | Csrrsi | Csrrci => {
+            let funct3 = match op {
+                Csrrw => 1,
+                Csrrs => 2,
+                Csrrc => 3,
+                Csrrwi => 5,
+                Csrrsi => 6,
+                _ => 7,
+            };
+            let csr = fields.value()?;
+            if !(0..1 << 12).contains(&csr) {
+                return Err(EncodeErrorKind::ImmediateRange(csr));
+            }
+            // the immediate forms keep their 5-bit constant in `rs1`
+            plain(Ok((csr as u32) << 20
+                | fields.rs1()? << 15
+                | funct3 << 12
+                | fields.rd()? << 7
+                | SYSTEM))
+        }
+
+        Lr(w, o) => plain(amo(0b00010, w, o)),
+        Sc(w, o) => plain(amo(0b00011, w, o)),
+        Amoswap(w, o) => plain(amo(0b00001, w, o)),
+        Amoadd(w, o) => plain(amo(0b00000, w, o)),
+        Amoxor(w, o) => plain(amo(0b00100, w, o)),
+        Amoand(w, o) => plain(amo(0b01100, w, o)),
+        Amoor(w, o) => plain(amo(0b01000, w, o)),
+        Amomin(w, o) => plain(amo(0b10000, w, o)),
+        Amomax(w, o) => plain(amo(0b10100, w, o)),
+        Amominu(w, o) => plain(amo(0b11000, w, o)),
+        Amomaxu(w, o) => plain(amo(0b11100, w, o)),
+
+        Fadd(p, rm) => plain(fp(0b00000, *p, rounding(rm, false), None)),
+        Fsub(p, rm) => plain(fp(0b00001, *p, rounding(rm, false), None)),
+        Fmul(p, rm) => plain(fp(0b00010, *p, rounding(rm, false), None)),
+        Fdiv(p, rm) => plain(fp(0b00011, *p, rounding(rm, false), None)),
+        Fsqrt(p, rm) => plain(fp(0b01011, *p, rounding(rm, false), Some(0))),
+        Fsgnj(p) => plain(fp(0b00100, *p, 0, None)),
+        Fsgnjn(p) => plain(fp(0b00100, *p, 1, None)),
+        Fsgnjx(p) => plain(fp(0b00100, *p, 2, None)),
+        Fmin(p) => plain(fp(0b00101, *p, 0, None)),
+        Fmax(p) => plain(fp(0b00101, *p, 1, None)),
+        Feq(p) => plain(compare(0b10100, *p, 2)),
+        Flt(p) => plain(compare(0b10100, *p, 1)),
+        Fle(p) => plain(compare(0b10100, *p, 0)),
+        Fclass(p) => plain(Ok(r_type(
+            OP_FP,
+            1,
+            0b11100 << 2 | format(*p),
+            fields.rd()?,
+            f(fields.rs1)?,
+            0,
+        ))),
+        Fmadd(p, rm) => plain(fma(MADD, *p, rm)),
+        Fmsub(p, rm) => plain(fma(MSUB, *p, rm)),
+        Fnmsub(p, rm) => plain(fma(NMSUB, *p, rm)),
+        Fnmadd(p, rm) => plain(fma(NMADD, *p, rm)),
+        Flw => Ok(vec![load_fp(2)?]),
+        Fld => Ok(vec![load_fp(3)?]),
+        Fsw => Ok(vec![store_fp(2)?]),
+        Fsd => Ok(vec![store_fp(3)?]),
+        Fcvt(to, from, rm) => {
+            let rm = rounding(rm, is_exact(*to, *from));
+            match (*to, *from) {
+                (FpType::Float(to), FpType::Float(from)) if to != from => {
+                    plain(fp(0b01000, to, rm, Some(format(from))))
+                }
+                (FpType::Int(to), FpType::Float(from)) => plain(Ok(r_type(
+                    OP_FP,
+                    rm,
+                    0b11000 << 2 | format(from),
+                    fields.rd()?,
+                    f(fields.rs1)?,
+                    width(to),
+                ))),
+                (FpType::Float(to), FpType::Int(from)) => plain(Ok(r_type(
+                    OP_FP,
+                    rm,
+                    0b11010 << 2 | format(to),
+                    f(fields.rd)?,
+                    fields.rs1()?,
+                    width(from),
+                ))),
+                _ => Err(EncodeErrorKind::Unsupported),
+            }
+        }
+        // the direction is in the register classes: `fmv.d.x`, `fmv.x.d` or
+        // `fmv.d`
+        Fmv(p) => match (to_float, from_float) {
+            (true, false) => plain(Ok(r_type(
+                OP_FP,
+                0,
+                0b11110 << 2 | format(*p),
+                f(fields.rd)?,
+                fields.rs1()?,
+                0,
+            ))),
+            (false, true) => plain(Ok(r_type(
+                OP_FP,
+                0,
+                0b11100 << 2 | format(*p),
+                fields.rd()?,
+                f(fields.rs1)?,
+                0,
+            ))),
+            _ => plain(fp(0b00100, *p, 0, Some(f(fields.rs1)?))),
+        },
+
+        Mv => plain(i_type(OP_IMM, 0, fields.rd()?, fields.rs1()?, 0)),
+        Not => plain(i_type(OP_IMM, 4, fields.rd()?, fields.rs1()?, -1)),
+        Neg => plain(Ok(r_type(OP, 0, 0x20, fields.rd()?, 0, fields.rs1()?))),
+        Negw => plain(Ok(r_type(OP_32, 0, 0x20, fields.rd()?, 0, fields.rs1()?))),
+        Li => {
+            let words = li(fields.only()?, fields.value()?)?;
+            Ok(words.into_iter().map(|word| (word, None)).collect())
+        }
+        // `auipc` and a `%pcrel_lo` of a label on it, or through the GOT for
+        // `lga`, which needs relocations of its own
+        La | Lla | Lga => Err(EncodeErrorKind::Unsupported),
+    }
+}
+
+/// The words of `li rd, value`, built the way LLVM does: `lui` and `addiw`
+/// for a 32-bit value, otherwise the upper bits, shifted into place, and an
+/// `addi` of the lower 12.
+fn li(rd: u32, value: i64) -> Result<Vec<u32>, EncodeErrorKind> {
+    let lo = value << 52 >> 52;
+    if i32::try_from(value).is_ok() {
+        let hi = (value + 0x800) >> 12 & 0xfffff;
+        let mut words = vec![];
+        if hi != 0 {
+            words.push(u_type(LUI, rd, hi)?);
+        }
+        match hi {
+            0 => words.push(i_type(OP_IMM, 0, rd, 0, lo)?),
+            _ if lo != 0 => words.push(i_type(OP_IMM_32, 0, rd, rd, lo)?),
+            _ => {}
+        }
+        return Ok(words);
+    }
+    let hi = (value as u64).wrapping_add(0x800) >> 12;
+    let shamt = 12 + hi.trailing_zeros();
+    let hi = ((hi >> (shamt - 12)) << shamt) as i64 >> shamt;
+    let mut words = li(rd, hi)?;
+    words.push(shamt << 20 | rd << 15 | 1 << 12 | rd << 7 | OP_IMM);
+    if lo != 0 {
+        words.push(i_type(OP_IMM, 0, rd, rd, lo)?);
+    }
+    Ok(words)
+}
+
+/// The 16-bit form of an RV64 instruction word, if it has one.
+pub fn compress(word: u32) -> Option<u16> {
+    let opcode = word & 0x7f;
+    let rd = (word >> 7) & 0x1f;
+    let funct3 = (word >> 12) & 0x7;
+    let rs1 = (word >> 15) & 0x1f;
+    let rs2 = (word >> 20) & 0x1f;
+    let funct7 = word >> 25;
+    let imm_i = i64::from((word as i32) >> 20);
+    let imm_s = i64::from(((word as i32) >> 25) << 5 | ((word >> 7) & 0x1f) as i32);
+    // registers x8-x15, which the 3-bit fields of the compact forms name
+    let short = |r: u32| (8..16).contains(&r).then(|| r - 8);
+    let small = |imm: i64| (-32..32).contains(&imm);
+    let bits6 = |imm: i64| (imm as u32 & 0x20) << 7 | (imm as u32 & 0x1f) << 2;
+    let ci = |funct3: u32, rd: u32, imm: i64, op: u32| funct3 << 13 | rd << 7 | bits6(imm) | op;
+
+    let half = match (opcode, funct3, funct7) {
+        (OP_IMM, 0, _) => match (rd, rs1, imm_i) {
+            (0, 0, 0) => 0x0001,
+            (2, 2, imm) if imm != 0 && imm % 16 == 0 && (-512..512).contains(&imm) => {
+                let imm = imm as u32;
+                let bit = |i: u32| (imm >> i) & 1;
+                0b011 << 13
+                    | bit(9) << 12
+                    | 2 << 7
+                    | bit(4) << 6
+                    | bit(6) << 5
+                    | (imm >> 7 & 3) << 3
+                    | bit(5) << 2
+                    | 0b01
+            }
+            (rd, 2, imm) if short(rd).is_some() && imm > 0 && imm < 1024 && imm % 4 == 0 => {
+                let imm = imm as u32;
+                (imm >> 4 & 3) << 11
+                    | (imm >> 6 & 0xf) << 7
+                    | (imm >> 2 & 1) << 6
+                    | (imm >> 3 & 1) << 5
+                    | short(rd)? << 2
+            }
+            (rd, 0, imm) if rd != 0 && small(imm) => ci(0b010, rd, imm, 0b01),
+            (rd, rs1, imm) if rd != 0 && rd == rs1 && imm != 0 && small(imm) => {
+                ci(0b000, rd, imm, 0b01)
+            }
+            (rd, rs1, 0) if rd != 0 && rs1 != 0 => 0b1000 << 12 | rd << 7 | rs1 << 2 | 0b10,
+            _ => return None,
+        },
+        (OP_IMM_32, 0, _) if rd != 0 && rd == rs1 && small(imm_i) => ci(0b001, rd, imm_i, 0b01),
+        (LUI, _, _) => {
+            let imm = i64::from((word as i32) >> 12);
+            match rd != 0 && rd != 2 && imm != 0 && small(imm) {
+                true => ci(0b011, rd, imm, 0b01),
+                false => return None,
+            }
+        }
+        (OP_IMM, 1, _) if rd != 0 && rd == rs1 && imm_i & 0x3f != 0 && imm_i >> 6 == 0 => {
+            ci(0b000, rd, imm_i, 0b10)
+        }
+        (OP_IMM, 5, _) if rd == rs1 && short(rd).is_some() => {
+            let shamt = imm_i & 0x3f;
+            let kind = match imm_i >> 6 {
+                0 => 0b00,
+                0b010000 => 0b01,
+                _ => return None,
+            };
+            if shamt == 0 {
+                return None;
+            }
+            0b100 << 13
+                | (shamt as u32 & 0x20) << 7
+                | kind << 10
+                | short(rd)? << 7
+                | (shamt as u32 & 0x1f) << 2
+                | 0b01
+        }
+        (OP_IMM, 7, _) if rd == rs1 && small(imm_i) => {
+            0b100 << 13 | 0b10 << 10 | short(rd)? << 7 | bits6(imm_i) | 0b01
+        }
+        (OP, 0, 0) => match (rd, rs1, rs2) {
+            (rd, 0, rs2) if rd != 0 && rs2 != 0 => 0b1000 << 12 | rd << 7 | rs2 << 2 | 0b10,
+            (rd, rs1, 0) if rd != 0 && rs1 != 0 => 0b1000 << 12 | rd << 7 | rs1 << 2 | 0b10,
+            (rd, rs1, rs2) if rd != 0 && rs2 != 0 && rd == rs1 => {
+                0b1001 << 12 | rd << 7 | rs2 << 2 | 0b10
+            }
+            (rd, rs1, rs2) if rd != 0 && rs1 != 0 && rd == rs2 => {
+                0b1001 << 12 | rd << 7 | rs1 << 2 | 0b10
+            }
+            _ => return None,
+        },
+        (OP, 0, 0x20) | (OP, 4, 0) | (OP, 6, 0) | (OP, 7, 0) | (OP_32, 0, 0) | (OP_32, 0, 0x20) => {
+            let (word_op, kind) = match (opcode, funct3, funct7) {
+                (OP, 0, _) => (0, 0b00),
+                (OP, 4, _) => (0, 0b01),
+                (OP, 6, _) => (0, 0b10),
+                (OP, 7, _) => (0, 0b11),
+                (_, _, 0x20) => (1, 0b00),
+                _ => (1, 0b01),
+            };
+            // `sub` and `subw` don't commute
+            let commutes = !(funct3 == 0 && funct7 == 0x20);
+            let other = match (rd == rs1, rd == rs2 && commutes) {
+                (true, _) => rs2,
+                (false, true) => rs1,
+                _ => return None,
+            };
+            0b100011 << 10 | word_op << 12 | short(rd)? << 7 | kind << 5 | short(other)? << 2 | 0b01
+        }
+        (JALR, 0, _) if rs1 != 0 && imm_i == 0 => match rd {
+            0 => 0b1000 << 12 | rs1 << 7 | 0b10,
+            1 => 0b1001 << 12 | rs1 << 7 | 0b10,
+            _ => return None,
+        },
+        (SYSTEM, 0, _) if word == 1 << 20 | SYSTEM => 0x9002,
+        (LOAD, 2 | 3, _) | (LOAD_FP, 3, _) => {
+            let scale = if funct3 == 2 { 4 } else { 8 };
+            // `c.fld` and `c.fldsp` share the layout of `c.ld` and `c.ldsp`
+            let top = if opcode == LOAD { funct3 } else { 0b001 };
+            let offset = imm_i;
+            if offset < 0 || offset % scale != 0 {
+                return None;
+            }
+            let offset = offset as u32;
+            match (rs1, short(rs1), short(rd)) {
+                (2, _, _) if (rd != 0 || opcode == LOAD_FP) && offset < 64 * scale as u32 => {
+                    match funct3 {
+                        2 => {
+                            0b010 << 13
+                                | (offset >> 5 & 1) << 12
+                                | rd << 7
+                                | (offset >> 2 & 7) << 4
+                                | (offset >> 6 & 3) << 2
+                                | 0b10
+                        }
+                        _ => {
+                            top << 13
+                                | (offset >> 5 & 1) << 12
+                                | rd << 7
+                                | (offset >> 3 & 3) << 5
+                                | (offset >> 6 & 7) << 2
+                                | 0b10
+                        }
+                    }
+                }
+                (_, Some(base), Some(rd)) if offset < 32 * scale as u32 => match funct3 {
+                    2 => {
+                        0b010 << 13
+                            | (offset >> 3 & 7) << 10
+                            | base << 7
+                            | (offset >> 2 & 1) << 6
+                            | (offset >> 6 & 1) << 5
+                            | rd << 2
+                    }
+                    _ => {
+                        top << 13
+                            | (offset >> 3 & 7) << 10
+                            | base << 7
+                            | (offset >> 6 & 3) << 5
+                            | rd << 2
+                    }
+                },
+                _ => return None,
+            }
+        }
+        (STORE, 2 | 3, _) | (STORE_FP, 3, _) => {
+            let scale = if funct3 == 2 { 4 } else { 8 };
+            let top = if opcode == STORE {
+                0b100 | funct3
+            } else {
+                0b101
+            };
+            let offset = imm_s;
+            if offset < 0 || offset % scale != 0 {
+                return None;
+            }
+            let offset = offset as u32;
+            match (rs1, short(rs1), short(rs2)) {
+                (2, _, _) if offset < 64 * scale as u32 => match funct3 {
+                    2 => {
+                        0b110 << 13
+                            | (offset >> 2 & 0xf) << 9
+                            | (offset >> 6 & 3) << 7
+                            | rs2 << 2
+                            | 0b10
+                    }
+                    _ => {
+                        top << 13
+                            | (offset >> 3 & 7) << 10
+                            | (offset >> 6 & 7) << 7
+                            | rs2 << 2
+                            | 0b10
+                    }
+                },
+                (_, Some(base), Some(src)) if offset < 32 * scale as u32 => match funct3 {
+                    2 => {
+                        0b110 << 13
+                            | (offset >> 3 & 7) << 10
+                            | base << 7
+                            | (offset >> 2 & 1) << 6
+                            | (offset >> 6 & 1) << 5
+                            | src << 2
+                    }
+                    _ => {
+                        top << 13
+                            | (offset >> 3 & 7) << 10
+                            | base << 7
+                            | (offset >> 6 & 3) << 5
+                            | src << 2
+                    }
+                },
+                _ => return None,
+            }
+        }
+        (BRANCH, 0 | 1, _) if rs2 == 0 => {
+            let offset = b_offset(word);
+            if !(-256..256).contains(&offset) {
+                return None;
+            }
+            let imm = offset as u32;
+            let bit = |i: u32| (imm >> i) & 1;
+            (0b110 | funct3) << 13
+                | bit(8) << 12
+                | (imm >> 3 & 3) << 10
+                | short(rs1)? << 7
+                | (imm >> 6 & 3) << 5
+                | (imm >> 1 & 3) << 3
+                | bit(5) << 2
+                | 0b01
+        }
+        (JAL, _, _) if rd == 0 => {
+            let offset = j_offset(word);
+            if !(-2048..2048).contains(&offset) {
+                return None;
+            }
+            0b101 << 13 | cj_offset(offset as u32) << 2 | 0b01
+        }
+        _ => return None,
+    };
+    Some(half as u16)
+}
+
+/// Branch offset of a B-type word.
+fn b_offset(word: u32) -> i64 {
+    let imm = (word >> 31) << 12
+        | ((word >> 7) & 1) << 11
+        | ((word >> 25) & 0x3f) << 5
+        | ((word >> 8) & 0xf) << 1;
+    i64::from((imm << 19) as i32 >> 19)
+}
+
+/// Jump offset of a J-type word.
+fn j_offset(word: u32) -> i64 {
+    let imm = (word >> 31) << 20
+        | ((word >> 12) & 0xff) << 12
+        | ((word >> 20) & 1) << 11
+        | ((word >> 21) & 0x3ff) << 1;
+    i64::from((imm << 11) as i32 >> 11)
+}
+
+/// The scrambled 11-bit offset field of `c.j`.
+fn cj_offset(imm: u32) -> u32 {
+    let bit = |i: u32| (imm >> i) & 1;
+    bit(11) << 10
+        | bit(4) << 9
+        | (imm >> 8 & 3) << 7
+        | bit(10) << 6
+        | bit(6) << 5
+        | bit(7) << 4
+        | (imm >> 1 & 7) << 1
+        | bit(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+    use RelocOperator::{Hi, Lo, PcrelHi, PcrelLo};
+
+    /// Each instruction's text next to its encoding, halfwords or words as
+    /// `llvm-objdump` prints them.
+    fn listing(encoder: Encoder, insts: &[Instruction]) -> String {
+        let mut out = String::new();
+        for inst in insts {
+            let code = encoder.encode(inst).unwrap();
+            let mut bytes = &code.bytes[..];
+            let mut hex = Vec::new();
+            while !bytes.is_empty() {
+                let len = if bytes[0] & 0b11 == 0b11 { 4 } else { 2 };
+                let (word, rest) = bytes.split_at(len);
+                hex.push(
+                    word.iter()
+                        .rev()
+                        .map(|b| format!("{b:02x}"))
+                        .collect::<String>(),
+                );
+                bytes = rest;
+            }
+            out += &format!("{:<17} {inst}\n", hex.join(" "));
+            for reloc in &code.relocs {
+                out += &format!("  +{} {:?} {}", reloc.offset, reloc.kind, reloc.symbol);
+                out += &match reloc.addend {
+                    0 => "\n".to_string(),
+                    addend => format!(" {addend:+}\n"),
+                };
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn encode_forms() {
+        use AmoOrdering::{Aq, AqRl, Relaxed, Rl};
+        use FpType::{Float, Int};
+        use IntWidth::{L, W};
+        use Precision::{D, H, S};
+        use RoundingMode::{Rne, Rtz};
+        let (ra, sp, s0, a0, a1, a2, t0) = (
+            Register::X(1),
+            Register::X(2),
+            Register::X(8),
+            Register::X(10),
+            Register::X(11),
+            Register::X(12),
+            Register::X(5),
+        );
+        let (fa0, fa1, fa2, fa3) = (
+            Register::F(10),
+            Register::F(11),
+            Register::F(12),
+            Register::F(13),
+        );
+        let n = Immediate::Number;
+        let label = |s: &str| Immediate::Label(s.to_string());
+        let reloc = |operator, s: &str| Immediate::Reloc {
+            operator,
+            symbol: s.to_string(),
+            addend: 0,
+        };
+        let insts = [
+            Instruction::new_r(Opcode::Add, a0, a1, a2),
+            Instruction::new_r(Opcode::Add, a0, a0, a2),
+            Instruction::new_r(Opcode::Sub, s0, s0, a1),
+            Instruction::new_r(Opcode::Addw, a0, a0, a1),
+            Instruction::new_r(Opcode::Mul, a0, a1, a2),
+            Instruction::new_r(Opcode::Remu, a0, a1, a2),
+            Instruction::new_i(Opcode::Addi, a0, a0, n(1)),
+            Instruction::new_i(Opcode::Addi, a0, Register::ZERO, n(-7)),
+            Instruction::new_i(Opcode::Addi, a0, a1, n(2047)),
+            Instruction::new_i(Opcode::Addi, sp, sp, n(-64)),
+            Instruction::new_i(Opcode::Addi, s0, sp, n(16)),
+            Instruction::new_i(Opcode::Addiw, a0, a0, n(-1)),
+            Instruction::new_i(Opcode::Andi, a0, a0, n(15)),
+            Instruction::new_i(Opcode::Xori, t0, a1, n(-1)),
+            Instruction::new_i(Opcode::Slli, a0, a0, n(32)),
+            Instruction::new_i(Opcode::Srai, a0, a0, n(63)),
+            Instruction::new_i(Opcode::Srliw, t0, a1, n(3)),
+            Instruction::new_i(Opcode::Lui, a0, Register::ZERO, n(31)),
+            Instruction::new_i(Opcode::Lui, a0, Register::ZERO, n(0x1234)),
+            Instruction::new_i(Opcode::Lui, a0, Register::ZERO, n(0xfffff)),
+            Instruction::new_i(Opcode::Ld, a0, sp, n(8)),
+            Instruction::new_i(Opcode::Lw, a0, a1, n(4)),
+            Instruction::new_i(Opcode::Lbu, a0, a1, n(-1)),
+            Instruction::new_s(Opcode::Sd, sp, ra, n(24)),
+            Instruction::new_s(Opcode::Sw, a1, a0, n(8)),
+            Instruction::new_s(Opcode::Sh, a1, a0, n(-2)),
+            Instruction::new_s(Opcode::Beq, a0, a1, n(-8)),
+            Instruction::new_s(Opcode::Bnez, a0, Register::ZERO, n(12)),
+            Instruction::new_s(Opcode::Bgt, a0, a1, n(16)),
+            Instruction::new_s(Opcode::Blez, a0, Register::ZERO, n(-4)),
+            Instruction::new_u(Opcode::J, Register::ZERO, n(-2048)),
+            Instruction::new_u(Opcode::Jal, ra, n(4096)),
+            Instruction::new_i(Opcode::Jalr, ra, a0, n(0)),
+            Instruction::new_i(Opcode::Csrrs, a0, Register::ZERO, n(3)),
+            Instruction::new_i(Opcode::Csrrwi, a0, t0, n(1)),
+            Instruction::new_amo(Opcode::Lr(AmoWidth::D, Relaxed), a0, Register::ZERO, a1),
+            Instruction::new_amo(Opcode::Sc(AmoWidth::D, Relaxed), a0, a2, a1),
+            Instruction::new_amo(Opcode::Amoadd(AmoWidth::D, Relaxed), a0, a2, a1),
+            Instruction::new_amo(Opcode::Amomaxu(AmoWidth::D, Relaxed), a0, a2, a1),
+            Instruction::new_amo(Opcode::Amoadd(AmoWidth::W, AqRl), a1, a1, a0),
+            Instruction::new_amo(Opcode::Amomin(AmoWidth::D, AqRl), a1, a1, a0),
+            Instruction::new_amo(Opcode::Lr(AmoWidth::W, Aq), a0, Register::ZERO, a1),
+            Instruction::new_amo(Opcode::Sc(AmoWidth::W, Rl), a0, a2, a1),
+            Instruction::new_r(Opcode::Fadd(D, None), fa0, fa0, fa1),
+            Instruction::new_r(Opcode::Fadd(H, None), fa0, fa0, fa1),
+            Instruction::new_r(Opcode::Fsqrt(D, None), fa0, fa1, Register::ZERO),
+            Instruction::new_r(Opcode::Fsgnjn(D), fa0, fa0, fa1),
+            Instruction::new_r(Opcode::Fmv(S), fa0, fa1, Register::ZERO),
+            Instruction::new_r(Opcode::Feq(S), a0, fa0, fa1),
+            Instruction::new_r(Opcode::Fclass(D), a0, fa1, Register::ZERO),
+            Instruction::new_r(
+                Opcode::Fcvt(Float(D), Int(L), None),
+                fa0,
+                a0,
+                Register::ZERO,
+            ),
+            Instruction::new_r(
+                Opcode::Fcvt(Int(L), Float(D), None),
+                a0,
+                fa0,
+                Register::ZERO,
+            ),
+            Instruction::new_r(
+                Opcode::Fcvt(Float(D), Int(W), None),
+                fa0,
+                a0,
+                Register::ZERO,
+            ),
+            Instruction::new_r(
+                Opcode::Fcvt(Int(W), Float(D), Some(Rtz)),
+                a0,
+                fa0,
+                Register::ZERO,
+            ),
+            Instruction::new_r(
+                Opcode::Fcvt(Float(S), Float(D), None),
+                fa0,
+                fa1,
+                Register::ZERO,
+            ),
+            Instruction::new_r(
+                Opcode::Fcvt(Float(S), Float(H), None),
+                fa0,
+                fa1,
+                Register::ZERO,
+            ),
+            Instruction::new_r4(Opcode::Fmadd(D, None), fa0, fa1, fa2, fa3),
+            Instruction::new_r4(Opcode::Fmadd(S, Some(Rne)), fa0, fa1, fa2, fa3),
+            Instruction::new_r(Opcode::Fmv(D), a0, fa1, Register::ZERO),
+            Instruction::new_r(Opcode::Fmv(D), fa0, a1, Register::ZERO),
+            Instruction::new_r(Opcode::Mv, a0, a1, Register::ZERO),
+            Instruction::new_r(Opcode::Neg, a0, a1, Register::ZERO),
+            Instruction::new_r(Opcode::Not, a0, a1, Register::ZERO),
+            Instruction::new_nop(),
+            Instruction::new_ret(),
+            Instruction {
+                opcode: Opcode::Ebreak,
+                operands: Operands::Nop,
+            },
+            Instruction {
+                opcode: Opcode::Fence,
+                operands: Operands::Nop,
+            },
+        ];
+        expect![[r#"
+            00c58533          add	a0, a1, a2
+            9532              add	a0, a0, a2
+            8c0d              sub	s0, s0, a1
+            9d2d              addw	a0, a0, a1
+            02c58533          mul	a0, a1, a2
+            02c5f533          remu	a0, a1, a2
+            0505              addi	a0, a0, 1
+            5565              addi	a0, zero, -7
+            7ff58513          addi	a0, a1, 2047
+            7139              addi	sp, sp, -64
+            0800              addi	s0, sp, 16
+            357d              addiw	a0, a0, -1
+            893d              andi	a0, a0, 15
+            fff5c293          xori	t0, a1, -1
+            1502              slli	a0, a0, 32
+            957d              srai	a0, a0, 63
+            0035d29b          srliw	t0, a1, 3
+            657d              lui	a0, 31
+            01234537          lui	a0, 4660
+            757d              lui	a0, 1048575
+            6522              ld	a0, 8(sp)
+            41c8              lw	a0, 4(a1)
+            fff5c503          lbu	a0, -1(a1)
+            ec06              sd	ra, 24(sp)
+            c588              sw	a0, 8(a1)
+            fea59f23          sh	a0, -2(a1)
+            feb50ce3          beq	a0, a1, -8
+            e511              bnez	a0, 12
+            00a5c863          bgt	a0, a1, 16
+            fea05ee3          blez	a0, -4
+            b001              j	-2048
+            000010ef          jal	ra, 4096
+            9502              jalr	ra, 0(a0)
+            00302573          csrrs	a0, 3, zero
+            0012d573          csrrwi	a0, 1, 5
+            1005b52f          lr.d	a0, (a1)
+            18c5b52f          sc.d	a0, a2, (a1)
+            00c5b52f          amoadd.d	a0, a2, (a1)
+            e0c5b52f          amomaxu.d	a0, a2, (a1)
+            06b525af          amoadd.w.aqrl	a1, a1, (a0)
+            86b535af          amomin.d.aqrl	a1, a1, (a0)
+            1405a52f          lr.w.aq	a0, (a1)
+            1ac5a52f          sc.w.rl	a0, a2, (a1)
+            02b57553          fadd.d	fa0, fa0, fa1
+            04b57553          fadd.h	fa0, fa0, fa1
+            5a05f553          fsqrt.d	fa0, fa1
+            22b51553          fsgnjn.d	fa0, fa0, fa1
+            20b58553          fmv.s	fa0, fa1
+            a0b52553          feq.s	a0, fa0, fa1
+            e2059553          fclass.d	a0, fa1
+            d2257553          fcvt.d.l	fa0, a0
+            c2257553          fcvt.l.d	a0, fa0
+            d2050553          fcvt.d.w	fa0, a0
+            c2051553          fcvt.w.d	a0, fa0, rtz
+            4015f553          fcvt.s.d	fa0, fa1
+            40258553          fcvt.s.h	fa0, fa1
+            6ac5f543          fmadd.d	fa0, fa1, fa2, fa3
+            68c58543          fmadd.s	fa0, fa1, fa2, fa3, rne
+            e2058553          fmv.x.d	a0, fa1
+            f2058553          fmv.d.x	fa0, a1
+            852e              mv	a0, a1
+            40b00533          neg	a0, a1
+            fff5c513          not	a0, a1
+            0001              nop
+            8082              ret
+            9002              ebreak
+            0ff0000f          fence
+        "#]]
+        .assert_eq(&listing(Encoder::default(), &insts));
+
+        let relocated = [
+            Instruction::new_i(Opcode::Lui, a0, Register::ZERO, reloc(Hi, "msg")),
+            Instruction::new_i(Opcode::Addi, a0, a0, reloc(Lo, "msg")),
+            Instruction::new_s(Opcode::Sd, a0, a1, reloc(Lo, "msg")),
+            Instruction::new_i(
+                Opcode::Ld,
+                a0,
+                a0,
+                Immediate::Reloc {
+                    operator: Lo,
+                    symbol: ".LC2".to_string(),
+                    addend: -8,
+                },
+            ),
+            Instruction::new_u(Opcode::Auipc, a1, reloc(PcrelHi, ".L1")),
+            Instruction::new_i(Opcode::Ld, a0, a1, reloc(PcrelLo, ".L1")),
+            Instruction::new_s(Opcode::Sw, a1, a0, reloc(PcrelLo, ".L1")),
+            Instruction::new_s(Opcode::Beqz, a0, Register::ZERO, label(".L2")),
+            Instruction::new_u(Opcode::J, Register::ZERO, label(".L3")),
+            Instruction::new_label(Opcode::Call, "puts".to_string()),
+            Instruction::new_label(Opcode::Tail, "exit".to_string()),
+        ];
+        expect![[r#"
+            00000537          lui	a0, %hi(msg)
+              +0 Hi20 msg
+            00050513          addi	a0, a0, %lo(msg)
+              +0 Lo12I msg
+            00b53023          sd	a1, %lo(msg)(a0)
+              +0 Lo12S msg
+            00053503          ld	a0, %lo(.LC2-8)(a0)
+              +0 Lo12I .LC2 -8
+            00000597          auipc	a1, %pcrel_hi(.L1)
+              +0 PcrelHi20 .L1
+            0005b503          ld	a0, %pcrel_lo(.L1)(a1)
+              +0 PcrelLo12I .L1
+            00a5a023          sw	a0, %pcrel_lo(.L1)(a1)
+              +0 PcrelLo12S .L1
+            00050063          beqz	a0, .L2
+              +0 Branch .L2
+            0000006f          j	.L3
+              +0 Jal .L3
+            00000097 000080e7 call	puts
+              +0 Call puts
+            00000317 00030067 tail	exit
+              +0 Call exit
+        "#]]
+        .assert_eq(&listing(Encoder::default(), &relocated));
+
+        let code = Encoder::default().assemble(&relocated[7..11]).unwrap();
+        let offsets: Vec<_> = code.relocs.iter().map(|r| r.offset).collect();
+        assert_eq!(offsets, [0, 4, 8, 16]);
+        assert_eq!(code.bytes.len(), 24);
+
+        let constants = [
+            281474972516352,
+            2047,
+            -2048,
+            4096,
+            0x7fff_ffff,
+            0x8000_0000,
+            -0x8000_0001,
+            0x1234_5678_9abc_def0,
+        ];
+        let li = constants.map(|value| Instruction::new_u(Opcode::Li, a0, n(value)));
+        expect![[r#"
+            04000537 357d 055a li	a0, 281474972516352
+            7ff00513          li	a0, 2047
+            80000513          li	a0, -2048
+            6505              li	a0, 4096
+            80000537 357d     li	a0, 2147483647
+            4505 057e         li	a0, 2147483648
+            557d 057e 157d    li	a0, -2147483649
+            00247537 8ad5051b 053a c4d50513 0532 5e750513 0536 ef050513 li	a0, 1311768467463790320
+        "#]]
+        .assert_eq(&listing(Encoder::default(), &li));
+    }
+
+    #[test]
+    fn encode_errors() {
+        let a0 = Register::X(10);
+        let error = |inst: Instruction| Encoder::default().encode(&inst).unwrap_err().to_string();
+        expect!["`addi	a0, a0, 4096`: immediate 4096 out of range"].assert_eq(&error(
+            Instruction::new_i(Opcode::Addi, a0, a0, Immediate::Number(4096)),
+        ));
+        expect!["`add	a0, ft1, a0`: register `ft1` can't be encoded here"].assert_eq(&error(
+            Instruction::new_r(Opcode::Add, a0, Register::F(1), a0),
+        ));
+        expect!["`slli	a0, a0, x`: a label can't be encoded here"].assert_eq(&error(
+            Instruction::new_i(Opcode::Slli, a0, a0, Immediate::Label("x".to_string())),
+        ));
+        expect!["`lui	a0, %lo(x)`: `%lo` can't be encoded here"].assert_eq(&error(
+            Instruction::new_i(
+                Opcode::Lui,
+                a0,
+                Register::ZERO,
+                Immediate::Reloc {
+                    operator: RelocOperator::Lo,
+                    symbol: "x".to_string(),
+                    addend: 0,
+                },
+            ),
+        ));
+        expect!["`lui	a0, -1`: immediate -1 out of range"].assert_eq(&error(Instruction::new_i(
+            Opcode::Lui,
+            a0,
+            Register::ZERO,
+            Immediate::Number(-1),
+        )));
+        expect!["`amoadd.d	a0, a0, a0`: no encoding for this operand layout"].assert_eq(&error(
+            Instruction::new_r(
+                Opcode::Amoadd(AmoWidth::D, AmoOrdering::Relaxed),
+                a0,
+                a0,
+                a0,
+            ),
+        ));
+        expect!["`la	a0, table`: no encoding for this operand layout"].assert_eq(&error(
+            Instruction::new_u(Opcode::La, a0, Immediate::Label("table".to_string())),
+        ));
+        expect!["`fcvt.d.d	fa0, fa1`: no encoding for this operand layout"].assert_eq(&error(
+            Instruction::new_r(
+                Opcode::Fcvt(
+                    FpType::Float(Precision::D),
+                    FpType::Float(Precision::D),
+                    None,
+                ),
+                Register::F(10),
+                Register::F(11),
+                Register::ZERO,
+            ),
+        ));
+    }
+}
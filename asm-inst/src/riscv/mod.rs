@@ -1,3 +1,11 @@
+mod decode;
+mod emit;
+mod encode;
+
+pub use decode::{decode, decode_bytes, expand, label_at, DecodeError, DecodeErrorKind};
+pub use emit::{emit, Emit, RegisterNames};
+pub use encode::{compress, Code, EncodeError, EncodeErrorKind, Encoder, Reloc, RelocKind};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Instruction {
     pub opcode: Opcode,
@@ -27,6 +35,21 @@ pub enum Operands {
         imm: Immediate,
     },
 
+    /// `rd, rs2, (rs1)` of the atomics; `rs2` is zero for `lr`
+    Amo {
+        rd: Register,
+        rs2: Register,
+        rs1: Register,
+    },
+
+    /// The fused multiply-adds' three sources
+    R4 {
+        rd: Register,
+        rs1: Register,
+        rs2: Register,
+        rs3: Register,
+    },
+
     /// `ret` pre
     Nop,
     Label(String),
@@ -63,22 +86,41 @@ pub enum Opcode{
     Csrrw,Csrrs,Csrrc,Csrrwi,Csrrsi,Csrrci,
     /// Multiply,Divide and Reminder
     Mul,Mulh,Mulhsu,Mulhu,Mulw,
-    Div,Divu,
-    Rem,Remu,
-    /// Atomic
-    Lr,Sc,
-    Amoswap,Amoadd,
-    Amoxor,Amoand,Amoor,
-    Amomin,Amomax,Amominu,Amomaxu,
-
-    /// Floating-point
-    Fcvt,Fmv,
-    Fadd,Fsub,Fmul,Fdiv,Fsqrt,
-    Fmadd,Fmsub,Fnmadd,Fnmsub,
-    Fsgnj,Fsgnjn,Fsgnjx,
+    Div,Divu,Divw,Divuw,
+    Rem,Remu,Remw,Remuw,
+    /// Atomic, with the width and ordering they're written with
+    Lr(AmoWidth,AmoOrdering),Sc(AmoWidth,AmoOrdering),
+    Amoswap(AmoWidth,AmoOrdering),Amoadd(AmoWidth,AmoOrdering),
+    Amoxor(AmoWidth,AmoOrdering),Amoand(AmoWidth,AmoOrdering),Amoor(AmoWidth,AmoOrdering),
+    Amomin(AmoWidth,AmoOrdering),Amomax(AmoWidth,AmoOrdering),
+    Amominu(AmoWidth,AmoOrdering),Amomaxu(AmoWidth,AmoOrdering),
+
+    /// Floating-point load and store
+    Flw,Fld,Fsw,Fsd,
+    /// Floating-point, with the format and rounding mode they're written with
+    Fcvt(FpType,FpType,Option<RoundingMode>),Fmv(Precision),
+    Fadd(Precision,Option<RoundingMode>),Fsub(Precision,Option<RoundingMode>),
+    Fmul(Precision,Option<RoundingMode>),Fdiv(Precision,Option<RoundingMode>),
+    Fsqrt(Precision,Option<RoundingMode>),
+    Fmadd(Precision,Option<RoundingMode>),Fmsub(Precision,Option<RoundingMode>),
+    Fnmadd(Precision,Option<RoundingMode>),Fnmsub(Precision,Option<RoundingMode>),
+    Fsgnj(Precision),Fsgnjn(Precision),Fsgnjx(Precision),
+    Fmin(Precision),Fmax(Precision),
+    Feq(Precision),Flt(Precision),Fle(Precision),Fclass(Precision),
+
+    /// Address generation (Zba)
+    Sh1add,Sh2add,Sh3add,
+    AddUw,Sh1addUw,Sh2addUw,Sh3addUw,SlliUw,
+    /// Basic bit manipulation (Zbb)
+    Andn,Orn,Xnor,
+    Clz,Clzw,Ctz,Ctzw,Cpop,Cpopw,
+    Max,Maxu,Min,Minu,
+    SextB,SextH,ZextH,
+    Rol,Rolw,Ror,Rori,Roriw,Rorw,
+    OrcB,Rev8,
 
     /// Pseudoinstruction
-    La,Lla,Lga,Mv,
+    La,Lla,Lga,Li,Mv,
     Nop,
     Not,Neg,Negw,
     J,Ret,Call,Tail,
@@ -86,6 +128,60 @@ pub enum Opcode{
 
 }
 
+/// Size of the memory an atomic works on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AmoWidth {
+    W,
+    D,
+}
+
+/// The `aq` and `rl` bits of an atomic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AmoOrdering {
+    #[default]
+    Relaxed,
+    Aq,
+    Rl,
+    AqRl,
+}
+
+/// Floating-point format, ordered narrowest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Precision {
+    /// Half, from Zfh
+    H,
+    S,
+    D,
+}
+
+/// Integer side of an `fcvt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntWidth {
+    W,
+    Wu,
+    L,
+    Lu,
+}
+
+/// One side of an `fcvt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FpType {
+    Float(Precision),
+    Int(IntWidth),
+}
+
+/// Rounding mode of an FP instruction, where it's written out. Without one
+/// the assembler picks `dyn`, or `rne` for conversions that are exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoundingMode {
+    Rne,
+    Rtz,
+    Rdn,
+    Rup,
+    Rmm,
+    Dyn,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Register {
     X(u8),
@@ -95,8 +191,27 @@ pub enum Register {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Immediate {
-    Number(i16),
+    Number(i64),
     Label(String),
+    /// Part of a symbol's address, as in `%lo(msg+8)`
+    Reloc {
+        operator: RelocOperator,
+        symbol: String,
+        addend: i64,
+    },
+}
+
+/// Operator picking the part of a symbol's address an immediate holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RelocOperator {
+    /// `%hi`, the upper 20 bits for `lui`
+    Hi,
+    /// `%lo`, the lower 12 bits to go with `%hi`
+    Lo,
+    /// `%pcrel_hi`, the upper 20 bits of the offset from `auipc`
+    PcrelHi,
+    /// `%pcrel_lo`, the lower 12 bits, naming the label of that `auipc`
+    PcrelLo,
 }
 
 impl Register {
@@ -207,6 +322,26 @@ impl Instruction {
         }
     }
 
+    pub fn new_amo(opcode: Opcode, rd: Register, rs2: Register, rs1: Register) -> Self {
+        Self {
+            opcode,
+            operands: Operands::Amo { rd, rs2, rs1 },
+        }
+    }
+
+    pub fn new_r4(
+        opcode: Opcode,
+        rd: Register,
+        rs1: Register,
+        rs2: Register,
+        rs3: Register,
+    ) -> Self {
+        Self {
+            opcode,
+            operands: Operands::R4 { rd, rs1, rs2, rs3 },
+        }
+    }
+
     pub fn new_u(opcode: Opcode, rd: Register, imm: Immediate) -> Self {
         Self {
             opcode,
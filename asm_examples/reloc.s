@@ -0,0 +1,64 @@
+// Source of reloc.o, the object the ELF reader is tested with:
+//   llvm-mc -triple=aarch64 -filetype=obj reloc.s -o reloc.o
+	.text
+	.globl	main
+	.p2align	2
+	.type	main, %function
+main:
+	stp	x29, x30, [sp, #-16]!
+	adrp	x0, .LC0
+	add	x0, x0, :lo12:.LC0
+	bl	puts
+	adrp	x1, counter
+	ldr	x2, [x1, :lo12:counter]
+	cbnz	x2, 1f
+	movz	x3, #:abs_g1:table
+	movk	x3, #:abs_g0_nc:table
+	movn	x5, #:abs_g0_s:table
+	adrp	x4, :got:stdout
+	ldr	x4, [x4, :got_lo12:stdout]
+1:	subs	x2, x2, #1
+	b.ne	1b
+	ldp	x29, x30, [sp], #16
+	b	helper
+	.size	main, .-main
+
+	.type	helper, %function
+helper:
+	mov	w0, wzr
+	add	x0, x0, :tprel_lo12_nc:var
+	ret
+	.size	helper, .-helper
+
+	.section	.rodata.str1.1,"aMS",@progbits,1
+.LC0:
+	.string	"hi"
+
+	.data
+	.globl	counter
+	.p2align	3
+	.type	counter, %object
+counter:
+	.quad	42
+	.size	counter, 8
+table:
+	.quad	main
+	.word	helper - .
+	.byte	1, 2, 3
+	.p2align	3
+.Lpair:
+	.quad	main
+	.quad	helper
+	.quad	.Lpair + 12
+	.quad	.Lextra + 4
+
+	.section	.extra,"a"
+.Lextra:
+	.word	1, 2
+
+	.bss
+	.globl	buffer
+	.type	buffer, %object
+buffer:
+	.zero	16
+	.size	buffer, 16
@@ -0,0 +1,1143 @@
+//! Decoding of A64 machine code into the [`arm64`] model, for code that is
+//! only available as bytes, such as `.inst 0x...` words or the contents of
+//! an object file.
+//!
+//! Instructions come out the way [`lower`](crate::lower) would give them for
+//! their disassembly, using the same aliases: `subs xzr, x0, #5` decodes as
+//! `cmp x0, #5` and `orr x0, xzr, x1` as `mov x0, x1`.
+
+use asm_inst::arm64::{
+    self, Addressing, Cond, Extend, General, Index, Opcode, Operand, Order, Register, Regoperand,
+    Shift, Special,
+};
+
+/// A word that doesn't decode into the arm64 model.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{address:#x}: {kind} ({word:#010x})")]
+pub struct DecodeError {
+    pub address: u64,
+    pub word: u32,
+    pub kind: DecodeErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DecodeErrorKind {
+    /// Unallocated, or of an encoding class the decoder doesn't know
+    #[error("unknown encoding")]
+    Unknown,
+    /// A valid instruction the model has no opcode for
+    #[error("unsupported instruction `{0}`")]
+    Unsupported(String),
+    /// An operand the model can't hold
+    #[error("{0}")]
+    Operand(String),
+    /// Fewer than four bytes were left
+    #[error("truncated instruction")]
+    Truncated,
+}
+
+/// Name of the label that [`decode`] gives branch targets at `address`.
+pub fn label_at(address: u64) -> String {
+    format!(".L{address:x}")
+}
+
+/// Decodes the instruction `word`, found at `address`. Branch and literal
+/// targets become labels named by [`label_at`].
+pub fn decode(word: u32, address: u64) -> Result<arm64::Instruction, DecodeError> {
+    let w = Word(word);
+    let res = match w.bits(28, 25) {
+        0b1000 | 0b1001 => data_processing_imm(w, address),
+        0b1010 | 0b1011 => branch(w, address),
+        0b0100 | 0b0110 | 0b1100 | 0b1110 => load_store(w, address),
+        0b0101 | 0b1101 => data_processing_reg(w),
+        0b0111 | 0b1111 => fp(w),
+        _ => Err(DecodeErrorKind::Unknown),
+    };
+    res.map(|(opcode, operand)| arm64::Instruction { opcode, operand })
+        .map_err(|kind| DecodeError {
+            address,
+            word,
+            kind,
+        })
+}
+
+/// Decodes little-endian instruction words from `bytes`, the first of them
+/// at `address`.
+pub fn decode_bytes(bytes: &[u8], address: u64) -> Vec<Result<arm64::Instruction, DecodeError>> {
+    let chunks = bytes.chunks_exact(4);
+    let rest = chunks.remainder();
+    let end = address + (bytes.len() - rest.len()) as u64;
+    let mut res = chunks
+        .zip((address..).step_by(4))
+        .map(|(chunk, address)| {
+            let word = u32::from_le_bytes(chunk.try_into().expect("Chunk of four bytes"));
+            decode(word, address)
+        })
+        .collect::<Vec<_>>();
+    if !rest.is_empty() {
+        let mut word = [0; 4];
+        word[..rest.len()].copy_from_slice(rest);
+        res.push(Err(DecodeError {
+            address: end,
+            word: u32::from_le_bytes(word),
+            kind: DecodeErrorKind::Truncated,
+        }));
+    }
+    res
+}
+
+type Decoded = Result<(Opcode, Vec<Operand>), DecodeErrorKind>;
+
+#[derive(Clone, Copy)]
+struct Word(u32);
+
+impl Word {
+    /// Bits `hi` down to `lo`, inclusive.
+    fn bits(self, hi: u32, lo: u32) -> u32 {
+        (self.0 >> lo) & ((1 << (hi - lo + 1)) - 1)
+    }
+
+    fn bit(self, n: u32) -> bool {
+        self.0 >> n & 1 == 1
+    }
+
+    /// Bits `hi` down to `lo` as a two's complement number.
+    fn signed(self, hi: u32, lo: u32) -> i64 {
+        let width = hi - lo + 1;
+        let value = i64::from(self.bits(hi, lo));
+        (value << (64 - width)) >> (64 - width)
+    }
+
+    fn rd(self) -> u32 {
+        self.bits(4, 0)
+    }
+
+    fn rn(self) -> u32 {
+        self.bits(9, 5)
+    }
+
+    fn rm(self) -> u32 {
+        self.bits(20, 16)
+    }
+}
+
+fn unsupported(name: &str) -> DecodeErrorKind {
+    DecodeErrorKind::Unsupported(name.to_string())
+}
+
+/// A general register, where number 31 is `sp` or `wsp` if `sp` is set and
+/// the zero register otherwise.
+fn gpr(n: u32, x: bool, sp: bool) -> Register {
+    match (n, x) {
+        (31, true) if sp => Register::Special(Special::SP),
+        (31, false) if sp => Register::Special(Special::Wsp),
+        (31, true) => Register::Special(Special::Xzr),
+        (31, false) => Register::Special(Special::Wzr),
+        // the model keeps the link register apart, as `lower` does
+        (30, true) => Register::Special(Special::LR),
+        (n, x) => Register::General {
+            ty: if x { General::X } else { General::W },
+            n: n as u8,
+        },
+    }
+}
+
+fn reg(reg: Register) -> Operand {
+    Operand::Reg(Regoperand::Reg(reg))
+}
+
+fn imm(imm: i64, shift: Option<(u8, Shift)>) -> Operand {
+    Operand::Imm { imm, shift }
+}
+
+fn label(target: u64) -> Operand {
+    Operand::Label(label_at(target))
+}
+
+fn data_processing_imm(w: Word, address: u64) -> Decoded {
+    let sf = w.bit(31);
+    match w.bits(25, 23) {
+        0b000 | 0b001 => {
+            let offset = (w.signed(23, 5) << 2) | i64::from(w.bits(30, 29));
+            let (opcode, target) = match w.bit(31) {
+                true => (
+                    Opcode::Adrp,
+                    (address & !0xfff).wrapping_add_signed(offset << 12),
+                ),
+                false => (Opcode::Adr, address.wrapping_add_signed(offset)),
+            };
+            Ok((opcode, vec![reg(gpr(w.rd(), true, false)), label(target)]))
+        }
+        0b010 => add_sub_imm(w),
+        0b100 => logical_imm(w),
+        0b101 => {
+            let hw = w.bits(22, 21);
+            if !sf && hw > 1 {
+                return Err(DecodeErrorKind::Unknown);
+            }
+            let imm16 = w.bits(20, 5);
+            let shift = (hw != 0).then(|| ((hw * 16) as u8, Shift::Lsl));
+            let rd = reg(gpr(w.rd(), sf, false));
+            let opc = w.bits(30, 29);
+            // `movz` and `movn` read as a `mov` of the value they set, unless
+            // that's a zero chunk at a shift or, for `movn` of a `w`, all ones
+            let alias = (imm16 != 0 || hw == 0) && !(opc == 0b00 && !sf && imm16 == 0xffff);
+            if alias && matches!(opc, 0b00 | 0b10) {
+                let value = i64::from(imm16) << (hw * 16);
+                let value = if opc == 0b00 { !value } else { value };
+                let value = if sf { value } else { i64::from(value as i32) };
+                return Ok((Opcode::Mov, vec![rd, imm(value, None)]));
+            }
+            let opcode = match opc {
+                // `movn` sets the inverse of the shifted immediate
+                0b00 => Opcode::Mvn,
+                0b10 => Opcode::Mov,
+                0b11 => Opcode::Mvk,
+                _ => return Err(DecodeErrorKind::Unknown),
+            };
+            Ok((opcode, vec![rd, imm(i64::from(imm16), shift)]))
+        }
+        0b110 => bitfield(w),
+        0b111 => Err(unsupported("extr")),
+        _ => Err(DecodeErrorKind::Unknown),
+    }
+}
+
+fn add_sub_imm(w: Word) -> Decoded {
+    let (sf, sub, set_flags) = (w.bit(31), w.bit(30), w.bit(29));
+    let imm12 = i64::from(w.bits(21, 10));
+    let shift = w.bit(22).then_some((12, Shift::Lsl));
+    let rn = gpr(w.rn(), sf, true);
+    if set_flags && w.rd() == 31 {
+        return match sub {
+            true => Ok((Opcode::Cmp, vec![reg(rn), imm(imm12, shift)])),
+            false => Err(unsupported("cmn")),
+        };
+    }
+    let rd = gpr(w.rd(), sf, !set_flags);
+    if !set_flags && !sub && imm12 == 0 && shift.is_none() && (w.rd() == 31 || w.rn() == 31) {
+        return Ok((Opcode::Mov, vec![reg(rd), reg(rn)]));
+    }
+    let opcode = match (sub, set_flags) {
+        (false, false) => Opcode::Add,
+        (false, true) => Opcode::Adds,
+        (true, false) => Opcode::Sub,
+        (true, true) => Opcode::Subs,
+    };
+    Ok((opcode, vec![reg(rd), reg(rn), imm(imm12, shift)]))
+}
+
+fn logical_imm(w: Word) -> Decoded {
+    let sf = w.bit(31);
+    let n = w.bit(22);
+    if !sf && n {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    let mask =
+        decode_bit_mask(n, w.bits(21, 16), w.bits(15, 10), sf).ok_or(DecodeErrorKind::Unknown)?;
+    // the register's bits, negative when the top one is set
+    let value = imm(mask as i64, None);
+    let opcode = match w.bits(30, 29) {
+        0b00 => Opcode::And,
+        0b01 if w.rn() == 31 => return Ok((Opcode::Mov, vec![reg(gpr(w.rd(), sf, true)), value])),
+        0b01 => Opcode::Or,
+        0b10 => Opcode::Xor,
+        _ => return Err(unsupported(if w.rd() == 31 { "tst" } else { "ands" })),
+    };
+    let rd = gpr(w.rd(), sf, true);
+    let rn = gpr(w.rn(), sf, false);
+    Ok((opcode, vec![reg(rd), reg(rn), value]))
+}
+
+/// The value of a logical immediate, as the manual's `DecodeBitMasks`
+/// computes it: a run of `imms + 1` ones rotated right by `immr`, repeated
+/// across the register.
+fn decode_bit_mask(n: bool, immr: u32, imms: u32, sf: bool) -> Option<u64> {
+    let combined = (u32::from(n) << 6) | (!imms & 0x3f);
+    let len = combined.checked_ilog2().filter(|&len| len >= 1)?;
+    let size = 1u32 << len;
+    let levels = size - 1;
+    let (s, r) = (imms & levels, immr & levels);
+    if s == levels {
+        return None;
+    }
+    let ones = (1u64 << (s + 1)) - 1;
+    let mask = if size == 64 {
+        u64::MAX
+    } else {
+        (1 << size) - 1
+    };
+    let element = ((ones >> r) | (ones << ((size - r) % size))) & mask;
+    let width = if sf { 64 } else { 32 };
+    let value = (0..width / size).fold(0, |acc, i| acc | element << (i * size));
+    Some(value)
+}
+
+fn bitfield(w: Word) -> Decoded {
+    let sf = w.bit(31);
+    if sf != w.bit(22) {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    let (immr, imms) = (w.bits(21, 16), w.bits(15, 10));
+    if !sf && (immr | imms) >= 32 {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    let top = if sf { 63 } else { 31 };
+    let (opcode, amount) = match w.bits(30, 29) {
+        0b00 if imms == top => (Opcode::Asr, immr),
+        0b10 if imms == top => (Opcode::Lsr, immr),
+        0b10 if imms + 1 == immr => (Opcode::Lsl, top - imms),
+        0b00 => return Err(unsupported("sbfm")),
+        0b01 => return Err(unsupported("bfm")),
+        0b10 => return Err(unsupported("ubfm")),
+        _ => return Err(DecodeErrorKind::Unknown),
+    };
+    let rd = gpr(w.rd(), sf, false);
+    let rn = gpr(w.rn(), sf, false);
+    Ok((opcode, vec![reg(rd), reg(rn), imm(i64::from(amount), None)]))
+}
+
+const CONDITIONS: [Cond; 16] = {
+    use Cond::*;
+    [
+        Eq, Ne, Hs, Lo, Mi, Pl, Vs, Vc, Hi, Ls, Ge, Lt, Gt, Le, Al, Nv,
+    ]
+};
+
+fn branch(w: Word, address: u64) -> Decoded {
+    let target = |offset: i64| address.wrapping_add_signed(offset * 4);
+    match w.bits(31, 26) {
+        0b000101 => return Ok((Opcode::B, vec![label(target(w.signed(25, 0)))])),
+        0b100101 => return Ok((Opcode::Bl, vec![label(target(w.signed(25, 0)))])),
+        _ => {}
+    }
+    if w.bits(31, 24) == 0b0101_0100 && !w.bit(4) {
+        let opcode = Opcode::branch_on(CONDITIONS[w.bits(3, 0) as usize]);
+        return Ok((opcode, vec![label(target(w.signed(23, 5)))]));
+    }
+    match w.bits(30, 25) {
+        0b011010 => {
+            let opcode = if w.bit(24) { Opcode::Cbnz } else { Opcode::Cbz };
+            let rt = gpr(w.rd(), w.bit(31), false);
+            return Ok((opcode, vec![reg(rt), label(target(w.signed(23, 5)))]));
+        }
+        // the top bit of the bit number also picks the register size
+        0b011011 => {
+            let opcode = if w.bit(24) { Opcode::Tbnz } else { Opcode::Tbz };
+            let rt = gpr(w.rd(), w.bit(31), false);
+            let bit = w.bits(31, 31) << 5 | w.bits(23, 19);
+            let operand = vec![
+                reg(rt),
+                imm(bit.into(), None),
+                label(target(w.signed(18, 5))),
+            ];
+            return Ok((opcode, operand));
+        }
+        _ => {}
+    }
+    if w.0 == 0xd503201f {
+        return Ok((Opcode::Nop, Vec::new()));
+    }
+    match w.bits(31, 25) {
+        0b1101011 if w.bits(20, 10) != 0b11111_000000 || w.rd() != 0 => {
+            Err(DecodeErrorKind::Unknown)
+        }
+        0b1101011 => {
+            let rn = gpr(w.rn(), true, false);
+            match w.bits(24, 21) {
+                0b0000 => Ok((Opcode::Br, vec![reg(rn)])),
+                0b0001 => Ok((Opcode::Blr, vec![reg(rn)])),
+                0b0010 if w.rn() == 30 => Ok((Opcode::Ret, Vec::new())),
+                0b0010 => Ok((Opcode::Ret, vec![reg(rn)])),
+                _ => Err(DecodeErrorKind::Unknown),
+            }
+        }
+        0b1101010 if w.bits(24, 22) == 0b100 => Err(unsupported("system instruction")),
+        0b1101010 if w.bits(24, 24) == 0 => Err(unsupported("exception")),
+        _ => Err(DecodeErrorKind::Unknown),
+    }
+}
+
+fn load_store(w: Word, address: u64) -> Decoded {
+    match w.bits(29, 24) {
+        0b001000 => exclusive(w),
+        0b011000 | 0b011100 => load_literal(w, address),
+        0b101000..=0b101111 => load_store_pair(w),
+        0b111000 | 0b111100 if w.bit(21) && w.bits(11, 10) == 0b00 => atomic(w),
+        0b111000 | 0b111100 if w.bit(21) => Err(DecodeErrorKind::Operand(
+            "unsupported register offset".to_string(),
+        )),
+        0b111000 | 0b111100 => {
+            let index = match w.bits(11, 10) {
+                0b00 => None,
+                0b01 => Some(Index::Post),
+                0b11 => Some(Index::Pre),
+                _ => return Err(unsupported("unprivileged load/store")),
+            };
+            load_store_reg(w, w.signed(20, 12), index)
+        }
+        0b111001 | 0b111101 => {
+            let (_, _, scale) = transfer_reg(w)?;
+            load_store_reg(w, i64::from(w.bits(21, 10)) << scale, None)
+        }
+        _ => Err(DecodeErrorKind::Unknown),
+    }
+}
+
+/// A load or store of a single register at an immediate offset from the
+/// base register.
+fn load_store_reg(w: Word, offset: i64, index: Option<Index>) -> Decoded {
+    let (opcode, rt, _) = transfer_reg(w)?;
+    // writing back to the register transferred is unpredictable
+    if index.is_some() && !w.bit(26) && w.rd() == w.rn() && w.rn() != 31 {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    Ok((opcode, vec![reg(rt), addressing(w, offset, index)]))
+}
+
+/// `[xn, #offset]` with the base register in `Rn`, or `[xn]` for no offset.
+fn addressing(w: Word, offset: i64, index: Option<Index>) -> Operand {
+    let base = gpr(w.rn(), true, true);
+    Operand::Addressing(match (offset, &index) {
+        (0, None) => Addressing::BaseRegister(base),
+        _ => Addressing::Offset {
+            offset,
+            reg: base,
+            index,
+        },
+    })
+}
+
+/// `ldp` and `stp`, whose scaled offset sits in bits 21 to 15.
+fn load_store_pair(w: Word) -> Decoded {
+    let load = w.bit(22);
+    let index = match w.bits(24, 23) {
+        0b00 => return Err(unsupported(if load { "ldnp" } else { "stnp" })),
+        0b01 => Some(Index::Post),
+        0b10 => None,
+        _ => Some(Index::Pre),
+    };
+    let (ty, scale) = match (w.bit(26), w.bits(31, 30)) {
+        (false, 0b00) => (General::W, 2),
+        (false, 0b10) => (General::X, 3),
+        (false, 0b01) if load => return Err(unsupported("ldpsw")),
+        (true, 0b00) => (General::S, 2),
+        (true, 0b01) => (General::D, 3),
+        (true, 0b10) => (General::Q, 4),
+        _ => return Err(DecodeErrorKind::Unknown),
+    };
+    let (rt, rt2) = (w.rd(), w.bits(14, 10));
+    // loading both halves into one register is unpredictable, as is writing
+    // back to a register transferred
+    let writeback = index.is_some() && !w.bit(26) && w.rn() != 31;
+    if load && rt == rt2 || writeback && (w.rn() == rt || w.rn() == rt2) {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    let transfer = |n: u32| match ty {
+        General::W | General::X => gpr(n, ty == General::X, false),
+        _ => Register::General { ty, n: n as u8 },
+    };
+    let opcode = if load { Opcode::Ldp } else { Opcode::Stp };
+    let operand = vec![
+        reg(transfer(rt)),
+        reg(transfer(rt2)),
+        addressing(w, w.signed(21, 15) << scale, index),
+    ];
+    Ok((opcode, operand))
+}
+
+/// The opcode of a load or store, the register it transfers and the log2
+/// of its size in bytes, from the `size`, `V` and `opc` fields.
+fn transfer_reg(w: Word) -> Result<(Opcode, Register, u32), DecodeErrorKind> {
+    let size = w.bits(31, 30);
+    let load = w.bit(22);
+    if w.bit(26) {
+        let (ty, scale) = match (size, w.bit(23)) {
+            (0b00, false) => (General::B, 0),
+            (0b01, false) => (General::H, 1),
+            (0b10, false) => (General::S, 2),
+            (0b11, false) => (General::D, 3),
+            (0b00, true) => (General::Q, 4),
+            _ => return Err(DecodeErrorKind::Unknown),
+        };
+        let opcode = if load { Opcode::Ldr } else { Opcode::Str };
+        let rt = Register::General {
+            ty,
+            n: w.rd() as u8,
+        };
+        return Ok((opcode, rt, scale));
+    }
+    let opc = w.bits(23, 22);
+    let (opcode, x) = match (size, opc) {
+        (0b11 | 0b10, 0b00) => (Opcode::Str, size == 0b11),
+        (0b11 | 0b10, 0b01) => (Opcode::Ldr, size == 0b11),
+        (0b11, 0b10) => return Err(unsupported("prfm")),
+        (0b10, 0b10) => (Opcode::Ldrsw, true),
+        (0b00, 0b00) => (Opcode::Strb, false),
+        (0b00, 0b01) => (Opcode::Ldrb, false),
+        (0b01, 0b00) => (Opcode::Strh, false),
+        (0b01, 0b01) => (Opcode::Ldrh, false),
+        // opc 10 sign-extends to 64 bits and 11 to 32
+        (0b00, _) => (Opcode::Ldrsb, opc == 0b10),
+        (0b01, _) => (Opcode::Ldrsh, opc == 0b10),
+        _ => return Err(DecodeErrorKind::Unknown),
+    };
+    Ok((opcode, gpr(w.rd(), x, false), size))
+}
+
+fn load_literal(w: Word, address: u64) -> Decoded {
+    let (opcode, rt) = match (w.bit(26), w.bits(31, 30)) {
+        (false, 0b00) => (Opcode::Ldr, gpr(w.rd(), false, false)),
+        (false, 0b01) => (Opcode::Ldr, gpr(w.rd(), true, false)),
+        (false, 0b10) => (Opcode::Ldrsw, gpr(w.rd(), true, false)),
+        (false, _) => return Err(unsupported("prfm")),
+        (true, size @ 0b00..=0b10) => {
+            let ty = [General::S, General::D, General::Q][size as usize];
+            (
+                Opcode::Ldr,
+                Register::General {
+                    ty,
+                    n: w.rd() as u8,
+                },
+            )
+        }
+        (true, _) => return Err(DecodeErrorKind::Unknown),
+    };
+    let target = address.wrapping_add_signed(w.signed(23, 5) * 4);
+    Ok((opcode, vec![reg(rt), label(target)]))
+}
+
+/// The load/store exclusive and ordered group, which also holds `cas`.
+fn exclusive(w: Word) -> Decoded {
+    let x = w.bits(31, 30) == 0b11;
+    let (o2, load, o1, o0) = (w.bit(23), w.bit(22), w.bit(21), w.bit(15));
+    if w.bits(31, 30) < 0b10 {
+        return Err(unsupported("byte or halfword exclusive"));
+    }
+    // only the pair forms have an `Rt2`, and only the stores an `Rs`
+    let (rs_unused, rt2_unused) = (w.rm() == 31, w.bits(14, 10) == 31);
+    let rs = gpr(w.rm(), false, false);
+    let rt = gpr(w.rd(), x, false);
+    let base = Operand::Addressing(Addressing::BaseRegister(gpr(w.rn(), true, true)));
+    match (o2, load, o1, o0) {
+        (_, _, false, _) | (true, _, true, _) if !rt2_unused => Err(DecodeErrorKind::Unknown),
+        (false, true, false, _) | (true, _, false, _) if !rs_unused => {
+            Err(DecodeErrorKind::Unknown)
+        }
+        (false, true, false, true) => Ok((Opcode::Ldaxr, vec![reg(rt), base])),
+        // the status register can't be one the store reads
+        (false, false, false, _) if w.rm() == w.rd() || w.rm() == w.rn() && w.rn() != 31 => {
+            Err(DecodeErrorKind::Unknown)
+        }
+        (false, false, false, true) => Ok((Opcode::Stlxr, vec![reg(rs), reg(rt), base])),
+        (true, true, false, true) => Ok((Opcode::Ldar, vec![reg(rt), base])),
+        (true, false, false, true) => Ok((Opcode::Stlr, vec![reg(rt), base])),
+        (true, _, true, _) => {
+            let rs = gpr(w.rm(), x, false);
+            Ok((Opcode::Cas(order(load, o0)), vec![reg(rs), reg(rt), base]))
+        }
+        (false, true, false, false) => Err(unsupported("ldxr")),
+        (false, false, false, false) => Err(unsupported("stxr")),
+        (false, _, true, _) => Err(unsupported(if load { "ldxp" } else { "stxp" })),
+        (true, _, false, false) => Err(unsupported(if load { "ldlar" } else { "stllr" })),
+    }
+}
+
+fn order(acquire: bool, release: bool) -> Order {
+    match (acquire, release) {
+        (false, false) => Order::Relaxed,
+        (true, false) => Order::Acquire,
+        (false, true) => Order::Release,
+        (true, true) => Order::AcqRel,
+    }
+}
+
+/// LSE atomic memory operations like `ldadd` and `swp`.
+fn atomic(w: Word) -> Decoded {
+    if w.bit(26) {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    let op = match (w.bit(15), w.bits(14, 12)) {
+        (false, 0b000) => Opcode::Ldadd,
+        (false, 0b001) => Opcode::Ldclr,
+        (false, 0b010) => Opcode::Ldeor,
+        (false, 0b011) => Opcode::Ldset,
+        (false, 0b100) => Opcode::Ldsmax,
+        (false, 0b101) => Opcode::Ldsmin,
+        (false, 0b110) => Opcode::Ldumax,
+        (false, 0b111) => Opcode::Ldumin,
+        (true, 0b000) => Opcode::Swp,
+        (true, 0b100) => return Err(unsupported("ldapr")),
+        _ => return Err(DecodeErrorKind::Unknown),
+    };
+    let x = match w.bits(31, 30) {
+        0b10 => false,
+        0b11 => true,
+        _ => return Err(unsupported("byte or halfword atomic")),
+    };
+    let rs = gpr(w.rm(), x, false);
+    let rt = gpr(w.rd(), x, false);
+    let base = Operand::Addressing(Addressing::BaseRegister(gpr(w.rn(), true, true)));
+    let opcode = op(order(w.bit(23), w.bit(22)));
+    Ok((opcode, vec![reg(rs), reg(rt), base]))
+}
+
+fn data_processing_reg(w: Word) -> Decoded {
+    let sf = w.bit(31);
+    match (w.bits(28, 24), w.bit(21)) {
+        (0b01010, _) => logical_reg(w),
+        (0b01011, false) => add_sub_reg(w),
+        (0b01011, true) => add_sub_ext(w),
+        (0b11011, _) => three_source(w),
+        (0b11010, _) => match w.bits(23, 21) {
+            0b110 if w.bit(29) => Err(DecodeErrorKind::Unknown),
+            0b110 if w.bit(30) => Err(unsupported("rbit, rev or clz")),
+            0b110 => {
+                let opcode = match w.bits(15, 10) {
+                    0b000010 => Opcode::Udiv,
+                    0b000011 => Opcode::Div,
+                    0b001000 => Opcode::Lsl,
+                    0b001001 => Opcode::Lsr,
+                    0b001010 => Opcode::Asr,
+                    0b001011 => return Err(unsupported("ror")),
+                    _ => return Err(DecodeErrorKind::Unknown),
+                };
+                let regs = [w.rd(), w.rn(), w.rm()];
+                Ok((opcode, regs.map(|n| reg(gpr(n, sf, false))).to_vec()))
+            }
+            0b100 => conditional_select(w),
+            0b000 => Err(unsupported(if w.bit(30) { "sbc" } else { "adc" })),
+            0b010 => Err(unsupported(if w.bit(30) { "ccmp" } else { "ccmn" })),
+            _ => Err(DecodeErrorKind::Unknown),
+        },
+        _ => Err(DecodeErrorKind::Unknown),
+    }
+}
+
+/// `csel` and its increment, invert and negate forms, with `cset` for the
+/// `csinc` it stands for.
+fn conditional_select(w: Word) -> Decoded {
+    let sf = w.bit(31);
+    if w.bit(29) || w.bit(11) {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    let cond = w.bits(15, 12);
+    let rd = reg(gpr(w.rd(), sf, false));
+    let opcode = match (w.bit(30), w.bit(10)) {
+        (false, false) => Opcode::Csel,
+        // `cset rd, cond` is `csinc rd, zr, zr` with the inverse condition
+        (false, true) if w.rn() == 31 && w.rm() == 31 && cond >> 1 != 0b111 => {
+            let cond = Operand::Cond(CONDITIONS[(cond ^ 1) as usize]);
+            return Ok((Opcode::Cset, vec![rd, cond]));
+        }
+        (false, true) => Opcode::Csinc,
+        (true, false) => Opcode::Csinv,
+        (true, true) => Opcode::Csneg,
+    };
+    let rn = reg(gpr(w.rn(), sf, false));
+    let rm = reg(gpr(w.rm(), sf, false));
+    let cond = Operand::Cond(CONDITIONS[cond as usize]);
+    Ok((opcode, vec![rd, rn, rm, cond]))
+}
+
+/// A register shifted by `amount`, left plain when there is no shift.
+fn shifted(r: Register, shift: u32, amount: u32) -> Operand {
+    let shift = [Shift::Lsl, Shift::Lsr, Shift::Asr, Shift::Ror][shift as usize].clone();
+    match (&shift, amount) {
+        (Shift::Lsl, 0) => reg(r),
+        _ => Operand::Reg(Regoperand::ShiftReg(r, (amount as u8, shift))),
+    }
+}
+
+fn logical_reg(w: Word) -> Decoded {
+    let sf = w.bit(31);
+    let (shift, amount) = (w.bits(23, 22), w.bits(15, 10));
+    if !sf && amount >= 32 {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    let rd = reg(gpr(w.rd(), sf, false));
+    let rm = shifted(gpr(w.rm(), sf, false), shift, amount);
+    let opcode = match (w.bits(30, 29), w.bit(21)) {
+        (0b01, false) if w.rn() == 31 && shift == 0 && amount == 0 => {
+            return Ok((Opcode::Mov, vec![rd, rm]))
+        }
+        (0b01, true) if w.rn() == 31 => return Ok((Opcode::Mvn, vec![rd, rm])),
+        (0b00, false) => Opcode::And,
+        (0b01, false) => Opcode::Or,
+        (0b10, false) => Opcode::Xor,
+        (0b00, true) => return Err(unsupported("bic")),
+        (0b01, true) => return Err(unsupported("orn")),
+        (0b10, true) => return Err(unsupported("eon")),
+        (_, false) => return Err(unsupported(if w.rd() == 31 { "tst" } else { "ands" })),
+        (_, true) => return Err(unsupported("bics")),
+    };
+    Ok((opcode, vec![rd, reg(gpr(w.rn(), sf, false)), rm]))
+}
+
+fn add_sub_opcode(w: Word) -> Result<Option<Opcode>, DecodeErrorKind> {
+    let (sub, set_flags) = (w.bit(30), w.bit(29));
+    if set_flags && w.rd() == 31 {
+        return match sub {
+            true => Ok(None),
+            false => Err(unsupported("cmn")),
+        };
+    }
+    Ok(Some(match (sub, set_flags) {
+        (false, false) => Opcode::Add,
+        (false, true) => Opcode::Adds,
+        (true, false) => Opcode::Sub,
+        (true, true) => Opcode::Subs,
+    }))
+}
+
+fn add_sub_reg(w: Word) -> Decoded {
+    let sf = w.bit(31);
+    let (shift, amount) = (w.bits(23, 22), w.bits(15, 10));
+    if shift == 0b11 || (!sf && amount >= 32) {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    let rn = reg(gpr(w.rn(), sf, false));
+    let rm = shifted(gpr(w.rm(), sf, false), shift, amount);
+    Ok(match add_sub_opcode(w)? {
+        Some(opcode) => (opcode, vec![reg(gpr(w.rd(), sf, false)), rn, rm]),
+        None => (Opcode::Cmp, vec![rn, rm]),
+    })
+}
+
+fn add_sub_ext(w: Word) -> Decoded {
+    let sf = w.bit(31);
+    let (option, amount) = (w.bits(15, 13), w.bits(12, 10));
+    if w.bits(23, 22) != 0 || amount > 4 {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    let set_flags = w.bit(29);
+    let rn = reg(gpr(w.rn(), sf, true));
+    let rm = gpr(w.rm(), sf && option & 0b011 == 0b011, false);
+    // with `sp` involved, the extend that does nothing is written as `lsl`
+    let uses_sp = (w.rd() == 31 && !set_flags) || w.rn() == 31;
+    let rm = match (uses_sp, option) {
+        (true, 0b011) if sf => shifted(rm, 0, amount),
+        (true, 0b010) if !sf => shifted(rm, 0, amount),
+        _ => {
+            let extend = [
+                Extend::Uxtb,
+                Extend::Uxth,
+                Extend::Uxtw,
+                Extend::Uxtx,
+                Extend::Sxtb,
+                Extend::Sxth,
+                Extend::Sxtw,
+                Extend::Sxtx,
+            ][option as usize]
+                .clone();
+            Operand::Reg(Regoperand::ExtendReg(rm, (amount as u8, extend)))
+        }
+    };
+    Ok(match add_sub_opcode(w)? {
+        Some(opcode) => (opcode, vec![reg(gpr(w.rd(), sf, !set_flags)), rn, rm]),
+        None => (Opcode::Cmp, vec![rn, rm]),
+    })
+}
+
+fn three_source(w: Word) -> Decoded {
+    let sf = w.bit(31);
+    let ra = w.bits(14, 10);
+    match (w.bits(30, 29), w.bits(23, 21), w.bit(15)) {
+        (0b00, 0b000, false) if ra == 31 => {
+            let regs = [w.rd(), w.rn(), w.rm()];
+            Ok((Opcode::Mul, regs.map(|n| reg(gpr(n, sf, false))).to_vec()))
+        }
+        (0b00, 0b101, false) if sf => {
+            let operand = vec![
+                reg(gpr(w.rd(), true, false)),
+                reg(gpr(w.rn(), false, false)),
+                reg(gpr(w.rm(), false, false)),
+                reg(gpr(ra, true, false)),
+            ];
+            Ok((Opcode::Umaddl, operand))
+        }
+        (0b00, 0b000, msub) => {
+            let opcode = if msub { Opcode::Msub } else { Opcode::Madd };
+            let regs = [w.rd(), w.rn(), w.rm(), ra];
+            Ok((opcode, regs.map(|n| reg(gpr(n, sf, false))).to_vec()))
+        }
+        (0b00, 0b001, false) if sf => Err(unsupported("smaddl")),
+        (0b00, 0b010, false) if sf => Err(unsupported("smulh")),
+        (0b00, 0b110, false) if sf => Err(unsupported("umulh")),
+        _ => Err(DecodeErrorKind::Unknown),
+    }
+}
+
+/// Scalar floating-point instructions.
+fn fp(w: Word) -> Decoded {
+    if w.bit(30) || w.bit(29) || w.bits(28, 24) & 0b11110 != 0b11110 {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    let ty = match w.bits(23, 22) {
+        0b00 => General::S,
+        0b01 => General::D,
+        0b11 => General::H,
+        // the upper half of a vector register, as in `fmov x0, v1.d[1]`
+        _ if w.bit(31) && w.bits(21, 17) == 0b10111 && w.bits(15, 10) == 0 => {
+            return Err(unsupported("fmov to or from a vector element"))
+        }
+        _ => return Err(DecodeErrorKind::Unknown),
+    };
+    let fpr = |n: u32| reg(Register::General { ty, n: n as u8 });
+    if w.bit(24) {
+        if w.bit(31) {
+            return Err(DecodeErrorKind::Unknown);
+        }
+        let opcode = match (w.bit(21), w.bit(15)) {
+            (false, false) => Opcode::Fmuladd,
+            (false, true) => Opcode::Fmsub,
+            (true, false) => Opcode::Fnmadd,
+            (true, true) => return Err(unsupported("fnmsub")),
+        };
+        let regs = [w.rd(), w.rn(), w.rm(), w.bits(14, 10)];
+        return Ok((opcode, regs.map(fpr).to_vec()));
+    }
+    if !w.bit(21) || w.bit(31) && w.bits(15, 10) != 0 {
+        return Err(DecodeErrorKind::Unknown);
+    }
+    if w.bits(15, 10) == 0 {
+        return fp_int_conversion(w, ty);
+    }
+    if w.bits(14, 10) == 0b10000 {
+        let opcode = match w.bits(20, 15) {
+            0b000000 => Opcode::Fmov,
+            0b000010 => Opcode::Fneg,
+            0b000011 => Opcode::Fsqrt,
+            0b000001 => Opcode::Fabs,
+            // to a type other than its own
+            0b000100 | 0b000101 | 0b000111 if w.bits(16, 15) != w.bits(23, 22) => {
+                return Err(unsupported("fcvt"))
+            }
+            0b001000..=0b001100 | 0b001110 | 0b001111 | 0b010000..=0b010011 => {
+                return Err(unsupported("frint"))
+            }
+            _ => return Err(DecodeErrorKind::Unknown),
+        };
+        return Ok((opcode, vec![fpr(w.rd()), fpr(w.rn())]));
+    }
+    if w.bits(13, 10) == 0b1000 {
+        if w.bits(15, 14) != 0 || w.bits(2, 0) != 0 {
+            return Err(DecodeErrorKind::Unknown);
+        }
+        let opcode = if w.bit(4) {
+            Opcode::Fcmpe
+        } else {
+            Opcode::Fcmp
+        };
+        let rhs = if w.bit(3) { imm(0, None) } else { fpr(w.rm()) };
+        return Ok((opcode, vec![fpr(w.rn()), rhs]));
+    }
+    if w.bits(12, 10) == 0b100 {
+        if w.bits(9, 5) != 0 {
+            return Err(DecodeErrorKind::Unknown);
+        }
+        return Err(DecodeErrorKind::Operand(
+            "unsupported floating-point immediate".to_string(),
+        ));
+    }
+    match w.bits(11, 10) {
+        0b10 => {
+            let opcode = match w.bits(15, 12) {
+                0b0000 => Opcode::Fmul,
+                0b0001 => Opcode::Fdiv,
+                0b0010 => Opcode::Fadd,
+                0b0011 => Opcode::Fsub,
+                0b1000 => Opcode::Fnmul,
+                0b0100..=0b0111 => return Err(unsupported("fmax or fmin")),
+                _ => return Err(DecodeErrorKind::Unknown),
+            };
+            let regs = [w.rd(), w.rn(), w.rm()];
+            Ok((opcode, regs.map(fpr).to_vec()))
+        }
+        0b01 => Err(unsupported("fccmp")),
+        _ => Err(unsupported("fcsel")),
+    }
+}
+
+/// Conversions and moves between general and floating-point registers.
+fn fp_int_conversion(w: Word, ty: General) -> Decoded {
+    let sf = w.bit(31);
+    let fpr = reg(Register::General {
+        ty,
+        n: w.rd() as u8,
+    });
+    let gpr_n = |n| reg(gpr(n, sf, false));
+    // `fmov` keeps the bits as they are, so both registers have one size
+    let same_size = matches!(
+        (sf, ty),
+        (false, General::S) | (true, General::D) | (_, General::H)
+    );
+    match (w.bits(20, 19), w.bits(18, 16)) {
+        (0b00, 0b010) => Ok((Opcode::Scvtf, vec![fpr, gpr_n(w.rn())])),
+        (0b00, 0b011) => Ok((Opcode::Ucvtf, vec![fpr, gpr_n(w.rn())])),
+        (0b00, 0b110 | 0b111) if !same_size => Err(DecodeErrorKind::Unknown),
+        (0b00, 0b111) => Ok((Opcode::Fmov, vec![fpr, gpr_n(w.rn())])),
+        (0b00, 0b110) => {
+            let fn_ = reg(Register::General {
+                ty,
+                n: w.rn() as u8,
+            });
+            Ok((Opcode::Fmov, vec![gpr_n(w.rd()), fn_]))
+        }
+        (0b11, 0b000) => Err(unsupported("fcvtzs")),
+        (0b11, 0b001) => Err(unsupported("fcvtzu")),
+        (_, 0b000 | 0b001) | (0b00, 0b100 | 0b101) => Err(unsupported("fcvt")),
+        _ => Err(DecodeErrorKind::Unknown),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lower::lower_line;
+    use expect_test::{expect, Expect};
+
+    /// Checks that each word decodes to what its disassembly lowers to.
+    fn check_same_as_lowered(cases: &[(u32, &str)]) {
+        for &(word, text) in cases {
+            let lines = armparser::parse_asm(text).unwrap();
+            let lowered = lower_line(text, &lines[0]).unwrap().unwrap();
+            let decoded = decode(word, 0x1000).unwrap();
+            assert_eq!(format!("{decoded:?}"), format!("{lowered:?}"), "{text}");
+            // and prints as something that reads back the same
+            let printed = decoded.to_string();
+            let lines = armparser::parse_asm(&printed).unwrap();
+            let reread = lower_line(&printed, &lines[0]).unwrap().unwrap();
+            assert_eq!(format!("{reread:?}"), format!("{lowered:?}"), "{printed}");
+        }
+    }
+
+    fn check(words: &[u32], expect: Expect) {
+        let res = words
+            .iter()
+            .map(|&word| match decode(word, 0x1000) {
+                Ok(inst) => format!("{inst:?}\n"),
+                Err(e) => format!("{e}\n"),
+            })
+            .collect::<String>();
+        expect.assert_eq(&res);
+    }
+
+    #[test]
+    fn decode_as_lowered() {
+        check_same_as_lowered(&[
+            (0x91004020, "add x0, x1, #16"),
+            (0x913ffc20, "add x0, x1, #4095"),
+            (0x313ffc20, "adds w0, w1, #4095"),
+            (0xd10083ff, "sub sp, sp, #32"),
+            (0xf1002020, "subs x0, x1, #8"),
+            (0xf100141f, "cmp x0, #5"),
+            (0x910003fd, "mov x29, sp"),
+            (0x511773ff, "sub wsp, wsp, #1500"),
+            (0x1100001f, "mov wsp, w0"),
+            (0x8b020c20, "add x0, x1, x2, lsl #3"),
+            (0x4b820820, "sub w0, w1, w2, asr #2"),
+            (0x8b22c820, "add x0, x1, w2, sxtw #2"),
+            (0x8b2163ff, "add sp, sp, x1"),
+            (0x4b220bff, "sub wsp, wsp, w2, uxtb #2"),
+            (0x0b2143e0, "add w0, wsp, w1"),
+            (0x6b01001f, "cmp w0, w1"),
+            (0x92401c20, "and x0, x1, #0xff"),
+            (0x12001c3f, "and wsp, w1, #0xff"),
+            (0x321c03e0, "mov w0, #0x10"),
+            (0xca020020, "eor x0, x1, x2"),
+            (0xaac21020, "orr x0, x1, x2, ror #4"),
+            (0xaa0103e0, "mov x0, x1"),
+            (0x2a2103e0, "mvn w0, w1"),
+            (0xd2824680, "mov x0, #0x1234"),
+            (0xd2a24680, "mov x0, #0x12340000"),
+            (0x52b00000, "mov w0, #-2147483648"),
+            (0x92800000, "mov x0, #-1"),
+            (0x12800000, "mov w0, #-1"),
+            (0x92a00020, "mov x0, #-65537"),
+            (0x12a00020, "mov w0, #-65537"),
+            (0xf2a24680, "movk x0, #0x1234, lsl #16"),
+            (0xd37df020, "lsl x0, x1, #3"),
+            (0x53047c20, "lsr w0, w1, #4"),
+            (0x937ffc20, "asr x0, x1, #63"),
+            (0x9ac22020, "lsl x0, x1, x2"),
+            (0x9ac20c20, "sdiv x0, x1, x2"),
+            (0x1ac20820, "udiv w0, w1, w2"),
+            (0x9b027c20, "mul x0, x1, x2"),
+            (0x9ba20c20, "umaddl x0, w1, w2, x3"),
+            (0x9b020c20, "madd x0, x1, x2, x3"),
+            (0x1b028c20, "msub w0, w1, w2, w3"),
+            (0xf9400020, "ldr x0, [x1]"),
+            (0xf9400420, "ldr x0, [x1, #8]"),
+            (0xb9000fe0, "str w0, [sp, #12]"),
+            (0xf85f8c20, "ldr x0, [x1, #-8]!"),
+            (0xf80107e0, "str x0, [sp], #16"),
+            (0xfd400820, "ldr d0, [x1, #16]"),
+            (0x3d800be1, "str q1, [sp, #32]"),
+            (0xbc404402, "ldr s2, [x0], #4"),
+            (0x39400020, "ldrb w0, [x1]"),
+            (0x38401c20, "ldrb w0, [x1, #1]!"),
+            (0x39000fe0, "strb w0, [sp, #3]"),
+            (0x79400420, "ldrh w0, [x1, #2]"),
+            (0x78002462, "strh w2, [x3], #2"),
+            (0xb9800420, "ldrsw x0, [x1, #4]"),
+            (0x39800020, "ldrsb x0, [x1]"),
+            (0x39c00020, "ldrsb w0, [x1]"),
+            (0x79800c62, "ldrsh x2, [x3, #6]"),
+            (0xa9bf7bfd, "stp x29, x30, [sp, #-16]!"),
+            (0xa8c17bfd, "ldp x29, x30, [sp], #16"),
+            (0x29010440, "stp w0, w1, [x2, #8]"),
+            (0x6d400400, "ldp d0, d1, [x0]"),
+            (0xadbf07e0, "stp q0, q1, [sp, #-32]!"),
+            (0x2d7f0c22, "ldp s2, s3, [x1, #-8]"),
+            (0x885ffc01, "ldaxr w1, [x0]"),
+            (0x8802fc03, "stlxr w2, w3, [x0]"),
+            (0xc8dffc20, "ldar x0, [x1]"),
+            (0x889ffc20, "stlr w0, [x1]"),
+            (0xf8e00041, "ldaddal x0, x1, [x2]"),
+            (0xb8a08041, "swpa w0, w1, [x2]"),
+            (0xc8a0fc41, "casl x0, x1, [x2]"),
+            (0xb82013e1, "ldclr w0, w1, [sp]"),
+            (0x1e604020, "fmov d0, d1"),
+            (0x9e660020, "fmov x0, d1"),
+            (0x1e270020, "fmov s0, w1"),
+            (0x1e622820, "fadd d0, d1, d2"),
+            (0x1e223820, "fsub s0, s1, s2"),
+            (0x1e620820, "fmul d0, d1, d2"),
+            (0x1e221820, "fdiv s0, s1, s2"),
+            (0x1e628820, "fnmul d0, d1, d2"),
+            (0x1e614020, "fneg d0, d1"),
+            (0x1e60c020, "fabs d0, d1"),
+            (0x1e21c020, "fsqrt s0, s1"),
+            (0x1e612000, "fcmp d0, d1"),
+            (0x1e602008, "fcmp d0, #0.0"),
+            (0x1e202018, "fcmpe s0, #0.0"),
+            (0x1f420c20, "fmadd d0, d1, d2, d3"),
+            (0x1f028c20, "fmsub s0, s1, s2, s3"),
+            (0x1f620c20, "fnmadd d0, d1, d2, d3"),
+            (0x9e620020, "scvtf d0, x1"),
+            (0x1e230020, "ucvtf s0, w1"),
+            (0x9a820020, "csel x0, x1, x2, eq"),
+            (0x1a9fc0a4, "csel w4, w5, wzr, gt"),
+            (0x1a9f07e0, "cset w0, ne"),
+            (0x9a9f27e3, "cset x3, lo"),
+            (0x9a820420, "csinc x0, x1, x2, eq"),
+            (0x5a821020, "csinv w0, w1, w2, ne"),
+            (0xda82b420, "csneg x0, x1, x2, lt"),
+            (0xd503201f, "nop"),
+            (0x14000004, "b .L1010"),
+            (0x97fffc00, "bl .L0"),
+            (0x54ffffe1, "b.ne .Lffc"),
+            (0x54000003, "b.lo .L1000"),
+            (0x54000022, "b.hs .L1004"),
+            (0x54000044, "b.mi .L1008"),
+            (0x54000046, "b.vs .L1008"),
+            (0x54000047, "b.vc .L1008"),
+            (0x54000049, "b.ls .L1008"),
+            (0xb5000040, "cbnz x0, .L1008"),
+            (0xb4000040, "cbz x0, .L1008"),
+            (0x34ffffe3, "cbz w3, .Lffc"),
+            (0x36180040, "tbz w0, #3, .L1008"),
+            (0xb7ffffc1, "tbnz x1, #63, .Lff8"),
+            (0xd65f03c0, "ret"),
+            (0xd65f0020, "ret x1"),
+            (0xd61f0040, "br x2"),
+            (0xd63f0060, "blr x3"),
+            (0x58000040, "ldr x0, .L1008"),
+            (0x98000040, "ldrsw x0, .L1008"),
+            (0x10000080, "adr x0, .L1010"),
+            (0xb0000001, "adrp x1, .L2000"),
+        ]);
+    }
+
+    #[test]
+    fn decode_forms() {
+        check(
+            &[
+                0x91400420, // add x0, x1, #1, lsl #12
+                0xd2a24680, // mov x0, #0x12340000
+                0x92800000, // mov x0, #-1
+                0x92a00000, // movn x0, #0, lsl #16
+                0xd2a00000, // movz x0, #0, lsl #16
+                0x129fffe0, // movn w0, #0xffff
+                0xf85fd020, // ldur x0, [x1, #-3]
+                0x1e202018, // fcmpe s0, #0.0
+                0x92703c20, // and x0, x1, #0xffff0000
+                0x92103c20, // and x0, x1, #0xffff0000ffff0000
+            ],
+            expect![[r#"
+                Instruction { opcode: Add, operand: [Reg(Reg(General { ty: X, n: 0 })), Reg(Reg(General { ty: X, n: 1 })), Imm { imm: 1, shift: Some((12, Lsl)) }] }
+                Instruction { opcode: Mov, operand: [Reg(Reg(General { ty: X, n: 0 })), Imm { imm: 305397760, shift: None }] }
+                Instruction { opcode: Mov, operand: [Reg(Reg(General { ty: X, n: 0 })), Imm { imm: -1, shift: None }] }
+                Instruction { opcode: Mvn, operand: [Reg(Reg(General { ty: X, n: 0 })), Imm { imm: 0, shift: Some((16, Lsl)) }] }
+                Instruction { opcode: Mov, operand: [Reg(Reg(General { ty: X, n: 0 })), Imm { imm: 0, shift: Some((16, Lsl)) }] }
+                Instruction { opcode: Mvn, operand: [Reg(Reg(General { ty: W, n: 0 })), Imm { imm: 65535, shift: None }] }
+                Instruction { opcode: Ldr, operand: [Reg(Reg(General { ty: X, n: 0 })), Addressing(Offset { offset: -3, reg: General { ty: X, n: 1 }, index: None })] }
+                Instruction { opcode: Fcmpe, operand: [Reg(Reg(General { ty: S, n: 0 })), Imm { imm: 0, shift: None }] }
+                Instruction { opcode: And, operand: [Reg(Reg(General { ty: X, n: 0 })), Reg(Reg(General { ty: X, n: 1 })), Imm { imm: 4294901760, shift: None }] }
+                Instruction { opcode: And, operand: [Reg(Reg(General { ty: X, n: 0 })), Reg(Reg(General { ty: X, n: 1 })), Imm { imm: -281470681808896, shift: None }] }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        check(
+            &[
+                0x00000000, // udf #0
+                0x1e6f1000, // fmov d0, #1.5
+                0x9200fc20, // reserved logical immediate
+                0x3f5a101f, // fmadd with S set
+                0xbfea4aad, // fnmadd with M set
+                0x1e60e000, // fcmp with op set
+                0x1e602001, // fcmp with opcode2 set
+                0x1e6700f5, // fmov between d and w
+                0x3ade29c7, // asr with S set
+                0x53307ca0, // ubfm w with immr of 48
+                0xc8a0089b, // cas with Rt2 set
+                0x8840fc01, // ldaxr with Rs set
+                0xc807fd27, // stlxr with Rs the same as Rt
+                0xf85705ce, // ldr x14, [x14], #-144
+                0xa9400020, // ldp x0, x0, [x1]
+                0xa8c17bbd, // ldp x29, x30, [x29], #16
+            ],
+            expect![[r#"
+                0x1000: unknown encoding (0x00000000)
+                0x1000: unsupported floating-point immediate (0x1e6f1000)
+                0x1000: unknown encoding (0x9200fc20)
+                0x1000: unknown encoding (0x3f5a101f)
+                0x1000: unknown encoding (0xbfea4aad)
+                0x1000: unknown encoding (0x1e60e000)
+                0x1000: unknown encoding (0x1e602001)
+                0x1000: unknown encoding (0x1e6700f5)
+                0x1000: unknown encoding (0x3ade29c7)
+                0x1000: unknown encoding (0x53307ca0)
+                0x1000: unknown encoding (0xc8a0089b)
+                0x1000: unknown encoding (0x8840fc01)
+                0x1000: unknown encoding (0xc807fd27)
+                0x1000: unknown encoding (0xf85705ce)
+                0x1000: unknown encoding (0xa9400020)
+                0x1000: unknown encoding (0xa8c17bbd)
+            "#]],
+        );
+        let res = decode_bytes(&[0x1f, 0x20, 0x03, 0xd5, 0xc0, 0x03], 0x400000);
+        expect![[r#"
+            [
+                Ok(
+                    Instruction {
+                        opcode: Nop,
+                        operand: [],
+                    },
+                ),
+                Err(
+                    DecodeError {
+                        address: 4194308,
+                        word: 960,
+                        kind: Truncated,
+                    },
+                ),
+            ]
+        "#]]
+        .assert_debug_eq(&res);
+    }
+}
@@ -0,0 +1,711 @@
+//! Reading of AArch64 ELF relocatable objects back into assembly, so that
+//! `.o` build artifacts can be translated like compiler output.
+//!
+//! The object is turned into GNU as text and parsed with [`armparser`], so
+//! the result is the same [`SourceLine`](armparser::SourceLine) model a `.s`
+//! file gives. Code is decoded with [`decode`](crate::decode), and
+//! relocations become symbolic operands: an `R_AARCH64_ADD_ABS_LO12_NC`
+//! against `buf` makes the `add` read `add x0, x0, :lo12:buf`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use armparser::generated::Generated;
+use asm_inst::arm64::{Addressing, Opcode, Operand, RelocOperator};
+use object::elf;
+use object::read::elf::{ElfFile64, ElfSection64, FileHeader};
+use object::{
+    Endianness, Object, ObjectSection, ObjectSymbol, RelocationFlags, RelocationTarget,
+    SectionIndex, SectionKind, SymbolKind, SymbolSection,
+};
+
+use crate::decode::{decode, label_at, DecodeErrorKind};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ElfError {
+    #[error("invalid object file: {0}")]
+    Object(#[from] object::Error),
+    #[error("not an AArch64 relocatable object")]
+    NotAarch64Object,
+}
+
+/// Where a line of the disassembly came from in the object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Origin {
+    /// Index of the section in the object
+    pub section: usize,
+    /// Offset in the section
+    pub offset: u64,
+    /// The instruction word, for decoded instructions
+    pub encoding: Option<u32>,
+}
+
+/// An object converted to assembly text.
+pub type Disassembly = Generated<Origin>;
+
+/// Converts the `.text`, `.data`, `.rodata` and `.bss` sections of an
+/// AArch64 relocatable object to assembly.
+///
+/// Symbols keep their binding, type and size. Sections are laid out one
+/// after the other to give every location an address, and places that are
+/// referred to without a symbol get a [`label_at`] label. Words that don't
+/// decode are kept as `.inst`, and relocations that can't be written as an
+/// operand as `.reloc`.
+pub fn disassemble(data: &[u8]) -> Result<Disassembly, ElfError> {
+    let file = ElfFile64::<Endianness>::parse(data)?;
+    let header = file.elf_header();
+    let endian = file.endian();
+    if header.e_machine(endian) != elf::EM_AARCH64 || header.e_type(endian) != elf::ET_REL {
+        return Err(ElfError::NotAarch64Object);
+    }
+
+    let mut bases = BTreeMap::new();
+    let mut end = 0u64;
+    let sections = file
+        .sections()
+        .filter(|s| is_walked(s.name().unwrap_or("")))
+        .collect::<Vec<_>>();
+    for section in &sections {
+        let base = end.next_multiple_of(section.align().max(1));
+        bases.insert(section.index().0, base);
+        end = base + section.size();
+    }
+
+    let mut object = Layout {
+        bases,
+        symbols: BTreeMap::new(),
+        mapping: BTreeMap::new(),
+        labels: BTreeMap::new(),
+        values: BTreeMap::new(),
+    };
+    for symbol in file.symbols() {
+        let (SymbolSection::Section(index), Ok(name)) = (symbol.section(), symbol.name()) else {
+            continue;
+        };
+        let Some(address) = object.address(index, symbol.address()) else {
+            continue;
+        };
+        match (name.strip_prefix('$'), symbol.kind()) {
+            // `$x` and `$d` mark where code and data start
+            (Some(mapping), _) => {
+                object.mapping.insert(address, mapping.starts_with('d'));
+            }
+            (None, SymbolKind::Section | SymbolKind::File) => {}
+            (None, kind) => {
+                let symbols = object.symbols.entry(address).or_default();
+                symbols.push(Symbol {
+                    name: name.to_string(),
+                    global: symbol.is_global(),
+                    weak: symbol.is_weak(),
+                    kind: match kind {
+                        SymbolKind::Text => Some("%function"),
+                        SymbolKind::Data => Some("%object"),
+                        SymbolKind::Tls => Some("%tls_object"),
+                        _ => None,
+                    },
+                    size: symbol.size(),
+                });
+                // globals name the place when there is a choice
+                symbols.sort_by_key(|s| !s.global);
+            }
+        }
+    }
+
+    // data relocations are found first, as a place inside one may be named
+    // before the walk reaches it
+    for section in &sections {
+        let base = object.bases[&section.index().0];
+        let flags = section.elf_section_header().sh_flags.get(endian);
+        let code = flags & u64::from(elf::SHF_EXECINSTR) != 0;
+        for (offset, reloc) in section.relocations() {
+            let RelocationFlags::Elf { r_type } = reloc.flags() else {
+                continue;
+            };
+            if let Some((_, len, _)) = data_directive(r_type) {
+                if object.is_data(base, base + offset, code) {
+                    object.values.insert(base + offset, len);
+                }
+            }
+        }
+    }
+
+    // the first pass finds the places that need a label, as branches may
+    // go backwards
+    let mut out = Output::default();
+    for _ in 0..2 {
+        out = Output::default();
+        for section in &sections {
+            object.section(&file, section, &mut out)?;
+        }
+    }
+    // labels wanted after the last section, like a branch past the end
+    let labels = std::mem::take(&mut object.labels);
+    for (address, name) in labels.range(end..) {
+        out.line(
+            format!("{name}:"),
+            Origin::at(section_of(&object, *address), 0),
+        );
+    }
+    Ok(Disassembly {
+        text: out.text.join("\n"),
+        origins: out.origins,
+    })
+}
+
+fn section_of(object: &Layout, address: u64) -> usize {
+    (object.bases.iter())
+        .rfind(|(_, base)| **base <= address)
+        .map_or(0, |(index, _)| *index)
+}
+
+fn is_walked(name: &str) -> bool {
+    ["text", "data", "rodata", "bss"].iter().any(|kind| {
+        name.strip_prefix('.')
+            .and_then(|name| name.strip_prefix(kind))
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+struct Symbol {
+    name: String,
+    global: bool,
+    weak: bool,
+    kind: Option<&'static str>,
+    size: u64,
+}
+
+struct Layout {
+    /// Address each walked section is laid out at
+    bases: BTreeMap<usize, u64>,
+    symbols: BTreeMap<u64, Vec<Symbol>>,
+    /// Addresses where data (`true`) or code (`false`) starts
+    mapping: BTreeMap<u64, bool>,
+    /// Labels to define for places that have no symbol
+    labels: BTreeMap<u64, String>,
+    /// Address and size of the data relocations written as one value
+    values: BTreeMap<u64, u64>,
+}
+
+#[derive(Default)]
+struct Output {
+    text: Vec<String>,
+    origins: Vec<Origin>,
+}
+
+impl Output {
+    fn line(&mut self, text: String, origin: Origin) {
+        self.text.push(text);
+        self.origins.push(origin);
+    }
+}
+
+impl Origin {
+    fn at(section: usize, offset: u64) -> Self {
+        Origin {
+            section,
+            offset,
+            encoding: None,
+        }
+    }
+}
+
+/// A relocation, with its target written as an expression.
+struct Reloc {
+    r_type: u32,
+    target: String,
+}
+
+impl Layout {
+    fn address(&self, section: SectionIndex, offset: u64) -> Option<u64> {
+        Some(self.bases.get(&section.0)? + offset)
+    }
+
+    /// Whether `address`, in the section laid out at `base`, holds data.
+    fn is_data(&self, base: u64, address: u64, code: bool) -> bool {
+        !code || (self.mapping.range(base..=address).next_back()).is_some_and(|(_, d)| *d)
+    }
+
+    /// The name for `address`: a symbol defined there, or a label that will
+    /// be defined.
+    ///
+    /// No line starts inside the value of a data relocation, so a place there
+    /// is named from the closest symbol or label before it in its section.
+    fn name_at(&mut self, address: u64) -> String {
+        if let Some(symbol) = self.symbols.get(&address).and_then(|s| s.first()) {
+            return symbol.name.clone();
+        }
+        let value = (self.values.range(..address).next_back())
+            .filter(|(start, len)| address < *start + *len)
+            .map(|(start, _)| *start);
+        let Some(start) = value else {
+            return self.label(address);
+        };
+        let base = self.bases[&section_of(self, address)];
+        let symbol = (self.symbols.range(base..address).next_back())
+            .map(|(at, symbols)| (*at, symbols[0].name.clone()));
+        let label =
+            (self.labels.range(base..address).next_back()).map(|(at, label)| (*at, label.clone()));
+        let (at, name) = match symbol.into_iter().chain(label).max_by_key(|(at, _)| *at) {
+            Some(before) => before,
+            None => (start, self.label(start)),
+        };
+        format!("{name}+{}", address - at)
+    }
+
+    fn label(&mut self, address: u64) -> String {
+        self.labels
+            .entry(address)
+            .or_insert_with(|| label_at(address))
+            .clone()
+    }
+
+    fn section(
+        &mut self,
+        file: &ElfFile64<Endianness>,
+        section: &ElfSection64<Endianness>,
+        out: &mut Output,
+    ) -> Result<(), ElfError> {
+        let index = section.index();
+        let name = section.name()?;
+        let base = self.bases[&index.0];
+        let size = section.size();
+        let header = section.elf_section_header();
+        let flags = header.sh_flags.get(file.endian());
+        let nobits = section.kind() == SectionKind::UninitializedData;
+
+        let mut directive = format!("\t.section\t{name},\"{}\"", section_flags(flags));
+        directive += if nobits { ",@nobits" } else { ",@progbits" };
+        if flags & u64::from(elf::SHF_MERGE) != 0 {
+            let _ = write!(directive, ",{}", header.sh_entsize.get(file.endian()));
+        }
+        out.line(directive, Origin::at(index.0, 0));
+        if section.align() > 1 {
+            let align = format!("\t.p2align\t{}", section.align().trailing_zeros());
+            out.line(align, Origin::at(index.0, 0));
+        }
+
+        let mut relocs = BTreeMap::new();
+        for (offset, reloc) in section.relocations() {
+            let RelocationFlags::Elf { r_type } = reloc.flags() else {
+                continue;
+            };
+            let RelocationTarget::Symbol(symbol) = reloc.target() else {
+                continue;
+            };
+            let symbol = file.symbol_by_index(symbol)?;
+            let (name, addend) = match symbol.kind() {
+                // a place in a section, which gets the name of what is there
+                SymbolKind::Section => match symbol.section_index() {
+                    Some(target) => match self.address(target, reloc.addend() as u64) {
+                        Some(address) => (self.name_at(address), 0),
+                        None => {
+                            let name = file.section_by_index(target)?.name()?;
+                            (name.to_string(), reloc.addend())
+                        }
+                    },
+                    None => continue,
+                },
+                _ => (symbol.name()?.to_string(), reloc.addend()),
+            };
+            let target = match addend {
+                0 => name,
+                addend if addend > 0 => format!("{name}+{addend}"),
+                addend => format!("{name}{addend}"),
+            };
+            relocs.insert(offset, Reloc { r_type, target });
+        }
+
+        let data = if nobits { &[][..] } else { section.data()? };
+        let code = flags & u64::from(elf::SHF_EXECINSTR) != 0;
+        let mut offset = 0;
+        while offset < size {
+            let address = base + offset;
+            self.symbol_lines(address, index.0, offset, out);
+            let is_data = self.is_data(base, address, code);
+            // stop short of the next place that needs a line of its own
+            let next = [
+                self.symbols.range(address + 1..).next().map(|(a, _)| *a),
+                self.labels.range(address + 1..).next().map(|(a, _)| *a),
+                self.mapping.range(address + 1..).next().map(|(a, _)| *a),
+                relocs.range(offset + 1..).next().map(|(o, _)| base + o),
+            ]
+            .into_iter()
+            .flatten()
+            .fold(base + size, u64::min);
+            let origin = Origin::at(index.0, offset);
+
+            if let Some(reloc) = relocs.get(&offset) {
+                let used = match is_data {
+                    true => data_reloc(reloc, out, origin),
+                    false => None,
+                };
+                if let Some(len) = used {
+                    offset += len;
+                    continue;
+                }
+            }
+            if nobits {
+                out.line(format!("\t.zero\t{}", next - address), origin);
+                offset = next - base;
+            } else if !is_data && offset % 4 == 0 && next - address >= 4 {
+                let word = u32::from_le_bytes(data[offset as usize..][..4].try_into().unwrap());
+                let reloc = relocs.get(&offset);
+                for line in self.instruction(word, address, reloc) {
+                    out.line(
+                        line,
+                        Origin {
+                            encoding: Some(word),
+                            ..origin
+                        },
+                    );
+                }
+                offset += 4;
+            } else {
+                let len = (next - address).min(16);
+                let bytes = data[offset as usize..][..len as usize]
+                    .iter()
+                    .map(|b| format!("{b:#04x}"))
+                    .collect::<Vec<_>>();
+                if let Some(reloc) = relocs.get(&offset) {
+                    out.line(raw_reloc(reloc), origin);
+                }
+                out.line(format!("\t.byte\t{}", bytes.join(", ")), origin);
+                offset += len;
+            }
+        }
+        Ok(())
+    }
+
+    /// Binding, type and label lines of the symbols at `address`, and the
+    /// labels wanted there.
+    fn symbol_lines(&mut self, address: u64, section: usize, offset: u64, out: &mut Output) {
+        let origin = Origin::at(section, offset);
+        for symbol in self.symbols.get(&address).into_iter().flatten() {
+            let name = &symbol.name;
+            if symbol.weak {
+                out.line(format!("\t.weak\t{name}"), origin);
+            } else if symbol.global {
+                out.line(format!("\t.globl\t{name}"), origin);
+            }
+            if let Some(kind) = symbol.kind {
+                out.line(format!("\t.type\t{name}, {kind}"), origin);
+            }
+            out.line(format!("{name}:"), origin);
+            if symbol.size != 0 {
+                out.line(format!("\t.size\t{name}, {}", symbol.size), origin);
+            }
+        }
+        if let Some(label) = self.labels.get(&address) {
+            out.line(format!("{label}:"), origin);
+        }
+    }
+
+    /// The instruction `word` as text, with the operand `reloc` applies to
+    /// written symbolically.
+    fn instruction(&mut self, word: u32, address: u64, reloc: Option<&Reloc>) -> Vec<String> {
+        let inst = match decode(word, address) {
+            Ok(inst) => inst,
+            Err(e) => {
+                let note = match e.kind {
+                    DecodeErrorKind::Unsupported(name) => format!("\t// {name}"),
+                    _ => String::new(),
+                };
+                let inst = format!("\t.inst\t{word:#010x}{note}");
+                return reloc.map(raw_reloc).into_iter().chain([inst]).collect();
+            }
+        };
+        let mut operands = inst
+            .operand
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        for (operand, text) in inst.operand.iter().zip(&mut operands) {
+            // decoded branch targets refer to labels that must exist
+            if let Operand::Label(_) = operand {
+                if reloc.is_none_or(|r| code_field(r.r_type).is_none()) {
+                    let target = text
+                        .strip_prefix(".L")
+                        .and_then(|a| u64::from_str_radix(a, 16).ok());
+                    *text = self.name_at(target.expect("Label from the decoder"));
+                }
+            }
+        }
+        let mut mnemonic = inst.mnemonic();
+        let mut lines = Vec::new();
+        match reloc.map(|r| (r, code_field(r.r_type))) {
+            None => {}
+            Some((reloc, Some(field))) => {
+                let applied = inst
+                    .operand
+                    .iter()
+                    .zip(&mut operands)
+                    .find_map(|(operand, text)| {
+                        match (field, operand) {
+                            (Field::Target(None), Operand::Label(_)) => {
+                                text.clone_from(&reloc.target);
+                            }
+                            (Field::Target(Some(modifier)), Operand::Label(_)) => {
+                                *text = format!("{modifier}{}", reloc.target);
+                            }
+                            (Field::Imm(modifier), Operand::Imm { .. }) => {
+                                *text = format!("#{modifier}{}", reloc.target);
+                                // only `movz` and `movn` take a relocated immediate,
+                                // told apart by the opc bits as both decode as `mov`
+                                mnemonic = match (&inst.opcode, word >> 29 & 0b11) {
+                                    (Opcode::Mov | Opcode::Mvn, 0b00) => "movn".to_string(),
+                                    (Opcode::Mov | Opcode::Mvn, _) => "movz".to_string(),
+                                    _ => mnemonic.clone(),
+                                };
+                            }
+                            (Field::Offset(modifier), Operand::Addressing(addressing)) => {
+                                let base = match addressing {
+                                    Addressing::BaseRegister(reg) => reg,
+                                    Addressing::Offset { reg, .. } => reg,
+                                    Addressing::Reloc { reg, .. } => reg,
+                                };
+                                *text = format!("[{base}, {modifier}{}]", reloc.target);
+                            }
+                            _ => return None,
+                        }
+                        Some(())
+                    });
+                if applied.is_none() {
+                    lines.push(raw_reloc(reloc));
+                }
+            }
+            Some((reloc, None)) => lines.push(raw_reloc(reloc)),
+        }
+        let mut line = format!("\t{mnemonic}");
+        for (i, operand) in operands.iter().enumerate() {
+            line += if i == 0 { "\t" } else { ", " };
+            line += operand;
+        }
+        lines.push(line);
+        lines
+    }
+}
+
+/// The operand an instruction relocation fills in, with the relocation
+/// operator it is written with.
+#[derive(Clone, Copy)]
+enum Field {
+    /// A branch or address target
+    Target(Option<RelocOperator>),
+    /// An immediate, like that of `add` or `movz`
+    Imm(RelocOperator),
+    /// The offset of a load or store
+    Offset(RelocOperator),
+}
+
+fn code_field(r_type: u32) -> Option<Field> {
+    use RelocOperator::*;
+    let field = match r_type {
+        elf::R_AARCH64_CALL26
+        | elf::R_AARCH64_JUMP26
+        | elf::R_AARCH64_CONDBR19
+        | elf::R_AARCH64_TSTBR14
+        | elf::R_AARCH64_LD_PREL_LO19
+        | elf::R_AARCH64_ADR_PREL_LO21
+        | elf::R_AARCH64_ADR_PREL_PG_HI21 => Field::Target(None),
+        elf::R_AARCH64_ADR_PREL_PG_HI21_NC => Field::Target(Some(PgHi21Nc)),
+        elf::R_AARCH64_ADR_GOT_PAGE => Field::Target(Some(Got)),
+        elf::R_AARCH64_ADD_ABS_LO12_NC => Field::Imm(Lo12),
+        elf::R_AARCH64_LDST8_ABS_LO12_NC
+        | elf::R_AARCH64_LDST16_ABS_LO12_NC
+        | elf::R_AARCH64_LDST32_ABS_LO12_NC
+        | elf::R_AARCH64_LDST64_ABS_LO12_NC
+        | elf::R_AARCH64_LDST128_ABS_LO12_NC => Field::Offset(Lo12),
+        elf::R_AARCH64_LD64_GOT_LO12_NC => Field::Offset(GotLo12),
+        elf::R_AARCH64_MOVW_UABS_G0 => Field::Imm(AbsG0),
+        elf::R_AARCH64_MOVW_UABS_G0_NC => Field::Imm(AbsG0Nc),
+        elf::R_AARCH64_MOVW_UABS_G1 => Field::Imm(AbsG1),
+        elf::R_AARCH64_MOVW_UABS_G1_NC => Field::Imm(AbsG1Nc),
+        elf::R_AARCH64_MOVW_UABS_G2 => Field::Imm(AbsG2),
+        elf::R_AARCH64_MOVW_UABS_G2_NC => Field::Imm(AbsG2Nc),
+        elf::R_AARCH64_MOVW_UABS_G3 => Field::Imm(AbsG3),
+        elf::R_AARCH64_MOVW_SABS_G0 => Field::Imm(AbsG0S),
+        elf::R_AARCH64_MOVW_SABS_G1 => Field::Imm(AbsG1S),
+        elf::R_AARCH64_MOVW_SABS_G2 => Field::Imm(AbsG2S),
+        _ => return None,
+    };
+    Some(field)
+}
+
+/// The value directive a data relocation is written with, the number of
+/// bytes it covers and whether it is relative to the place.
+fn data_directive(r_type: u32) -> Option<(&'static str, u64, bool)> {
+    let directive = match r_type {
+        elf::R_AARCH64_ABS64 => (".quad", 8, false),
+        elf::R_AARCH64_ABS32 => (".word", 4, false),
+        elf::R_AARCH64_ABS16 => (".hword", 2, false),
+        elf::R_AARCH64_PREL64 => (".quad", 8, true),
+        elf::R_AARCH64_PREL32 => (".word", 4, true),
+        elf::R_AARCH64_PREL16 => (".hword", 2, true),
+        _ => return None,
+    };
+    Some(directive)
+}
+
+/// Writes a data relocation as a value directive, giving the number of
+/// bytes it covers.
+fn data_reloc(reloc: &Reloc, out: &mut Output, origin: Origin) -> Option<u64> {
+    let (directive, len, relative) = data_directive(reloc.r_type)?;
+    let value = match relative {
+        true => format!("{} - .", reloc.target),
+        false => reloc.target.clone(),
+    };
+    out.line(format!("\t{directive}\t{value}"), origin);
+    Some(len)
+}
+
+/// A `.reloc` directive for a relocation with no operand form, applying to
+/// the next line.
+fn raw_reloc(reloc: &Reloc) -> String {
+    let name = match reloc_name(reloc.r_type) {
+        Some(name) => name.to_string(),
+        None => reloc.r_type.to_string(),
+    };
+    format!("\t.reloc\t., {name}, {}", reloc.target)
+}
+
+/// The name of a static relocation type, as `.reloc` takes it.
+fn reloc_name(r_type: u32) -> Option<&'static str> {
+    macro_rules! names {
+        ($($name:ident)*) => {
+            match r_type {
+                $(elf::$name => Some(stringify!($name)),)*
+                _ => None,
+            }
+        };
+    }
+    names! {
+        R_AARCH64_ABS64 R_AARCH64_ABS32 R_AARCH64_ABS16 R_AARCH64_PREL64 R_AARCH64_PREL32
+        R_AARCH64_PREL16 R_AARCH64_MOVW_UABS_G0 R_AARCH64_MOVW_UABS_G0_NC R_AARCH64_MOVW_UABS_G1
+        R_AARCH64_MOVW_UABS_G1_NC R_AARCH64_MOVW_UABS_G2 R_AARCH64_MOVW_UABS_G2_NC
+        R_AARCH64_MOVW_UABS_G3 R_AARCH64_MOVW_SABS_G0 R_AARCH64_MOVW_SABS_G1 R_AARCH64_MOVW_SABS_G2
+        R_AARCH64_LD_PREL_LO19 R_AARCH64_ADR_PREL_LO21 R_AARCH64_ADR_PREL_PG_HI21
+        R_AARCH64_ADR_PREL_PG_HI21_NC R_AARCH64_ADD_ABS_LO12_NC R_AARCH64_LDST8_ABS_LO12_NC
+        R_AARCH64_TSTBR14 R_AARCH64_CONDBR19 R_AARCH64_JUMP26 R_AARCH64_CALL26
+        R_AARCH64_LDST16_ABS_LO12_NC R_AARCH64_LDST32_ABS_LO12_NC R_AARCH64_LDST64_ABS_LO12_NC
+        R_AARCH64_MOVW_PREL_G0 R_AARCH64_MOVW_PREL_G0_NC R_AARCH64_MOVW_PREL_G1
+        R_AARCH64_MOVW_PREL_G1_NC R_AARCH64_MOVW_PREL_G2 R_AARCH64_MOVW_PREL_G2_NC
+        R_AARCH64_MOVW_PREL_G3 R_AARCH64_LDST128_ABS_LO12_NC R_AARCH64_MOVW_GOTOFF_G0
+        R_AARCH64_MOVW_GOTOFF_G0_NC R_AARCH64_MOVW_GOTOFF_G1 R_AARCH64_MOVW_GOTOFF_G1_NC
+        R_AARCH64_MOVW_GOTOFF_G2 R_AARCH64_MOVW_GOTOFF_G2_NC R_AARCH64_MOVW_GOTOFF_G3
+        R_AARCH64_GOTREL64 R_AARCH64_GOTREL32 R_AARCH64_GOT_LD_PREL19 R_AARCH64_LD64_GOTOFF_LO15
+        R_AARCH64_ADR_GOT_PAGE R_AARCH64_LD64_GOT_LO12_NC R_AARCH64_LD64_GOTPAGE_LO15
+        R_AARCH64_TLSGD_ADR_PREL21 R_AARCH64_TLSGD_ADR_PAGE21 R_AARCH64_TLSGD_ADD_LO12_NC
+        R_AARCH64_TLSGD_MOVW_G1 R_AARCH64_TLSGD_MOVW_G0_NC R_AARCH64_TLSLD_ADR_PREL21
+        R_AARCH64_TLSLD_ADR_PAGE21 R_AARCH64_TLSLD_ADD_LO12_NC R_AARCH64_TLSLD_MOVW_G1
+        R_AARCH64_TLSLD_MOVW_G0_NC R_AARCH64_TLSLD_LD_PREL19 R_AARCH64_TLSLD_MOVW_DTPREL_G2
+        R_AARCH64_TLSLD_MOVW_DTPREL_G1 R_AARCH64_TLSLD_MOVW_DTPREL_G1_NC
+        R_AARCH64_TLSLD_MOVW_DTPREL_G0 R_AARCH64_TLSLD_MOVW_DTPREL_G0_NC
+        R_AARCH64_TLSLD_ADD_DTPREL_HI12 R_AARCH64_TLSLD_ADD_DTPREL_LO12
+        R_AARCH64_TLSLD_ADD_DTPREL_LO12_NC R_AARCH64_TLSLD_LDST8_DTPREL_LO12
+        R_AARCH64_TLSLD_LDST8_DTPREL_LO12_NC R_AARCH64_TLSLD_LDST16_DTPREL_LO12
+        R_AARCH64_TLSLD_LDST16_DTPREL_LO12_NC R_AARCH64_TLSLD_LDST32_DTPREL_LO12
+        R_AARCH64_TLSLD_LDST32_DTPREL_LO12_NC R_AARCH64_TLSLD_LDST64_DTPREL_LO12
+        R_AARCH64_TLSLD_LDST64_DTPREL_LO12_NC R_AARCH64_TLSIE_MOVW_GOTTPREL_G1
+        R_AARCH64_TLSIE_MOVW_GOTTPREL_G0_NC R_AARCH64_TLSIE_ADR_GOTTPREL_PAGE21
+        R_AARCH64_TLSIE_LD64_GOTTPREL_LO12_NC R_AARCH64_TLSIE_LD_GOTTPREL_PREL19
+        R_AARCH64_TLSLE_MOVW_TPREL_G2 R_AARCH64_TLSLE_MOVW_TPREL_G1 R_AARCH64_TLSLE_MOVW_TPREL_G1_NC
+        R_AARCH64_TLSLE_MOVW_TPREL_G0 R_AARCH64_TLSLE_MOVW_TPREL_G0_NC
+        R_AARCH64_TLSLE_ADD_TPREL_HI12 R_AARCH64_TLSLE_ADD_TPREL_LO12
+        R_AARCH64_TLSLE_ADD_TPREL_LO12_NC R_AARCH64_TLSLE_LDST8_TPREL_LO12
+        R_AARCH64_TLSLE_LDST8_TPREL_LO12_NC R_AARCH64_TLSLE_LDST16_TPREL_LO12
+        R_AARCH64_TLSLE_LDST16_TPREL_LO12_NC R_AARCH64_TLSLE_LDST32_TPREL_LO12
+        R_AARCH64_TLSLE_LDST32_TPREL_LO12_NC R_AARCH64_TLSLE_LDST64_TPREL_LO12
+        R_AARCH64_TLSLE_LDST64_TPREL_LO12_NC R_AARCH64_TLSDESC_LD_PREL19
+        R_AARCH64_TLSDESC_ADR_PREL21 R_AARCH64_TLSDESC_ADR_PAGE21 R_AARCH64_TLSDESC_LD64_LO12
+        R_AARCH64_TLSDESC_ADD_LO12 R_AARCH64_TLSDESC_OFF_G1 R_AARCH64_TLSDESC_OFF_G0_NC
+        R_AARCH64_TLSDESC_LDR R_AARCH64_TLSDESC_ADD R_AARCH64_TLSDESC_CALL
+        R_AARCH64_TLSLE_LDST128_TPREL_LO12 R_AARCH64_TLSLE_LDST128_TPREL_LO12_NC
+        R_AARCH64_TLSLD_LDST128_DTPREL_LO12 R_AARCH64_TLSLD_LDST128_DTPREL_LO12_NC
+    }
+}
+
+/// The flags string of `.section`, like `"ax"`.
+fn section_flags(flags: u64) -> String {
+    [
+        (elf::SHF_ALLOC, 'a'),
+        (elf::SHF_WRITE, 'w'),
+        (elf::SHF_EXECINSTR, 'x'),
+        (elf::SHF_MERGE, 'M'),
+        (elf::SHF_STRINGS, 'S'),
+        (elf::SHF_TLS, 'T'),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags & u64::from(*flag) != 0)
+    .map(|(_, c)| c)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn disassemble_object() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/asm_examples/reloc.o");
+        let disassembly = disassemble(&std::fs::read(path).unwrap()).unwrap();
+        expect![[r#"
+            	.section	.text,"ax",@progbits
+            	.p2align	2
+            	.globl	main
+            	.type	main, %function
+            main:
+            	.size	main, 64
+            	stp	x29, x30, [sp, #-16]!
+            	adrp	x0, .L4c
+            	add	x0, x0, #:lo12:.L4c
+            	bl	puts
+            	adrp	x1, counter
+            	ldr	x2, [x1, :lo12:counter]
+            	cbnz	x2, .L30
+            	movz	x3, #:abs_g1:table
+            	movk	x3, #:abs_g0_nc:table
+            	movn	x5, #:abs_g0_s:table
+            	adrp	x4, :got:stdout
+            	ldr	x4, [x4, :got_lo12:stdout]
+            .L30:
+            	subs	x2, x2, #1
+            	b.ne	.L30
+            	ldp	x29, x30, [sp], #16
+            	b	helper
+            	.type	helper, %function
+            helper:
+            	.size	helper, 12
+            	mov	w0, wzr
+            	.reloc	., R_AARCH64_TLSLE_ADD_TPREL_LO12_NC, var
+            	add	x0, x0, #0
+            	ret
+            	.section	.rodata.str1.1,"aMS",@progbits,1
+            .L4c:
+            	.byte	0x68, 0x69, 0x00
+            	.section	.data,"aw",@progbits
+            	.p2align	3
+            	.globl	counter
+            	.type	counter, %object
+            counter:
+            	.size	counter, 8
+            	.byte	0x2a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+            table:
+            	.quad	main
+            	.word	helper - .
+            	.byte	0x01, 0x02, 0x03, 0x00
+            	.quad	main
+            	.quad	helper
+            	.quad	table+28
+            	.quad	.extra+4
+            	.section	.bss,"aw",@nobits
+            	.globl	buffer
+            	.type	buffer, %object
+            buffer:
+            	.size	buffer, 16
+            	.zero	16"#]]
+        .assert_eq(&disassembly.text);
+        let lines = armparser::parse_asm(&disassembly.text).unwrap();
+        assert_eq!(lines.len(), disassembly.origins.len());
+    }
+}
@@ -0,0 +1,427 @@
+//! Lowering of parsed A64 assembly into the typed [`arm64`] model that
+//! [`Arm64Translator`](crate::translate::Arm64Translator) consumes.
+
+use armparser::condition::Condition;
+use armparser::expr::RelocModifier;
+use armparser::immediate::Immediate;
+use armparser::register::{Register, RegisterType};
+use armparser::{
+    AddressMode, ExtendType, ExtendedRegister, Indirect, Instruction, Line, Offset, Operand,
+    ProcLoad, ShiftAmount, ShiftType, ShiftedRegister, SourceLine,
+};
+use asm_inst::arm64;
+
+/// A source line the arm64 model can't represent.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("line {line}: {message}: `{text}`")]
+pub struct LowerError {
+    /// 1-based line number
+    pub line: usize,
+    /// Source text of the line
+    pub text: String,
+    pub message: String,
+}
+
+/// Lowers every instruction of a parsed file, in source order. `src` is the
+/// text the lines were parsed from.
+pub fn lower(src: &str, lines: &[SourceLine]) -> Result<Vec<arm64::Instruction>, LowerError> {
+    lines
+        .iter()
+        .filter_map(|line| lower_line(src, line).transpose())
+        .collect()
+}
+
+/// Lowers one parsed line. Labels and directives have no counterpart in the
+/// model and give `None`.
+pub fn lower_line(src: &str, line: &SourceLine) -> Result<Option<arm64::Instruction>, LowerError> {
+    let res = match &line.line {
+        Line::Instruction(inst) => lower_instruction(inst).map(Some),
+        Line::Error(_) => Err("line did not parse".to_string()),
+        Line::Label(_) | Line::Directive(_) => Ok(None),
+    };
+    res.map_err(|message| LowerError {
+        line: line.loc.line,
+        text: src[line.loc.start..line.loc.end].trim_end().to_string(),
+        message,
+    })
+}
+
+fn lower_instruction(inst: &Instruction) -> Result<arm64::Instruction, String> {
+    let opcode = lower_opcode(inst.opcode.name(), inst.condition)?;
+    let operand = inst
+        .operands
+        .iter()
+        .map(|operand| match (&opcode, operand) {
+            // `fcmp d0, #0.0`, kept as the integer zero
+            (arm64::Opcode::Fcmp | arm64::Opcode::Fcmpe, Operand::Immediate(imm))
+                if imm.as_float() == Some(0.0) =>
+            {
+                Ok(arm64::Operand::Imm {
+                    imm: 0,
+                    shift: None,
+                })
+            }
+            _ => lower_operand(operand),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(arm64::Instruction { opcode, operand })
+}
+
+fn lower_opcode(name: &str, condition: Option<Condition>) -> Result<arm64::Opcode, String> {
+    use arm64::Opcode::*;
+    let opcode = match (name, condition) {
+        ("b", Some(cond)) => arm64::Opcode::branch_on(lower_condition(&cond)),
+        (_, Some(cond)) => {
+            return Err(format!(
+                "unsupported conditional instruction `{name}.{}`",
+                cond.name()
+            ))
+        }
+        ("add", None) => Add,
+        ("adds", None) => Adds,
+        ("sub", None) => Sub,
+        ("subs", None) => Subs,
+        ("mul", None) => Mul,
+        ("sdiv", None) => Div,
+        ("udiv", None) => Udiv,
+        ("umaddl", None) => Umaddl,
+        ("madd", None) => Madd,
+        ("msub", None) => Msub,
+        ("and", None) => And,
+        ("orr", None) => Or,
+        ("eor", None) => Xor,
+        ("lsl", None) => Lsl,
+        ("lsr", None) => Lsr,
+        ("asr", None) => Asr,
+        ("cmp", None) => Cmp,
+        ("mov", None) => Mov,
+        ("mvn", None) => Mvn,
+        ("movk", None) => Mvk,
+        ("movz", None) => Mov,
+        ("movn", None) => Mvn,
+        ("ldr", None) => Ldr,
+        ("str", None) => Str,
+        ("ldrb", None) => Ldrb,
+        ("ldrh", None) => Ldrh,
+        ("ldrsb", None) => Ldrsb,
+        ("ldrsh", None) => Ldrsh,
+        ("ldrsw", None) => Ldrsw,
+        ("strb", None) => Strb,
+        ("strh", None) => Strh,
+        ("ldp", None) => Ldp,
+        ("stp", None) => Stp,
+        ("adr", None) => Adr,
+        ("adrp", None) => Adrp,
+        ("b", None) => B,
+        ("bl", None) => Bl,
+        ("br", None) => Br,
+        ("blr", None) => Blr,
+        ("ret", None) => Ret,
+        ("cbz", None) => Cbz,
+        ("cbnz", None) => Cbnz,
+        ("tbz", None) => Tbz,
+        ("tbnz", None) => Tbnz,
+        ("csel", None) => Csel,
+        ("cset", None) => Cset,
+        ("csinc", None) => Csinc,
+        ("csinv", None) => Csinv,
+        ("csneg", None) => Csneg,
+        ("fmov", None) => Fmov,
+        ("ucvtf", None) => Ucvtf,
+        ("scvtf", None) => Scvtf,
+        ("fcmp", None) => Fcmp,
+        ("fcmpe", None) => Fcmpe,
+        ("fadd", None) => Fadd,
+        ("fsub", None) => Fsub,
+        ("fmul", None) => Fmul,
+        ("fdiv", None) => Fdiv,
+        ("fneg", None) => Fneg,
+        ("fabs", None) => Fabs,
+        ("fsqrt", None) => Fsqrt,
+        ("fmsub", None) => Fmsub,
+        ("fmadd", None) => Fmuladd,
+        ("fnmadd", None) => Fnmadd,
+        ("fnmul", None) => Fnmul,
+        ("ldaxr", None) => Ldaxr,
+        ("stlxr", None) => Stlxr,
+        ("ldar", None) => Ldar,
+        ("stlr", None) => Stlr,
+        ("nop", None) => Nop,
+        (_, None) => {
+            return lower_atomic(name).ok_or_else(|| format!("unsupported instruction `{name}`"))
+        }
+    };
+    Ok(opcode)
+}
+
+/// Reads an LSE atomic like `ldaddal`, whose suffix gives its ordering.
+fn lower_atomic(name: &str) -> Option<arm64::Opcode> {
+    use arm64::Opcode::*;
+    type Atomic = fn(arm64::Order) -> arm64::Opcode;
+    const ATOMICS: [(&str, Atomic); 10] = [
+        ("ldadd", Ldadd),
+        ("ldclr", Ldclr),
+        ("ldeor", Ldeor),
+        ("ldset", Ldset),
+        ("ldsmax", Ldsmax),
+        ("ldsmin", Ldsmin),
+        ("ldumax", Ldumax),
+        ("ldumin", Ldumin),
+        ("swp", Swp),
+        ("cas", Cas),
+    ];
+    ATOMICS.iter().find_map(|(base, opcode)| {
+        let order = match name.strip_prefix(base)? {
+            "" => arm64::Order::Relaxed,
+            "a" => arm64::Order::Acquire,
+            "l" => arm64::Order::Release,
+            "al" => arm64::Order::AcqRel,
+            _ => return None,
+        };
+        Some(opcode(order))
+    })
+}
+
+fn lower_operand(operand: &Operand) -> Result<arm64::Operand, String> {
+    let operand = match operand {
+        Operand::Register(reg) => arm64::Operand::Reg(arm64::Regoperand::Reg(lower_register(reg)?)),
+        Operand::ShiftedRegister(reg) => arm64::Operand::Reg(lower_shifted_register(reg)?),
+        Operand::ExtendedRegister(reg) => arm64::Operand::Reg(lower_extended_register(reg)?),
+        Operand::Immediate(imm) => arm64::Operand::Imm {
+            imm: lower_int(imm)?,
+            shift: None,
+        },
+        Operand::ShiftedImmediate(shifted) => match shifted.value.as_ref() {
+            Operand::Immediate(imm) => arm64::Operand::Imm {
+                imm: lower_int(imm)?,
+                shift: Some((lower_shift_amount(&shifted.lsl)?, arm64::Shift::Lsl)),
+            },
+            _ => return Err("unsupported relocation operand".to_string()),
+        },
+        Operand::LabelTarget(label) => arm64::Operand::Label(label.to_string()),
+        Operand::Indirect(indirect) => arm64::Operand::Addressing(lower_indirect(indirect)?),
+        Operand::ProcLoad(load) => arm64::Operand::Reloc(lower_reloc(load)?),
+        Operand::Address(_) => return Err("unsupported literal pool load".to_string()),
+        Operand::RegisterList(_) => return Err("unsupported register list".to_string()),
+        Operand::Condition(cond) => arm64::Operand::Cond(lower_condition(cond)),
+        Operand::Expr(_) => return Err("unsupported symbol expression".to_string()),
+    };
+    Ok(operand)
+}
+
+fn lower_condition(cond: &Condition) -> arm64::Cond {
+    use arm64::Cond;
+    match cond {
+        Condition::EQ => Cond::Eq,
+        Condition::NE => Cond::Ne,
+        Condition::HS => Cond::Hs,
+        Condition::LO => Cond::Lo,
+        Condition::MI => Cond::Mi,
+        Condition::PL => Cond::Pl,
+        Condition::VS => Cond::Vs,
+        Condition::VC => Cond::Vc,
+        Condition::HI => Cond::Hi,
+        Condition::LS => Cond::Ls,
+        Condition::GE => Cond::Ge,
+        Condition::LT => Cond::Lt,
+        Condition::GT => Cond::Gt,
+        Condition::LE => Cond::Le,
+        Condition::AL => Cond::Al,
+        Condition::NV => Cond::Nv,
+    }
+}
+
+fn lower_register(reg: &Register) -> Result<arm64::Register, String> {
+    use arm64::General;
+    let n = reg.reg_num;
+    let ty = match reg.reg_type {
+        // the model keeps the link register apart so `bl` and `ret` agree
+        RegisterType::Full if n == 30 => return Ok(arm64::Register::Special(arm64::Special::LR)),
+        RegisterType::Full => General::X,
+        RegisterType::Half => General::W,
+        RegisterType::Byte => General::B,
+        RegisterType::HalfFloat => General::H,
+        RegisterType::SingleFloat => General::S,
+        RegisterType::DoubleFloat => General::D,
+        RegisterType::Quad => General::Q,
+        RegisterType::StackPointer => return Ok(arm64::Register::Special(arm64::Special::SP)),
+        RegisterType::WordStackPointer => return Ok(arm64::Register::Special(arm64::Special::Wsp)),
+        RegisterType::XZR => return Ok(arm64::Register::Special(arm64::Special::Xzr)),
+        RegisterType::WZR => return Ok(arm64::Register::Special(arm64::Special::Wzr)),
+        RegisterType::Vector(_)
+        | RegisterType::Element(_)
+        | RegisterType::Lane(..)
+        | RegisterType::LaneGroup(..) => return Err(format!("unsupported SIMD register `v{n}`")),
+    };
+    Ok(arm64::Register::General { ty, n })
+}
+
+fn lower_shifted_register(reg: &ShiftedRegister) -> Result<arm64::Regoperand, String> {
+    let shift = match reg.shift_type {
+        ShiftType::LSL => arm64::Shift::Lsl,
+        ShiftType::LSR => arm64::Shift::Lsr,
+        ShiftType::ASR => arm64::Shift::Asr,
+        ShiftType::ROR => arm64::Shift::Ror,
+        ShiftType::RRX => return Err("unsupported shift `rrx`".to_string()),
+    };
+    let amount = match &reg.shift_amount {
+        Some(ShiftAmount::Immediate(imm)) => lower_shift_amount(imm)?,
+        Some(ShiftAmount::Register(_)) => return Err("unsupported shift by register".to_string()),
+        None => 0,
+    };
+    Ok(arm64::Regoperand::ShiftReg(
+        lower_register(&reg.reg)?,
+        (amount, shift),
+    ))
+}
+
+fn lower_extended_register(reg: &ExtendedRegister) -> Result<arm64::Regoperand, String> {
+    let extend = match reg.extend_type {
+        ExtendType::UXTB => arm64::Extend::Uxtb,
+        ExtendType::UXTH => arm64::Extend::Uxth,
+        ExtendType::UXTW => arm64::Extend::Uxtw,
+        ExtendType::UXTX => arm64::Extend::Uxtx,
+        ExtendType::SXTB => arm64::Extend::Sxtb,
+        ExtendType::SXTH => arm64::Extend::Sxth,
+        ExtendType::SXTW => arm64::Extend::Sxtw,
+        ExtendType::SXTX => arm64::Extend::Sxtx,
+    };
+    let amount = match &reg.shift_amount {
+        Some(imm) => lower_shift_amount(imm)?,
+        None => 0,
+    };
+    Ok(arm64::Regoperand::ExtendReg(
+        lower_register(&reg.reg)?,
+        (amount, extend),
+    ))
+}
+
+fn lower_indirect(indirect: &Indirect) -> Result<arm64::Addressing, String> {
+    let reg = lower_register(&indirect.base)?;
+    let (offset, index) = match &indirect.mode {
+        AddressMode::Base => return Ok(arm64::Addressing::BaseRegister(reg)),
+        AddressMode::Offset(Offset::Immediate(imm)) => (imm, None),
+        AddressMode::PreIndex(imm) => (imm, Some(arm64::Index::Pre)),
+        AddressMode::PostIndex(imm) => (imm, Some(arm64::Index::Post)),
+        AddressMode::Offset(Offset::ProcLoad(load)) => {
+            let reloc = lower_reloc(load)?;
+            return Ok(arm64::Addressing::Reloc { reg, reloc });
+        }
+        AddressMode::Offset(_) | AddressMode::PostIndexRegister(_) => {
+            return Err("unsupported register offset".to_string())
+        }
+    };
+    Ok(arm64::Addressing::Offset {
+        offset: lower_int(offset)?,
+        reg,
+        index,
+    })
+}
+
+fn lower_int(imm: &Immediate) -> Result<i64, String> {
+    imm.as_int()
+        .ok_or_else(|| "unsupported floating-point immediate".to_string())
+}
+
+fn lower_reloc(load: &ProcLoad) -> Result<arm64::Reloc, String> {
+    use arm64::RelocOperator::*;
+    let operator = match load.modifier {
+        RelocModifier::Lo12 => Lo12,
+        RelocModifier::PgHi21Nc => PgHi21Nc,
+        RelocModifier::Got => Got,
+        RelocModifier::GotLo12 => GotLo12,
+        RelocModifier::AbsG0 => AbsG0,
+        RelocModifier::AbsG0Nc => AbsG0Nc,
+        RelocModifier::AbsG0S => AbsG0S,
+        RelocModifier::AbsG1 => AbsG1,
+        RelocModifier::AbsG1Nc => AbsG1Nc,
+        RelocModifier::AbsG1S => AbsG1S,
+        RelocModifier::AbsG2 => AbsG2,
+        RelocModifier::AbsG2Nc => AbsG2Nc,
+        RelocModifier::AbsG2S => AbsG2S,
+        RelocModifier::AbsG3 => AbsG3,
+        modifier => {
+            return Err(format!(
+                "unsupported relocation operator `:{}:`",
+                modifier.name()
+            ))
+        }
+    };
+    let (symbol, addend) = load
+        .expr
+        .as_symbol_offset()
+        .ok_or_else(|| "unsupported symbol expression".to_string())?;
+    Ok(arm64::Reloc {
+        operator,
+        symbol: symbol.to_string(),
+        addend,
+    })
+}
+
+fn lower_shift_amount(imm: &Immediate) -> Result<u8, String> {
+    let n = lower_int(imm)?;
+    u8::try_from(n)
+        .ok()
+        .filter(|&n| n < 64)
+        .ok_or_else(|| format!("shift amount {n} out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::{expect, Expect};
+
+    fn check(src: &str, expect: Expect) {
+        let lines = armparser::parse_asm(src).unwrap();
+        let res = lines
+            .iter()
+            .map(|line| match lower_line(src, line) {
+                Ok(Some(inst)) => format!("{inst:?}"),
+                Ok(None) => "-".to_string(),
+                Err(e) => e.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        expect.assert_eq(&res);
+    }
+
+    #[test]
+    fn lower_lines() {
+        check(
+            "main:
+\tstp\tx29, x30, [sp, -16]!
+\tadd\tx0, x1, x2, lsl #3
+\tsub\tw0, w1, w2, sxtw #2
+\tmovk\tx0, 0x1234, lsl 16
+\tldr\tx0, [x1, 8]
+\tb.ne\tmain
+\tret
+\tcsel\tx0, x1, x2, eq
+\tmov\tx0, 70000
+\tmov\tx0, 281474972516352
+\tldr\tx0, [sp, -48]
+\tadd\tx0, x0, :lo12:.LC2+8
+\tldr\tx0, [x0, #:got_lo12:stdout]
+\tadrp\tx0, :tlsgd:var
+\tldnp\tx0, x1, [sp]
+",
+            expect![[r#"
+                -
+                Instruction { opcode: Stp, operand: [Reg(Reg(General { ty: X, n: 29 })), Reg(Reg(Special(LR))), Addressing(Offset { offset: -16, reg: Special(SP), index: Some(Pre) })] }
+                Instruction { opcode: Add, operand: [Reg(Reg(General { ty: X, n: 0 })), Reg(Reg(General { ty: X, n: 1 })), Reg(ShiftReg(General { ty: X, n: 2 }, (3, Lsl)))] }
+                Instruction { opcode: Sub, operand: [Reg(Reg(General { ty: W, n: 0 })), Reg(Reg(General { ty: W, n: 1 })), Reg(ExtendReg(General { ty: W, n: 2 }, (2, Sxtw)))] }
+                Instruction { opcode: Mvk, operand: [Reg(Reg(General { ty: X, n: 0 })), Imm { imm: 4660, shift: Some((16, Lsl)) }] }
+                Instruction { opcode: Ldr, operand: [Reg(Reg(General { ty: X, n: 0 })), Addressing(Offset { offset: 8, reg: General { ty: X, n: 1 }, index: None })] }
+                Instruction { opcode: Bne, operand: [Label("main")] }
+                Instruction { opcode: Ret, operand: [] }
+                Instruction { opcode: Csel, operand: [Reg(Reg(General { ty: X, n: 0 })), Reg(Reg(General { ty: X, n: 1 })), Reg(Reg(General { ty: X, n: 2 })), Cond(Eq)] }
+                Instruction { opcode: Mov, operand: [Reg(Reg(General { ty: X, n: 0 })), Imm { imm: 70000, shift: None }] }
+                Instruction { opcode: Mov, operand: [Reg(Reg(General { ty: X, n: 0 })), Imm { imm: 281474972516352, shift: None }] }
+                Instruction { opcode: Ldr, operand: [Reg(Reg(General { ty: X, n: 0 })), Addressing(Offset { offset: -48, reg: Special(SP), index: None })] }
+                Instruction { opcode: Add, operand: [Reg(Reg(General { ty: X, n: 0 })), Reg(Reg(General { ty: X, n: 0 })), Reloc(Reloc { operator: Lo12, symbol: ".LC2", addend: 8 })] }
+                Instruction { opcode: Ldr, operand: [Reg(Reg(General { ty: X, n: 0 })), Addressing(Reloc { reg: General { ty: X, n: 0 }, reloc: Reloc { operator: GotLo12, symbol: "stdout", addend: 0 } })] }
+                line 15: unsupported relocation operator `:tlsgd:`: `adrp	x0, :tlsgd:var`
+                line 16: unsupported instruction `ldnp`: `ldnp	x0, x1, [sp]`"#]],
+        );
+    }
+}
@@ -1,3 +1,6 @@
+pub mod decode;
+pub mod elf;
+pub mod lower;
 pub mod translate;
 
 fn main() {
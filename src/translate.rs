@@ -1,6 +1,16 @@
 use arm64::Operand;
 use asm_inst::*;
-use either::Either;
+
+/// An A64 instruction with no RISC-V sequence to translate to.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TranslateError {
+    #[error("no translation for `{0}`")]
+    Opcode(arm64::Opcode),
+    #[error("no RISC-V counterpart for relocation `{0}`")]
+    Reloc(arm64::Reloc),
+    #[error("unsupported operand `{0}`")]
+    Operand(arm64::Operand),
+}
 pub fn basic_arm2riscv_reg_map(reg: &arm64::Register) -> riscv::Register {
     match reg {
         arm64::Register::General { ty, n } => match ty {
@@ -10,7 +20,7 @@ pub fn basic_arm2riscv_reg_map(reg: &arm64::Register) -> riscv::Register {
         },
         arm64::Register::Special(special) => match special {
             arm64::Special::Xzr | arm64::Special::Wzr => riscv::Register::X(0),
-            arm64::Special::SP => riscv::Register::X(8),
+            arm64::Special::SP | arm64::Special::Wsp => riscv::Register::X(8),
             arm64::Special::LR => riscv::Register::X(1),
         },
     }
@@ -24,8 +34,10 @@ fn map_operand_to_reg(op: &Option<Operand>) -> Option<riscv::Register> {
                 _ => None,
             },
             Operand::Imm { .. } => None,
+            Operand::Reloc(_) => None,
             Operand::Addressing(_) => None,
             Operand::Label(_) => None,
+            Operand::Cond(_) => None,
         })
         .map(basic_arm2riscv_reg_map)
 }
@@ -48,7 +60,7 @@ impl Arm64Translator {
     pub fn new(inst: arm64::Instruction) -> Self {
         let opcode = inst.opcode.clone();
         let mut riscv_inst = Vec::new();
-        let rd = inst.operand.get(0).cloned().and_then(|x| match x {
+        let rd = inst.operand.first().cloned().and_then(|x| match x {
             Operand::Reg(reg) => Some(reg),
             _ => None,
         });
@@ -56,20 +68,23 @@ impl Arm64Translator {
         let rs2 = inst.operand.get(2).cloned();
         let temp_reg = rs2.as_ref().and_then(|o| match o {
             Operand::Reg(_) => None,
-            Operand::Imm { imm, shift } => check_imm(&mut riscv_inst, *imm, shift.clone()),
+            Operand::Imm { imm, shift } => check_imm(&mut riscv_inst, *imm, shift),
+            Operand::Reloc(_) => None,
             Operand::Addressing(_) => None,
             Operand::Label(_) => None,
+            Operand::Cond(_) => None,
         });
         let rs3 = inst.operand.get(3).cloned();
 
-        let label = match inst.operand.get(0) {
+        let label = match inst.operand.first() {
             Some(arm64::Operand::Label(ref l)) => Some(l.to_string()),
             _ => None,
         };
-        let iflag = inst.operand.iter().any(|x| match x {
-            arm64::Operand::Imm { .. } => true,
-            _ => false,
-        }) && temp_reg.is_none();
+        let iflag = inst
+            .operand
+            .iter()
+            .any(|x| matches!(x, arm64::Operand::Imm { .. }))
+            && temp_reg.is_none();
 
         let wflag = inst.operand.iter().all(|x| match x {
             arm64::Operand::Reg(reg) => reg.is_word(),
@@ -119,30 +134,23 @@ impl Arm64Translator {
             _ => None,
         })
     }
-    fn map_rs3_reg(&self) -> Option<arm64::Regoperand> {
-        self.rs3.as_ref().and_then(|o| match o {
-            Operand::Reg(reg) => Some(reg.clone()),
-            _ => None,
-        })
-    }
 
-    fn rs2_as_imm(&self) -> u16 {
-        let imm = self
-            .rs2
-            .clone()
+    /// The immediate `rs2`, shifted into place.
+    fn rs2_as_imm(&self) -> i64 {
+        self.rs2
+            .as_ref()
             .and_then(|o| match o {
-                Operand::Imm { imm, .. } => Some(imm),
+                Operand::Imm { imm, shift } => Some(shifted(*imm, shift)),
                 _ => None,
             })
-            .unwrap();
-        imm
+            .unwrap()
     }
 
     /// `add` instruction
     /// - add immediate
     /// - add shift register
     /// - add extended register(todo)
-    pub fn add(&self, res: &mut Vec<riscv::Instruction>) {
+    pub fn add(&self, res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
         let opcode = match (self.iflag, self.wflag) {
             (true, true) => riscv::Opcode::Addiw,
             (true, false) => riscv::Opcode::Addi,
@@ -151,12 +159,14 @@ impl Arm64Translator {
         };
         let rd = self.map_rd().unwrap();
         let rs1 = self.map_rs1().unwrap();
-        if self.iflag {
-            let imm = self.rs2_as_imm();
-            let imm = riscv::Immediate::Number(imm as i16);
-            res.push(riscv::Instruction::new_i(opcode, rd, rs1, imm.clone()));
+        if let Some(Operand::Reloc(reloc)) = &self.rs2 {
+            let imm = map_reloc(reloc)?;
+            res.push(riscv::Instruction::new_i(opcode.to_imm(), rd, rs1, imm));
+        } else if self.iflag {
+            let imm = riscv::Immediate::Number(self.rs2_as_imm());
+            res.push(riscv::Instruction::new_i(opcode, rd, rs1, imm));
             if self.opcode == arm64::Opcode::Adds {
-                emit_set_flag(res, rd, rs1, Either::Right(imm));
+                emit_set_flag(res, rd, rs1);
             }
         } else {
             let rs2 = if let Some(reg) = self.imm_temp_rsg {
@@ -168,12 +178,13 @@ impl Arm64Translator {
 
             res.push(riscv::Instruction::new_r(opcode, rd, rs1, rs2));
             if self.opcode == arm64::Opcode::Adds {
-                emit_set_flag(res, rd, rs1, Either::Left(rs2));
+                emit_set_flag(res, rd, rs1);
             }
         }
+        Ok(())
     }
 
-    pub fn sub(&self, res: &mut Vec<riscv::Instruction>) {
+    pub fn sub(&self, res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
         let opcode = match (self.iflag, self.wflag) {
             (true, true) => riscv::Opcode::Addiw,
             (true, false) => riscv::Opcode::Addi,
@@ -182,10 +193,12 @@ impl Arm64Translator {
         };
         let rd = self.map_rd().unwrap();
         let rs1 = self.map_rs1().unwrap();
+        if let Some(Operand::Reloc(reloc)) = &self.rs2 {
+            return Err(TranslateError::Reloc(reloc.clone()));
+        }
         if self.iflag {
-            let imm = self.rs2_as_imm();
-            let imm = riscv::Immediate::Number(-(imm as i16));
-            res.push(riscv::Instruction::new_i(opcode, rd, rs1, imm.clone()));
+            let imm = riscv::Immediate::Number(-self.rs2_as_imm());
+            res.push(riscv::Instruction::new_i(opcode, rd, rs1, imm));
         } else {
             let rs2 = if let Some(reg) = self.imm_temp_rsg {
                 reg
@@ -195,7 +208,7 @@ impl Arm64Translator {
             };
             res.push(riscv::Instruction::new_r(opcode, rd, rs1, rs2));
             if self.opcode == arm64::Opcode::Subs {
-                emit_set_flag(res, rd, rs1, Either::Left(rs2));
+                emit_set_flag(res, rd, rs1);
                 res.push(riscv::Instruction::new_i(
                     riscv::Opcode::Xori,
                     riscv::Register::T5,
@@ -204,8 +217,9 @@ impl Arm64Translator {
                 ));
             }
         }
+        Ok(())
     }
-    pub fn madd(&self, res: &mut Vec<riscv::Instruction>) {
+    pub fn madd(&self, res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
         let rd = self.map_rd().unwrap();
         let rs1 = self.map_rs1().unwrap();
         let rs2 = self.map_rs2().unwrap();
@@ -213,29 +227,33 @@ impl Arm64Translator {
 
         res.push(riscv::Instruction::new_r(riscv::Opcode::Mul, rd, rs1, rs2));
         res.push(riscv::Instruction::new_r(riscv::Opcode::Add, rd, rd, rs3));
+        Ok(())
     }
 
-    pub fn mul(&self, res: &mut Vec<riscv::Instruction>) {
+    pub fn mul(&self, res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
         let rd = self.map_rd().unwrap();
         let rs1 = self.map_rs1().unwrap();
         let rs2 = self.map_rs2().unwrap();
         res.push(riscv::Instruction::new_r(riscv::Opcode::Mul, rd, rs1, rs2));
+        Ok(())
     }
 
-    pub fn sdiv(&self, res: &mut Vec<riscv::Instruction>) {
+    pub fn sdiv(&self, res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
         let rd = self.map_rd().unwrap();
         let rs1 = self.map_rs1().unwrap();
         let rs2 = self.map_rs2().unwrap();
         res.push(riscv::Instruction::new_r(riscv::Opcode::Div, rd, rs1, rs2));
+        Ok(())
     }
-    pub fn udiv(&self, res: &mut Vec<riscv::Instruction>) {
+    pub fn udiv(&self, res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
         let rd = self.map_rd().unwrap();
         let rs1 = self.map_rs1().unwrap();
         let rs2 = self.map_rs2().unwrap();
         res.push(riscv::Instruction::new_r(riscv::Opcode::Divu, rd, rs1, rs2));
+        Ok(())
     }
 
-    pub fn mov(&self, res: &mut Vec<riscv::Instruction>) {
+    pub fn mov(&self, res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
         let rd = self.map_rd().unwrap();
         let rs1 = self.rs1.as_ref().unwrap();
         match rs1 {
@@ -249,13 +267,8 @@ impl Arm64Translator {
                 ));
             }
             Operand::Imm { imm, shift } => {
-                let imm = riscv::Immediate::Number(*imm as i16);
-                res.push(riscv::Instruction::new_i(
-                    riscv::Opcode::Addi,
-                    rd,
-                    riscv::Register::ZERO,
-                    imm,
-                ));
+                let imm = riscv::Immediate::Number(shifted(*imm, shift));
+                res.push(riscv::Instruction::new_u(riscv::Opcode::Li, rd, imm));
             }
 
             Operand::Label(s) => {
@@ -263,18 +276,22 @@ impl Arm64Translator {
                     riscv::Opcode::Lui,
                     rd,
                     riscv::Register::ZERO,
-                    riscv::Immediate::Label(s.clone()),
+                    riscv::Immediate::Reloc {
+                        operator: riscv::RelocOperator::Hi,
+                        symbol: s.clone(),
+                        addend: 0,
+                    },
                 ));
             }
-            _ => {
-                unreachable!()
-            }
+            Operand::Reloc(reloc) => return Err(TranslateError::Reloc(reloc.clone())),
+            operand => return Err(TranslateError::Operand(operand.clone())),
         }
+        Ok(())
     }
 
-    pub fn mov_not(&self, res: &mut Vec<riscv::Instruction>) {
+    pub fn mov_not(&self, res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
         let rd = self.map_rd().unwrap();
-        self.mov(res);
+        self.mov(res)?;
         //按位取反
         res.push(riscv::Instruction::new_i(
             riscv::Opcode::Xori,
@@ -282,26 +299,27 @@ impl Arm64Translator {
             rd,
             riscv::Immediate::Number(-1),
         ));
+        Ok(())
     }
 
-    pub fn mov_zero(&self, res: &mut Vec<riscv::Instruction>) {
-        let rd = self.map_rd().unwrap();
-        self.mov(res);
+    pub fn mov_zero(&self, res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
+        self.mov(res)
     }
-    pub fn mov_keep(&self, res: &mut Vec<riscv::Instruction>) {
-        unimplemented!()
+    pub fn mov_keep(&self, _res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
+        Err(TranslateError::Opcode(self.opcode.clone()))
     }
 
-    pub fn branch(&self, res: &mut Vec<riscv::Instruction>) {
+    pub fn branch(&self, res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
         let label = self.label.as_ref().unwrap();
         res.push(riscv::Instruction::new_u(
             riscv::Opcode::Jal,
             riscv::Register::ZERO,
             riscv::Immediate::Label(label.clone()),
         ));
+        Ok(())
     }
 
-    pub fn shift(&self, res: &mut Vec<riscv::Instruction>) {
+    pub fn shift(&self, res: &mut Vec<riscv::Instruction>) -> Result<(), TranslateError> {
         let rd = self.map_rd().unwrap();
         let rs1 = self.map_rs1().unwrap();
         let rs2 = self.rs2.as_ref().unwrap();
@@ -314,54 +332,64 @@ impl Arm64Translator {
 
         match rs2 {
             Operand::Imm { imm, .. } => {
-                let imm = *imm;
                 res.push(riscv::Instruction::new_i(
                     opcode.to_imm(),
                     rd,
                     rs1,
-                    riscv::Immediate::Number(imm as i16),
+                    riscv::Immediate::Number(*imm),
                 ));
             }
             Operand::Reg(reg) => {
                 let rs2 = emit_shift_or_extend(res, reg);
                 res.push(riscv::Instruction::new_r(opcode, rd, rs1, rs2));
             }
-            _ => {
-                unreachable!()
-            }
+            operand => return Err(TranslateError::Operand(operand.clone())),
         }
+        Ok(())
     }
 }
 
-fn shift_imm(
-    res: &mut Vec<riscv::Instruction>,
-    imm: u16,
-    shift: Option<(u8, arm64::Shift)>,
-) -> Either<u16, riscv::Register> {
-    todo!()
+/// The value of an A64 immediate with its optional `lsl`.
+fn shifted(imm: i64, shift: &Option<(u8, arm64::Shift)>) -> i64 {
+    match shift {
+        Some((amount, arm64::Shift::Lsl)) => imm << amount,
+        Some((_, shift)) => unreachable!("immediate shifted by `{shift}`"),
+        None => imm,
+    }
 }
+
+/// The RISC-V operand for an A64 relocation, for the operators that have
+/// one.
+fn map_reloc(reloc: &arm64::Reloc) -> Result<riscv::Immediate, TranslateError> {
+    match reloc.operator {
+        arm64::RelocOperator::Lo12 => Ok(riscv::Immediate::Reloc {
+            operator: riscv::RelocOperator::Lo,
+            symbol: reloc.symbol.clone(),
+            addend: reloc.addend,
+        }),
+        _ => Err(TranslateError::Reloc(reloc.clone())),
+    }
+}
+
+/// Loads an immediate that doesn't fit a 12-bit field into `t0`, giving
+/// the register to use in its place.
 pub fn check_imm(
     res: &mut Vec<riscv::Instruction>,
-    imm: u16,
-    shift: Option<(u8, arm64::Shift)>,
+    imm: i64,
+    shift: &Option<(u8, arm64::Shift)>,
 ) -> Option<riscv::Register> {
-    if imm > 0b111111111111 {
-        let rd = riscv::Register::T0;
-        let imm = riscv::Immediate::Number(imm as i16);
-        res.push(riscv::Instruction::new_i(
-            riscv::Opcode::Lui,
-            rd,
-            riscv::Register::ZERO,
-            imm,
-        ));
-        if let Some((s, sh)) = shift {
-            emit_shift_reg(res, rd, (s, sh));
-        }
-
-        Some(rd)
-    } else {
-        None
+    let value = shifted(imm, shift);
+    // `sub` negates the immediate, so both signs have to fit
+    if value.unsigned_abs() < 1 << 11 {
+        return None;
     }
+    let rd = riscv::Register::T0;
+    res.push(riscv::Instruction::new_u(
+        riscv::Opcode::Li,
+        rd,
+        riscv::Immediate::Number(value),
+    ));
+    Some(rd)
 }
 
 pub fn emit_shift_reg(
@@ -410,12 +438,7 @@ pub fn emit_extend_reg(
     res.push(inst1);
     res.push(inst2);
 }
-pub fn emit_set_flag(
-    res: &mut Vec<riscv::Instruction>,
-    rd: riscv::Register,
-    rs1: riscv::Register,
-    rm: Either<riscv::Register, riscv::Immediate>,
-) {
+pub fn emit_set_flag(res: &mut Vec<riscv::Instruction>, rd: riscv::Register, rs1: riscv::Register) {
     emit_set_nz_flag(res, rd);
     // set carry flag
     res.push(riscv::Instruction::new_r(
@@ -451,15 +474,85 @@ pub fn emit_shift_or_extend(
 ) -> riscv::Register {
     match reg {
         arm64::Regoperand::ShiftReg(register, x) => {
-            let riscv_rg = basic_arm2riscv_reg_map(&register);
+            let riscv_rg = basic_arm2riscv_reg_map(register);
             emit_shift_reg(res, riscv_rg, x.clone());
             riscv_rg
         }
         arm64::Regoperand::ExtendReg(register, x) => {
-            let riscv_rg = basic_arm2riscv_reg_map(&register);
+            let riscv_rg = basic_arm2riscv_reg_map(register);
             emit_extend_reg(res, riscv_rg, x.clone());
             riscv_rg
         }
-        arm64::Regoperand::Reg(register) => basic_arm2riscv_reg_map(&register),
+        arm64::Regoperand::Reg(register) => basic_arm2riscv_reg_map(register),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    type Translate =
+        fn(&Arm64Translator, &mut Vec<riscv::Instruction>) -> Result<(), TranslateError>;
+
+    fn translate(src: &str, f: Translate) -> String {
+        let lines = armparser::parse_asm(src).unwrap();
+        let inst = crate::lower::lower(src, &lines).unwrap().remove(0);
+        let translator = Arm64Translator::new(inst);
+        let mut res = translator.riscv_inst.clone();
+        match f(&translator, &mut res) {
+            Ok(()) => res.iter().map(|inst| format!("{inst}\n")).collect(),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn translate_immediates() {
+        expect![[r#"
+            li	a0, 281474972516352
+        "#]]
+        .assert_eq(&translate("mov x10, 281474972516352", Arm64Translator::mov));
+        expect![[r#"
+            li	a0, 305397760
+        "#]]
+        .assert_eq(&translate("movz x10, 0x1234, lsl 16", Arm64Translator::mov));
+        expect![[r#"
+            li	t0, 4096
+            add	a0, a1, t0
+        "#]]
+        .assert_eq(&translate(
+            "add x10, x11, #1, lsl #12",
+            Arm64Translator::add,
+        ));
+        expect![[r#"
+            li	t0, 70000
+            sub	a0, a1, t0
+        "#]]
+        .assert_eq(&translate("sub x10, x11, 70000", Arm64Translator::sub));
+        expect![[r#"
+            addi	a0, a1, -2047
+        "#]]
+        .assert_eq(&translate("sub x10, x11, 2047", Arm64Translator::sub));
+        expect![[r#"
+            addi	a0, a0, %lo(.LC0)
+        "#]]
+        .assert_eq(&translate("add x10, x10, :lo12:.LC0", Arm64Translator::add));
+        expect![[r#"
+            addi	a0, a0, %lo(.LC2+8)
+        "#]]
+        .assert_eq(&translate(
+            "add x10, x10, :lo12:.LC2+8",
+            Arm64Translator::add,
+        ));
+        expect!["no RISC-V counterpart for relocation `:got_lo12:sym`"].assert_eq(&translate(
+            "add x10, x10, :got_lo12:sym",
+            Arm64Translator::add,
+        ));
+        expect!["no RISC-V counterpart for relocation `:abs_g1:sym`"]
+            .assert_eq(&translate("movz x10, #:abs_g1:sym", Arm64Translator::mov));
+        expect!["no RISC-V counterpart for relocation `:abs_g0_nc:sym`"].assert_eq(&translate(
+            "movn x10, #:abs_g0_nc:sym",
+            Arm64Translator::mov_not,
+        ));
     }
 }